@@ -1,3 +1,5 @@
 fn main() {
-    embuild::espidf::sysenv::output();
+    if std::env::var_os("CARGO_FEATURE_HAL").is_some() {
+        embuild::espidf::sysenv::output();
+    }
 }
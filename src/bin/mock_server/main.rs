@@ -0,0 +1,261 @@
+// Mock of the Elisys Home Automation Server, used to exercise the station on a Linux host.
+// It implements the endpoints used by the station, records the payloads it receives and
+// can inject failures, delays and malformed responses.
+//
+// cargo run --bin mock_server --no-default-features --features std,mock-server \
+//     --target x86_64-unknown-linux-gnu -- --port 8080 [--mqtt-broker mqtt://localhost:1883]
+//
+// --port 0 listens on a free port, printed at startup
+//
+// GET    /mock/received   payloads received so far
+// DELETE /mock/received   forgets the received payloads
+// GET    /mock/behaviours failures that will be injected
+// POST   /mock/behaviours adds a failure, e.g. {"endpoint": "submit", "status": 503, "times": 2}
+// DELETE /mock/behaviours removes all the failures
+//
+// The registration issues a token, that the device must then send as "Authorization: Bearer"
+mod http;
+mod state;
+
+use http::{read_request, write_response, Request};
+use serde_json::{json, Map, Value};
+use state::{Behaviour, Command, State};
+use std::{
+    io::{self, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+const DEFAULT_PORT: u16 = 8080;
+// idle kept alive connections are closed after this time
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+const REGISTER_PATH: &str = "/api/v1/device/register";
+const CONFIGURATION_PATH: &str = "/api/v1/weather-sensor/configuration";
+const CONFIGURATION_ERRORS_PATH: &str = "/api/v1/weather-sensor/configuration/errors";
+const I_AM_ALIVE_PATH: &str = "/api/v1/i-am-alive/notify";
+const SUBMIT_PATH: &str = "/api/v1/weather-sensor/submit";
+
+fn main() {
+    let port = parse_option("--port")
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let listener = TcpListener::bind(("0.0.0.0", port)).expect("unable to bind the port");
+    // with --port 0 the system picks a free port, the tests read it from this line
+    let port = listener.local_addr().map_or(port, |address| address.port());
+    println!("mock server listening on port {}", port);
+
+    let mut state = State::default();
+    state.mqtt_broker = parse_option("--mqtt-broker");
+    let state = Arc::new(Mutex::new(state));
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("connection error: {}", e);
+                continue;
+            }
+        };
+        let state = state.clone();
+        thread::spawn(move || {
+            if let Err(e) = serve(&stream, &state) {
+                eprintln!("error while handling the connection: {}", e);
+            }
+        });
+    }
+}
+
+fn parse_option(name: &str) -> Option<String> {
+    let arguments: Vec<String> = std::env::args().collect();
+    let index = arguments.iter().position(|argument| argument == name)?;
+    arguments.get(index + 1).cloned()
+}
+
+// handles the requests of a connection until the client closes it or it stays idle
+fn serve(stream: &TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(IDLE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream);
+    loop {
+        let request = match read_request(&mut reader) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut => {
+                return Ok(())
+            }
+            Err(e) => return Err(e),
+        };
+        handle(stream, state, &request)?;
+        if request.wants_close() {
+            return Ok(());
+        }
+    }
+}
+
+fn handle(stream: &TcpStream, state: &Mutex<State>, request: &Request) -> io::Result<()> {
+    println!("<- {} {}", request.method, request.path);
+
+    if request.path.starts_with("/mock/") {
+        return handle_control(stream, state, request);
+    }
+
+    let endpoint = match request.path.as_str() {
+        REGISTER_PATH => "register",
+        CONFIGURATION_PATH => "configuration",
+        CONFIGURATION_ERRORS_PATH => "configuration-errors",
+        I_AM_ALIVE_PATH => "i-am-alive",
+        SUBMIT_PATH => "submit",
+        _ => return write_response(stream, 404, "text/plain", b"not found"),
+    };
+    if request.method != "POST" {
+        return write_response(stream, 400, "text/plain", b"POST expected");
+    }
+
+    let authorization = request.headers.get("authorization").map(String::as_str);
+    let mac_address = serde_json::from_slice::<Value>(&request.body)
+        .ok()
+        .and_then(|body| body["macAddress"].as_str().map(str::to_owned))
+        .unwrap_or_default();
+    let (behaviour, status, body) = {
+        let mut state = state.lock().unwrap();
+        state.record(endpoint, &request.body, authorization);
+        let behaviour = state.take_behaviour(endpoint);
+        // a device can register again without its token, not with a wrong one
+        let authorized = state.is_authorized(&mac_address, authorization)
+            || (endpoint == "register" && authorization.is_none());
+        let (status, body) = match endpoint {
+            _ if !authorized => (401, String::new()),
+            "register" => (200, json!({ "token": state.issue_token(&mac_address) }).to_string()),
+            "configuration" => {
+                let mut configuration = configuration(request, state.mqtt_broker.as_deref());
+                for (field, value) in &state.configuration_overrides {
+                    configuration[field] = value.clone();
+                }
+                (200, configuration.to_string())
+            }
+            "i-am-alive" => {
+                let acknowledged = acknowledged_commands(request);
+                let commands = state.exchange_commands(&mac_address, &acknowledged);
+                (200, json!({ "commands": commands }).to_string())
+            }
+            _ => (200, String::new()),
+        };
+        (behaviour, status, body)
+    };
+    if let Some(behaviour) = &behaviour {
+        println!("injecting {:?}", behaviour);
+        thread::sleep(Duration::from_millis(behaviour.delay_millis));
+    }
+
+    let status = behaviour
+        .as_ref()
+        .and_then(|behaviour| behaviour.status)
+        .unwrap_or(status);
+    if behaviour.map(|behaviour| behaviour.malformed) == Some(true) {
+        return write_response(stream, status, "application/json", b"{\"truncated");
+    }
+    write_response(stream, status, "application/json", body.as_bytes())
+}
+
+// ids of the commands acknowledged by the I-am-alive message
+fn acknowledged_commands(request: &Request) -> Vec<String> {
+    let body = serde_json::from_slice::<Value>(&request.body).unwrap_or_default();
+    body["acknowledgedCommands"]
+        .as_array()
+        .map(|acknowledgements| {
+            acknowledgements
+                .iter()
+                .filter_map(|acknowledgement| acknowledgement["id"].as_str().map(str::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// the endpoints of the configuration point back to the mock server
+fn configuration(request: &Request, mqtt_broker: Option<&str>) -> Value {
+    let host = request
+        .headers
+        .get("host")
+        .cloned()
+        .unwrap_or_else(|| format!("localhost:{}", DEFAULT_PORT));
+    let mut configuration = json!({
+        "schemaVersion": 1,
+        "alertEndpoint": format!("http://{}{}", host, SUBMIT_PATH),
+        "iAmAliveEndpoint": format!("http://{}{}", host, I_AM_ALIVE_PATH),
+        "temperatureSensorUnitOfMeasure": "C",
+        "weatherSensorSupplyIntervalSeconds": 10,
+        "iAmAliveIntervalSeconds": 5
+    });
+    if let Some(mqtt_broker) = mqtt_broker {
+        configuration["transport"] = json!("mqtt");
+        configuration["mqtt"] = json!({ "brokerUrl": mqtt_broker });
+    }
+    configuration
+}
+
+fn handle_control(
+    stream: &TcpStream,
+    state: &Mutex<State>,
+    request: &Request,
+) -> io::Result<()> {
+    let mut state = state.lock().unwrap();
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/mock/received") => {
+            let body = serde_json::to_vec(state.received()).unwrap();
+            write_response(stream, 200, "application/json", &body)
+        }
+        ("DELETE", "/mock/received") => {
+            state.clear_received();
+            write_response(stream, 204, "text/plain", b"")
+        }
+        ("GET", "/mock/behaviours") => {
+            let body = serde_json::to_vec(state.behaviours()).unwrap();
+            write_response(stream, 200, "application/json", &body)
+        }
+        ("POST", "/mock/behaviours") => match serde_json::from_slice::<Behaviour>(&request.body) {
+            Ok(behaviour) => {
+                state.add_behaviour(behaviour);
+                write_response(stream, 204, "text/plain", b"")
+            }
+            Err(e) => write_response(stream, 400, "text/plain", e.to_string().as_bytes()),
+        },
+        ("DELETE", "/mock/behaviours") => {
+            state.clear_behaviours();
+            write_response(stream, 204, "text/plain", b"")
+        }
+        ("GET", "/mock/commands") => {
+            let body = serde_json::to_vec(state.commands()).unwrap();
+            write_response(stream, 200, "application/json", &body)
+        }
+        ("POST", "/mock/commands") => match serde_json::from_slice::<Command>(&request.body) {
+            Ok(command) => {
+                let body = serde_json::to_vec(&state.add_command(command)).unwrap();
+                write_response(stream, 200, "application/json", &body)
+            }
+            Err(e) => write_response(stream, 400, "text/plain", e.to_string().as_bytes()),
+        },
+        ("DELETE", "/mock/commands") => {
+            state.clear_commands();
+            write_response(stream, 204, "text/plain", b"")
+        }
+        ("GET", "/mock/configuration") => {
+            let body = serde_json::to_vec(&state.configuration_overrides).unwrap();
+            write_response(stream, 200, "application/json", &body)
+        }
+        ("POST", "/mock/configuration") => {
+            match serde_json::from_slice::<Map<String, Value>>(&request.body) {
+                Ok(overrides) => {
+                    state.configuration_overrides.extend(overrides);
+                    write_response(stream, 204, "text/plain", b"")
+                }
+                Err(e) => write_response(stream, 400, "text/plain", e.to_string().as_bytes()),
+            }
+        }
+        ("DELETE", "/mock/configuration") => {
+            state.configuration_overrides.clear();
+            write_response(stream, 204, "text/plain", b"")
+        }
+        _ => write_response(stream, 404, "text/plain", b"not found"),
+    }
+}
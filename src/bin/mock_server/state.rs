@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize, Clone)]
+pub struct ReceivedPayload {
+    pub endpoint: String,
+    #[serde(rename = "receivedAtMillis")]
+    pub received_at_millis: u128,
+    // the body as JSON, or as a string if it is not valid JSON
+    pub body: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorization: Option<String>,
+}
+
+// failure injected on the requests of an endpoint ("*" for every endpoint)
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Behaviour {
+    pub endpoint: String,
+    // status returned instead of the normal one
+    pub status: Option<u16>,
+    #[serde(rename = "delayMillis", default)]
+    pub delay_millis: u64,
+    // if true, the response body is not valid JSON
+    #[serde(default)]
+    pub malformed: bool,
+    // how many requests are affected, forever if absent
+    pub times: Option<u32>,
+}
+
+// command returned to the devices with the I-am-alive response until they acknowledge
+// it, e.g. `{"type": "identify"}`
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Command {
+    // assigned by the mock server if absent
+    #[serde(default)]
+    pub id: String,
+    #[serde(rename = "type")]
+    pub command_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Value>,
+    // only this device receives the command, every device if absent
+    #[serde(rename = "macAddress", skip_serializing_if = "Option::is_none")]
+    pub mac_address: Option<String>,
+}
+
+#[derive(Default)]
+pub struct State {
+    received: Vec<ReceivedPayload>,
+    behaviours: Vec<Behaviour>,
+    // token issued to each MAC address at the registration
+    tokens: HashMap<String, String>,
+    issued_tokens: u64,
+    commands: Vec<Command>,
+    issued_commands: u64,
+    // if set the configuration selects the MQTT transport with this broker
+    pub mqtt_broker: Option<String>,
+    // fields that replace the ones of the generated configuration
+    pub configuration_overrides: Map<String, Value>,
+}
+
+impl State {
+    pub fn record(&mut self, endpoint: &str, body: &[u8], authorization: Option<&str>) {
+        let body = serde_json::from_slice(body)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(body).into_owned()));
+        self.received.push(ReceivedPayload {
+            endpoint: endpoint.to_owned(),
+            received_at_millis: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_millis())
+                .unwrap_or_default(),
+            body,
+            authorization: authorization.map(str::to_owned),
+        });
+    }
+
+    // true if the MAC address has no token yet or the request carries its token
+    pub fn is_authorized(&self, mac_address: &str, authorization: Option<&str>) -> bool {
+        match self.tokens.get(mac_address) {
+            None => true,
+            Some(token) => authorization == Some(format!("Bearer {}", token).as_str()),
+        }
+    }
+
+    pub fn issue_token(&mut self, mac_address: &str) -> String {
+        self.issued_tokens += 1;
+        let token = format!("mock-{}-{}", self.issued_tokens, mac_address.replace(':', ""));
+        self.tokens.insert(mac_address.to_owned(), token.clone());
+        token
+    }
+
+    pub fn received(&self) -> &[ReceivedPayload] {
+        &self.received
+    }
+
+    pub fn clear_received(&mut self) {
+        self.received.clear();
+    }
+
+    pub fn add_behaviour(&mut self, behaviour: Behaviour) {
+        self.behaviours.push(behaviour);
+    }
+
+    pub fn behaviours(&self) -> &[Behaviour] {
+        &self.behaviours
+    }
+
+    pub fn clear_behaviours(&mut self) {
+        self.behaviours.clear();
+    }
+
+    // the first behaviour that applies to the endpoint, consuming one of its times
+    pub fn take_behaviour(&mut self, endpoint: &str) -> Option<Behaviour> {
+        let index = self
+            .behaviours
+            .iter()
+            .position(|behaviour| behaviour.endpoint == endpoint || behaviour.endpoint == "*")?;
+        let behaviour = self.behaviours[index].clone();
+        if let Some(times) = self.behaviours[index].times.as_mut() {
+            *times = times.saturating_sub(1);
+            if *times == 0 {
+                self.behaviours.remove(index);
+            }
+        }
+        Some(behaviour)
+    }
+
+    pub fn add_command(&mut self, mut command: Command) -> Command {
+        if command.id.is_empty() {
+            self.issued_commands += 1;
+            command.id = format!("cmd-{}", self.issued_commands);
+        }
+        self.commands.push(command.clone());
+        command
+    }
+
+    pub fn commands(&self) -> &[Command] {
+        &self.commands
+    }
+
+    pub fn clear_commands(&mut self) {
+        self.commands.clear();
+    }
+
+    // forgets the acknowledged commands, returns the ones still waiting for the device
+    pub fn exchange_commands(
+        &mut self,
+        mac_address: &str,
+        acknowledged: &[String],
+    ) -> Vec<Command> {
+        self.commands.retain(|command| !acknowledged.contains(&command.id));
+        self.commands
+            .iter()
+            .filter(|command| match &command.mac_address {
+                None => true,
+                Some(target) => target == mac_address,
+            })
+            .cloned()
+            .collect()
+    }
+}
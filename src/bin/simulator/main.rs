@@ -0,0 +1,219 @@
+// Runs one or more simulated stations on a Linux host: each station has its own MAC
+// address and climate, and runs the same orchestration as the firmware with synthetic
+// light, temperature, humidity and pressure readings following a daily cycle.
+//
+// cargo run --bin simulator --no-default-features --features std,simulator \
+//     --target x86_64-unknown-linux-gnu -- --stations 20
+//
+// --stations N        number of stations (default 1)
+// --first-station N   number of the first station, used to build the MAC addresses (default 1)
+// --noise X           scale of the sensor noise, 0 disables it (default 1)
+// --dropout P         probability that a reading fails (default 0.02)
+// --time-scale X      speed of the simulated day, e.g. 1440 for a day per minute (default 1)
+// --seed N            seed of the climates and of the noise (default 0)
+// --log-level LEVEL   error, warn, info or debug (default info)
+// --server URL        server of the stations, e.g. http://localhost:8080 for the mock
+//                     (default: the URLs of src/config/config.rs)
+// --cycles N          each station stops after running its due tasks N times, e.g. for
+//                     the integration tests (default: the stations run forever)
+#[cfg(feature = "hal")]
+compile_error!("the simulator runs on a Linux host, build it with --no-default-features");
+
+// the modules of the firmware, not everything is used by the simulator
+#[allow(dead_code)]
+#[path = "../../config/mod.rs"]
+mod config;
+#[allow(dead_code)]
+#[path = "../../dto/mod.rs"]
+mod dto;
+#[allow(dead_code)]
+#[path = "../../error.rs"]
+mod error;
+#[allow(dead_code)]
+#[path = "../../hal/mod.rs"]
+mod hal;
+#[allow(dead_code)]
+#[path = "../../service/mod.rs"]
+mod service;
+#[allow(dead_code)]
+#[path = "../../util/mod.rs"]
+mod util;
+
+mod sensors;
+mod weather;
+
+use hal::http::HttpSettings;
+use hal::host::{
+    http::HostHttpTransport, led::HostStatusLed, mqtt::HostMqttConnector, network::HostNetworkLink,
+    ota::HostFirmwareUpdater, storage::InMemoryStorageProvider, system::HostSystemControl,
+};
+use hal::system::SystemControl;
+use log::{error, info, LevelFilter};
+use sensors::{
+    Imperfections, SimulatedLightSensor, SimulatedPressureSensor,
+    SimulatedTemperatureHumiditySensor,
+};
+use service::{
+    orchestrator_service::orchestrate_cycles,
+    peripheral_service::PeripheralService,
+    settings_service::{Settings, SettingsService},
+};
+use std::{rc::Rc, str::FromStr, thread, time::Duration};
+use weather::{Climate, Rng, SimulationClock};
+
+// the stations do not start all together
+const START_INTERVAL_MILLIS: u64 = 100;
+
+struct Options {
+    stations: u16,
+    first_station: u16,
+    imperfections: Imperfections,
+    time_scale: f64,
+    seed: u64,
+    log_level: LevelFilter,
+    server: Option<String>,
+    cycles: Option<u32>,
+}
+
+fn main() {
+    let options = parse_options();
+    if let Some(server) = &options.server {
+        if let Err(e) = Settings::default().with_server(server) {
+            eprintln!("invalid value for --server: {}", e);
+            std::process::exit(2);
+        }
+    }
+    util::host_logger::initialize_default();
+    log::set_max_level(options.log_level);
+
+    let clock = SimulationClock::new(options.time_scale);
+    let mut stations = Vec::new();
+    for number in options.first_station..options.first_station.saturating_add(options.stations) {
+        let imperfections = options.imperfections;
+        let seed = options.seed;
+        let server = options.server.clone();
+        let cycles = options.cycles;
+        let station = thread::Builder::new()
+            .name(format!("station-{}", number))
+            .spawn(move || run_station(number, clock, imperfections, seed, server, cycles))
+            .expect("unable to start the station");
+        stations.push(station);
+        thread::sleep(Duration::from_millis(START_INTERVAL_MILLIS));
+    }
+    for station in stations {
+        if station.join().is_err() {
+            error!("[simulator]: a station stopped unexpectedly");
+        }
+    }
+}
+
+fn run_station(
+    number: u16,
+    clock: SimulationClock,
+    imperfections: Imperfections,
+    seed: u64,
+    server: Option<String>,
+    cycles: Option<u32>,
+) {
+    let seed = seed.wrapping_mul(0x10000).wrapping_add(number as u64);
+    let mut rng = Rng::new(seed);
+    let climate = Climate::random(&mut rng);
+    let mac = mac_address(number);
+    info!(
+        "[simulator]: station {} ({}) with climate {:?}",
+        number,
+        macaddr::MacAddr6::from(mac),
+        climate
+    );
+
+    // each sensor has its own noise
+    let peripheral_service = PeripheralService::new(
+        Box::new(HostStatusLed::new()),
+        Box::new(SimulatedLightSensor::new(
+            climate,
+            clock,
+            imperfections,
+            rng.next_f64().to_bits(),
+        )),
+        Box::new(SimulatedTemperatureHumiditySensor::new(
+            climate,
+            clock,
+            imperfections,
+            rng.next_f64().to_bits(),
+        )),
+        Some(Box::new(SimulatedPressureSensor::new(
+            climate,
+            clock,
+            imperfections,
+            rng.next_f64().to_bits(),
+        ))),
+        Box::new(HostNetworkLink::new(mac)),
+        Box::new(SimulatedSystemControl),
+    );
+    let mut storage_provider = InMemoryStorageProvider::new();
+    let mut settings = SettingsService::open(&mut storage_provider).load();
+    if let Some(server) = server {
+        // only the server is checked, it was at startup
+        settings = settings
+            .with_server(&server)
+            .expect("the server has been checked at startup");
+    }
+    orchestrate_cycles(
+        peripheral_service,
+        Box::new(HostHttpTransport::new(HttpSettings::default())),
+        Rc::new(HostMqttConnector),
+        Box::new(HostFirmwareUpdater),
+        Box::new(storage_provider),
+        settings,
+        cycles,
+    );
+}
+
+// exiting would stop all the stations, a restarted station just goes on
+struct SimulatedSystemControl;
+
+impl SystemControl for SimulatedSystemControl {
+    fn restart(&mut self) {
+        info!("restart requested, the simulated station goes on");
+    }
+
+    fn random(&mut self) -> u64 {
+        HostSystemControl.random()
+    }
+}
+
+// locally administered address, 02:53:49:4d ("SIM") followed by the station number
+fn mac_address(number: u16) -> [u8; 6] {
+    let [high, low] = number.to_be_bytes();
+    [0x02, 0x53, 0x49, 0x4d, high, low]
+}
+
+fn parse_options() -> Options {
+    let arguments: Vec<String> = std::env::args().collect();
+    Options {
+        stations: parse_option(&arguments, "--stations").unwrap_or(1),
+        first_station: parse_option(&arguments, "--first-station").unwrap_or(1),
+        imperfections: Imperfections {
+            noise: parse_option(&arguments, "--noise").unwrap_or(1.0),
+            dropout: parse_option(&arguments, "--dropout").unwrap_or(0.02),
+        },
+        time_scale: parse_option(&arguments, "--time-scale").unwrap_or(1.0),
+        seed: parse_option(&arguments, "--seed").unwrap_or(0),
+        log_level: parse_option(&arguments, "--log-level").unwrap_or(LevelFilter::Info),
+        server: parse_option(&arguments, "--server"),
+        cycles: parse_option(&arguments, "--cycles"),
+    }
+}
+
+// value following the option name, None if missing; an invalid value stops the simulator
+fn parse_option<T: FromStr>(arguments: &[String], name: &str) -> Option<T> {
+    let index = arguments.iter().position(|argument| argument == name)?;
+    let value = arguments.get(index + 1)?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            eprintln!("invalid value for {}: {}", name, value);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{
+    error::PeripheralError,
+    hal::sensor::{LightSensor, PressureSensor, TemperatureHumiditySensor},
+    weather::{Climate, Rng, SimulationClock},
+};
+
+// how much the simulated sensors deviate from the climate
+#[derive(Clone, Copy, Debug)]
+pub struct Imperfections {
+    // scale of the gaussian noise, 1 is about the noise of the real sensors
+    pub noise: f64,
+    // probability that a reading fails
+    pub dropout: f64,
+}
+
+// state shared by the simulated sensors of a station
+struct Source {
+    climate: Climate,
+    clock: SimulationClock,
+    imperfections: Imperfections,
+    rng: Rng,
+}
+
+impl Source {
+    fn new(
+        climate: Climate,
+        clock: SimulationClock,
+        imperfections: Imperfections,
+        seed: u64,
+    ) -> Self {
+        Source {
+            climate,
+            clock,
+            imperfections,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn dropout(&mut self, sensor: &'static str) -> Result<(), PeripheralError> {
+        if self.rng.chance(self.imperfections.dropout) {
+            return Err(PeripheralError::sensor(sensor, "simulated dropout"));
+        }
+        Ok(())
+    }
+
+    // gaussian noise with the given standard deviation at noise 1
+    fn noise(&mut self, standard_deviation: f64) -> f64 {
+        self.rng.gaussian() * standard_deviation * self.imperfections.noise
+    }
+}
+
+// simulated BH1750
+pub struct SimulatedLightSensor {
+    source: Source,
+}
+
+impl SimulatedLightSensor {
+    pub fn new(
+        climate: Climate,
+        clock: SimulationClock,
+        imperfections: Imperfections,
+        seed: u64,
+    ) -> Self {
+        SimulatedLightSensor {
+            source: Source::new(climate, clock, imperfections, seed),
+        }
+    }
+}
+
+impl LightSensor for SimulatedLightSensor {
+    fn read_lux(&mut self) -> Result<f32, PeripheralError> {
+        self.source.dropout("BH1750")?;
+        let lux = self.source.climate.lux(self.source.clock.unix_seconds());
+        // passing clouds: the noise is proportional to the light
+        let lux = lux * (1.0 + self.source.noise(0.05));
+        Ok(lux.max(0.0) as f32)
+    }
+}
+
+// simulated DHT11, with its 1 °C and 1 % resolution
+pub struct SimulatedTemperatureHumiditySensor {
+    source: Source,
+}
+
+impl SimulatedTemperatureHumiditySensor {
+    pub fn new(
+        climate: Climate,
+        clock: SimulationClock,
+        imperfections: Imperfections,
+        seed: u64,
+    ) -> Self {
+        SimulatedTemperatureHumiditySensor {
+            source: Source::new(climate, clock, imperfections, seed),
+        }
+    }
+}
+
+impl TemperatureHumiditySensor for SimulatedTemperatureHumiditySensor {
+    fn read_temperature_and_humidity(&mut self) -> Result<(f32, f32), PeripheralError> {
+        self.source.dropout("DHT11")?;
+        let now = self.source.clock.unix_seconds();
+        let temperature = self.source.climate.temperature(now) + self.source.noise(0.5);
+        let humidity = self.source.climate.humidity(now) + self.source.noise(2.0);
+        Ok((
+            temperature.round() as f32,
+            humidity.round().clamp(0.0, 100.0) as f32,
+        ))
+    }
+}
+
+// simulated BMP280
+pub struct SimulatedPressureSensor {
+    source: Source,
+}
+
+impl SimulatedPressureSensor {
+    pub fn new(
+        climate: Climate,
+        clock: SimulationClock,
+        imperfections: Imperfections,
+        seed: u64,
+    ) -> Self {
+        SimulatedPressureSensor {
+            source: Source::new(climate, clock, imperfections, seed),
+        }
+    }
+}
+
+impl PressureSensor for SimulatedPressureSensor {
+    fn read_pressure(&mut self) -> Result<f64, PeripheralError> {
+        self.source.dropout("BMP280")?;
+        let pressure = self
+            .source
+            .climate
+            .pressure(self.source.clock.unix_seconds());
+        Ok(pressure + self.source.noise(0.12))
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    f64::consts::PI,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+const SECONDS_PER_DAY: f64 = 86400.0;
+// hour of the day of the highest temperature and of the lowest humidity
+const WARMEST_HOUR: f64 = 15.0;
+const SUNRISE_HOUR: f64 = 6.0;
+const SUNSET_HOUR: f64 = 20.0;
+// period of the slow pressure oscillation (passing weather systems)
+const PRESSURE_PERIOD_DAYS: f64 = 4.0;
+
+// xorshift64*, seeded so that a run can be reproduced
+#[derive(Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // splitmix64 spreads close seeds (e.g. consecutive station numbers)
+        let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        Rng {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    // value in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        (x.wrapping_mul(0x2545F4914F6CDD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
+
+    // standard normal value (Box-Muller)
+    pub fn gaussian(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.next_f64() < probability
+    }
+}
+
+// simulated time: the real time, optionally accelerated (e.g. 1440 plays a day in a minute)
+#[derive(Clone, Copy)]
+pub struct SimulationClock {
+    started_at: Instant,
+    started_at_unix_seconds: f64,
+    time_scale: f64,
+}
+
+impl SimulationClock {
+    pub fn new(time_scale: f64) -> Self {
+        SimulationClock {
+            started_at: Instant::now(),
+            started_at_unix_seconds: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs_f64())
+                .unwrap_or_default(),
+            time_scale,
+        }
+    }
+
+    pub fn unix_seconds(&self) -> f64 {
+        self.started_at_unix_seconds + self.started_at.elapsed().as_secs_f64() * self.time_scale
+    }
+}
+
+// climate of a station, the readings follow its daily cycle
+#[derive(Clone, Copy, Debug)]
+pub struct Climate {
+    // offset from UTC of the local solar time
+    pub utc_offset_hours: f64,
+    pub mean_temperature: f64,
+    pub temperature_amplitude: f64,
+    pub mean_humidity: f64,
+    pub humidity_amplitude: f64,
+    // lux at noon with a clear sky
+    pub peak_lux: f64,
+    // 0 is a clear sky, 1 an overcast one
+    pub cloudiness: f64,
+    pub mean_pressure: f64,
+    pub pressure_amplitude: f64,
+    pub pressure_phase: f64,
+}
+
+impl Climate {
+    // a plausible temperate climate, different for each station
+    pub fn random(rng: &mut Rng) -> Self {
+        Climate {
+            utc_offset_hours: rng.range(-1.0, 3.0),
+            mean_temperature: rng.range(8.0, 22.0),
+            temperature_amplitude: rng.range(3.0, 7.0),
+            mean_humidity: rng.range(50.0, 75.0),
+            humidity_amplitude: rng.range(10.0, 20.0),
+            peak_lux: rng.range(60000.0, 100000.0),
+            cloudiness: rng.range(0.0, 0.8),
+            mean_pressure: rng.range(1005.0, 1020.0),
+            pressure_amplitude: rng.range(3.0, 10.0),
+            pressure_phase: rng.range(0.0, 2.0 * PI),
+        }
+    }
+
+    fn local_hour(&self, unix_seconds: f64) -> f64 {
+        let seconds = unix_seconds + self.utc_offset_hours * 3600.0;
+        seconds.rem_euclid(SECONDS_PER_DAY) / 3600.0
+    }
+
+    // 1 at the warmest hour, -1 twelve hours later
+    fn daily_cycle(&self, unix_seconds: f64) -> f64 {
+        (2.0 * PI * (self.local_hour(unix_seconds) - WARMEST_HOUR) / 24.0).cos()
+    }
+
+    pub fn temperature(&self, unix_seconds: f64) -> f64 {
+        self.mean_temperature + self.temperature_amplitude * self.daily_cycle(unix_seconds)
+    }
+
+    // the relative humidity drops while the air warms up
+    pub fn humidity(&self, unix_seconds: f64) -> f64 {
+        (self.mean_humidity - self.humidity_amplitude * self.daily_cycle(unix_seconds))
+            .clamp(5.0, 95.0)
+    }
+
+    pub fn lux(&self, unix_seconds: f64) -> f64 {
+        let hour = self.local_hour(unix_seconds);
+        if !(SUNRISE_HOUR..SUNSET_HOUR).contains(&hour) {
+            return 0.0;
+        }
+        let elevation = (PI * (hour - SUNRISE_HOUR) / (SUNSET_HOUR - SUNRISE_HOUR)).sin();
+        self.peak_lux * elevation * (1.0 - 0.9 * self.cloudiness)
+    }
+
+    pub fn pressure(&self, unix_seconds: f64) -> f64 {
+        let days = unix_seconds / SECONDS_PER_DAY;
+        self.mean_pressure
+            + self.pressure_amplitude
+                * (2.0 * PI * days / PRESSURE_PERIOD_DAYS + self.pressure_phase).sin()
+    }
+}
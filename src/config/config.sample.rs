@@ -1,4 +1,5 @@
 // rename the file in config.rs
+use crate::dto::retry_configuration::RetryPolicyConfiguration;
 // customize your settings by editing this variables
 // ------------------------------------------------------------------
 // wifi name
@@ -6,14 +7,18 @@ pub const WIFI_SSID: &str = "wifi name";
 // wifi password
 pub const WIFI_PASS: &str = "wifi password";
 // endpoint that is used to send an alert after a movement detection
-pub const DEFAULT_ALERT_URL: &str = "http://192.168.1.102:8080/api/v1/weather-sensor/submit";
+pub const DEFAULT_ALERT_URL: &str = "https://192.168.1.102:8443/api/v1/weather-sensor/submit";
 // endpoint on which the server is informed that the device is alive
-pub const DEFAULT_I_AM_ALIVE_URL: &str = "http://192.168.1.102:8080/api/v1/i-am-alive/notify";
+pub const DEFAULT_I_AM_ALIVE_URL: &str = "https://192.168.1.102:8443/api/v1/i-am-alive/notify";
 // time interval between is alive requests
-// pub const DEFAULT_I_AM_ALIVE_INTERVAL_SECONDS: u64 = 30;
+pub const DEFAULT_I_AM_ALIVE_INTERVAL_SECONDS: u64 = 30;
 // endpoint for configuration download
-pub const CONFIGURATION_URL: &str = "http://192.168.1.102:8080/api/v1/weather-sensor/configuration";
-// the unit of measure of the temperature sensor - could be "F" or "C"
+pub const CONFIGURATION_URL: &str = "https://192.168.1.102:8443/api/v1/weather-sensor/configuration";
+// endpoint on which the device reports the fields of the configuration it did not accept
+pub const CONFIGURATION_ERRORS_URL: &str = "https://192.168.1.102:8443/api/v1/weather-sensor/configuration/errors";
+// time interval between the downloads of the configuration, 0 to download it only at startup
+pub const CONFIGURATION_REFRESH_INTERVAL_SECONDS: u64 = 3600;
+// the unit of measure of the temperature sensor - could be "C", "F" or "K"
 pub const TEMPERATURE_SENSOR_UNIT_OF_MEASURE: &str = "C";
 // if enabled, if cannot download configuration then will terminate the application
 pub const IS_REMOTE_CONFIGURATION_MANDATORY: bool = false;
@@ -22,8 +27,56 @@ pub const WEATHER_SENSOR_SUPPLY_INTERVAL_SECONDS: u64 = 30;
 // This is the default crontab value if server value is wrong
 // pub const DEFAULT_CRONTAB: &str =
 // "0-59   0-59   0-23     1-31       Jan-Dec  Mon,Tue,Wed,Thu,Fri,Sat,Sun  2023-2100";
+// maximum number of measurements kept (in NVS) while the server is not reachable
+pub const OFFLINE_BUFFER_CAPACITY: u32 = 100;
+// maximum size of a response body, longer responses are rejected
+pub const MAX_RESPONSE_BODY_BYTES: usize = 16384;
+// timeout of the connection to the server and of each read and write
+pub const HTTP_TIMEOUT_MILLIS: u64 = 10000;
+// timeout of each I2C transaction: a device that holds the bus does not block the
+// readings of the other sensors
+#[cfg_attr(not(feature = "hal"), allow(dead_code))]
+pub const I2C_TIMEOUT_MILLIS: u64 = 50;
+// the boot waits at most this time for the SNTP synchronization of the clock, then
+// the measurements are sent flagged as captured with an unsynchronized clock
+#[cfg_attr(not(feature = "hal"), allow(dead_code))]
+pub const CLOCK_SYNC_TIMEOUT_SECONDS: u64 = 30;
+// the connection to the server is reused if it has been idle for less than this time
+pub const HTTP_KEEP_ALIVE_IDLE_SECONDS: u64 = 50;
+// retry policies of the calls to the server, used until the configuration has its own:
+// attempts, delay before the first retry and maximum delay in milliseconds, randomized
+// fraction (0 to 1) of the delay
+pub const SUBMIT_RETRY_POLICY: RetryPolicyConfiguration = RetryPolicyConfiguration::new(4, 500, 8000, 0.5);
+// the next heartbeat is never far away
+pub const HEARTBEAT_RETRY_POLICY: RetryPolicyConfiguration = RetryPolicyConfiguration::new(2, 500, 2000, 0.5);
+pub const CONFIGURATION_RETRY_POLICY: RetryPolicyConfiguration = RetryPolicyConfiguration::new(5, 1000, 16000, 0.5);
+pub const REGISTRATION_RETRY_POLICY: RetryPolicyConfiguration = RetryPolicyConfiguration::new(5, 1000, 16000, 0.5);
+// if false only https URLs are used, also for the endpoints received from the server
+pub const ALLOW_PLAIN_HTTP: bool = false;
+// CA certificate (PEM) that signed the certificate of the server;
+// if None the certificate bundle of ESP-IDF is used (the common public CAs)
+pub const SERVER_CA_CERTIFICATE_PEM: Option<&str> = None;
+// SHA-256 fingerprint of the certificate of the server, e.g. the output of
+// openssl x509 -in server.pem -noout -fingerprint -sha256
+// if set, only this certificate is accepted and the CA is not checked
+pub const SERVER_CERTIFICATE_SHA256: Option<&str> = None;
+// key of the HMAC-SHA256 signature of the request bodies, shared with the server;
+// if None the bodies are signed with the token received at the registration (if any)
+pub const SIGNING_KEY: Option<&str> = None;
+// topics of the MQTT transport (used if the configuration selects it), {mac} is
+// replaced by the MAC address of the device
+pub const MQTT_MEASUREMENT_TOPIC: &str = "elisys/weather-station/{mac}/measurement";
+pub const MQTT_STATUS_TOPIC: &str = "elisys/weather-station/{mac}/status";
+pub const MQTT_REGISTRATION_TOPIC: &str = "elisys/weather-station/{mac}/registration";
+// prefix of the Home Assistant discovery topics
+pub const MQTT_DISCOVERY_PREFIX: &str = "homeassistant";
+// quality of service of the MQTT messages: 0, 1 or 2
+pub const MQTT_QOS: u8 = 1;
+// the broker considers the device gone (and publishes the "offline" status) if it does
+// not hear from it for 1.5 times this interval
+pub const MQTT_KEEP_ALIVE_SECONDS: u64 = 60;
 // Device registration endpoint
-pub const REGISTER_DEVICE_URL: &str = "http://192.168.1.102:8080/api/v1/device/register";
+pub const REGISTER_DEVICE_URL: &str = "https://192.168.1.102:8443/api/v1/device/register";
 // Device name
 pub const DEVICE_NAME: &str = "Weather Station";
 // Device description
@@ -1 +1,3 @@
+// config.rs is created by the user from config.sample.rs, the name is kept
+#[allow(clippy::module_inception)]
 pub mod config;
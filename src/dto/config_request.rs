@@ -1,3 +1,4 @@
+use crate::service::configuration_merge_service::CONFIGURATION_SCHEMA_VERSION;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -5,10 +6,16 @@ use serde::Serialize;
 pub struct ConfigRequest {
     #[serde(rename = "macAddress")]
     mac_address: String,
+    // the newest configuration schema known by the firmware
+    #[serde(rename = "schemaVersion")]
+    schema_version: u64,
 }
 
 impl ConfigRequest {
     pub fn new(mac_address: String) -> ConfigRequest {
-        ConfigRequest { mac_address }
+        ConfigRequest {
+            mac_address,
+            schema_version: CONFIGURATION_SCHEMA_VERSION,
+        }
     }
 }
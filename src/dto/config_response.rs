@@ -1,6 +1,15 @@
-use serde::Deserialize;
+use super::{
+    mqtt_configuration::MqttConfiguration, request_configuration_errors::FieldError,
+    retry_configuration::RetryConfiguration, temperature_unit::TemperatureUnit,
+};
+use crate::config::config::{
+    CONFIGURATION_REFRESH_INTERVAL_SECONDS, DEFAULT_I_AM_ALIVE_INTERVAL_SECONDS,
+    TEMPERATURE_SENSOR_UNIT_OF_MEASURE,
+};
+use log::error;
+use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct Configuration {
     #[serde(rename = "alertEndpoint")]
     pub alert_endpoint: String,
@@ -10,4 +19,283 @@ pub struct Configuration {
     pub temperature_sensor_unit_of_measure: String,
     #[serde(rename = "weatherSensorSupplyIntervalSeconds")]
     pub weather_sensor_supply_interval_seconds: u64,
+    #[serde(
+        rename = "iAmAliveIntervalSeconds",
+        default = "default_i_am_alive_interval_seconds"
+    )]
+    pub i_am_alive_interval_seconds: u64,
+    // optional cron expression that replaces the fixed interval
+    #[serde(rename = "crontab", default)]
+    pub crontab: Option<String>,
+    // "http" (default) or "mqtt", the latter needs the mqtt section
+    #[serde(rename = "transport", default)]
+    pub transport: Option<String>,
+    #[serde(rename = "mqtt", default)]
+    pub mqtt: Option<MqttConfiguration>,
+    // 0 to download the configuration only at startup
+    #[serde(
+        rename = "configurationRefreshIntervalSeconds",
+        default = "default_configuration_refresh_interval_seconds"
+    )]
+    pub configuration_refresh_interval_seconds: u64,
+    // how the calls to the server are retried
+    #[serde(rename = "retryPolicies", default)]
+    pub retry_policies: RetryConfiguration,
+}
+
+// a configuration received from the server, with the ETag of the response or else
+// the digest of its body
+#[derive(Debug, Clone)]
+pub struct VersionedConfiguration {
+    pub configuration: Configuration,
+    pub version: String,
+}
+
+// a configuration downloaded from the server, merged over the compiled defaults
+#[derive(Debug, Clone)]
+pub struct DownloadedConfiguration {
+    pub configuration: Configuration,
+    pub version: String,
+    // the fields that could not be read, the configuration has their default value
+    pub errors: Vec<FieldError>,
+}
+
+// the parts of the running station changed by a new configuration
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ConfigurationChanges {
+    // the schedule of the readings, of the heartbeat or of the refresh
+    pub schedule: bool,
+    pub temperature_unit: bool,
+    // the endpoints, the transport or the broker
+    pub transport: bool,
+    pub retry_policies: bool,
+}
+
+impl ConfigurationChanges {
+    pub fn is_empty(&self) -> bool {
+        *self == ConfigurationChanges::default()
+    }
+}
+
+// how the measurements, the heartbeats and the registration reach the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransportKind {
+    Http,
+    Mqtt,
+}
+
+impl Configuration {
+    // what changes if this configuration replaces the active one
+    pub fn changes_from(&self, active: &Configuration) -> ConfigurationChanges {
+        ConfigurationChanges {
+            schedule: self.weather_sensor_supply_interval_seconds
+                != active.weather_sensor_supply_interval_seconds
+                || self.crontab != active.crontab
+                || self.i_am_alive_interval_seconds != active.i_am_alive_interval_seconds
+                || self.configuration_refresh_interval_seconds
+                    != active.configuration_refresh_interval_seconds,
+            temperature_unit: self.temperature_sensor_unit_of_measure
+                != active.temperature_sensor_unit_of_measure,
+            transport: self.alert_endpoint != active.alert_endpoint
+                || self.i_am_alive_endpoint != active.i_am_alive_endpoint
+                || self.transport != active.transport
+                || self.mqtt != active.mqtt,
+            retry_policies: self.retry_policies != active.retry_policies,
+        }
+    }
+
+    // the configured unit, or the default one if the configured unit is not valid
+    pub fn temperature_unit(&self) -> TemperatureUnit {
+        match TemperatureUnit::parse(&self.temperature_sensor_unit_of_measure) {
+            Ok(unit) => unit,
+            Err(e) => {
+                error!("{}, using the default one", e);
+                TemperatureUnit::parse(TEMPERATURE_SENSOR_UNIT_OF_MEASURE).unwrap_or_default()
+            }
+        }
+    }
+
+    // the configured transport, HTTP if it is not valid or MQTT has no broker
+    pub fn transport_kind(&self) -> TransportKind {
+        let transport = self.transport.as_deref().unwrap_or("http");
+        match transport.trim().to_ascii_lowercase().as_str() {
+            "http" => TransportKind::Http,
+            "mqtt" if self.mqtt.is_some() => TransportKind::Mqtt,
+            "mqtt" => {
+                error!("MQTT transport without the mqtt section, using HTTP");
+                TransportKind::Http
+            }
+            _ => {
+                error!("invalid transport {:?}, using HTTP", transport);
+                TransportKind::Http
+            }
+        }
+    }
+}
+
+fn default_i_am_alive_interval_seconds() -> u64 {
+    DEFAULT_I_AM_ALIVE_INTERVAL_SECONDS
+}
+
+fn default_configuration_refresh_interval_seconds() -> u64 {
+    CONFIGURATION_REFRESH_INTERVAL_SECONDS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::retry_configuration::RetryPolicyConfiguration;
+
+    fn configuration() -> Configuration {
+        Configuration {
+            alert_endpoint: "https://server:8443/api/v1/weather-sensor/submit".to_owned(),
+            i_am_alive_endpoint: "https://server:8443/api/v1/i-am-alive/notify".to_owned(),
+            temperature_sensor_unit_of_measure: "C".to_owned(),
+            weather_sensor_supply_interval_seconds: 60,
+            i_am_alive_interval_seconds: 30,
+            crontab: None,
+            transport: None,
+            mqtt: None,
+            configuration_refresh_interval_seconds: 3600,
+            retry_policies: RetryConfiguration::default(),
+        }
+    }
+
+    // a field, how it changes and the parts of the station that change with it
+    type FieldChange = (&'static str, fn(&mut Configuration), ConfigurationChanges);
+
+    fn schedule() -> ConfigurationChanges {
+        ConfigurationChanges {
+            schedule: true,
+            ..ConfigurationChanges::default()
+        }
+    }
+
+    fn temperature_unit() -> ConfigurationChanges {
+        ConfigurationChanges {
+            temperature_unit: true,
+            ..ConfigurationChanges::default()
+        }
+    }
+
+    fn transport() -> ConfigurationChanges {
+        ConfigurationChanges {
+            transport: true,
+            ..ConfigurationChanges::default()
+        }
+    }
+
+    #[test]
+    fn same_configuration_changes_nothing() {
+        let changes = configuration().changes_from(&configuration());
+        assert!(changes.is_empty(), "{:?}", changes);
+    }
+
+    #[test]
+    fn each_field_changes_only_its_part() {
+        let mqtt = MqttConfiguration::new("mqtts://broker:8883".to_owned());
+        let changed: [FieldChange; 11] = [
+            (
+                "interval",
+                |c| c.weather_sensor_supply_interval_seconds = 120,
+                schedule(),
+            ),
+            (
+                "crontab",
+                |c| c.crontab = Some("0 * * * * *".to_owned()),
+                schedule(),
+            ),
+            (
+                "heartbeat",
+                |c| c.i_am_alive_interval_seconds = 45,
+                schedule(),
+            ),
+            (
+                "refresh",
+                |c| c.configuration_refresh_interval_seconds = 0,
+                schedule(),
+            ),
+            (
+                "unit",
+                |c| c.temperature_sensor_unit_of_measure = "F".to_owned(),
+                temperature_unit(),
+            ),
+            (
+                "alert endpoint",
+                |c| c.alert_endpoint = "https://other/submit".to_owned(),
+                transport(),
+            ),
+            (
+                "heartbeat endpoint",
+                |c| c.i_am_alive_endpoint = "https://other/notify".to_owned(),
+                transport(),
+            ),
+            (
+                "transport",
+                |c| c.transport = Some("mqtt".to_owned()),
+                transport(),
+            ),
+            (
+                "broker",
+                |c| c.mqtt = Some(MqttConfiguration::new("mqtts://other:8883".to_owned())),
+                transport(),
+            ),
+            (
+                "discovery",
+                |c| c.mqtt.as_mut().unwrap().home_assistant_discovery ^= true,
+                transport(),
+            ),
+            (
+                "retry policy",
+                |c| c.retry_policies.submit = RetryPolicyConfiguration::new(9, 100, 1000, 0.1),
+                ConfigurationChanges {
+                    retry_policies: true,
+                    ..ConfigurationChanges::default()
+                },
+            ),
+        ];
+        for (field, change, expected) in changed {
+            // the broker settings are compared with a broker already configured
+            let active = Configuration {
+                mqtt: Some(mqtt.clone()),
+                ..configuration()
+            };
+            let mut new = active.clone();
+            change(&mut new);
+            assert_eq!(new.changes_from(&active), expected, "{}", field);
+        }
+    }
+
+    #[test]
+    fn several_fields_change_several_parts() {
+        let active = configuration();
+        let new = Configuration {
+            weather_sensor_supply_interval_seconds: 120,
+            temperature_sensor_unit_of_measure: "K".to_owned(),
+            ..configuration()
+        };
+        assert_eq!(
+            new.changes_from(&active),
+            ConfigurationChanges {
+                schedule: true,
+                temperature_unit: true,
+                ..ConfigurationChanges::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unit_and_transport_that_are_not_valid_use_the_defaults() {
+        let configuration = Configuration {
+            temperature_sensor_unit_of_measure: "X".to_owned(),
+            transport: Some("mqtt".to_owned()),
+            ..configuration()
+        };
+        assert_eq!(
+            configuration.temperature_unit(),
+            TemperatureUnit::parse(TEMPERATURE_SENSOR_UNIT_OF_MEASURE).unwrap()
+        );
+        // MQTT without the broker
+        assert_eq!(configuration.transport_kind(), TransportKind::Http);
+    }
 }
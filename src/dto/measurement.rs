@@ -0,0 +1,27 @@
+use super::temperature_unit::TemperatureUnit;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+// values read from the sensors, kept in the offline buffer until they are sent
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub temperature: Option<f32>,
+    #[serde(rename = "temperatureUnit", default)]
+    pub temperature_unit: TemperatureUnit,
+    pub humidity: Option<f32>,
+    pub pressure: Option<f64>,
+    pub lux: Option<f32>,
+    pub light: Option<bool>,
+    #[serde(rename = "capturedAtMillis")]
+    pub captured_at_millis: i64,
+    #[serde(rename = "clockSynchronized", default)]
+    pub clock_synchronized: bool,
+}
+
+impl Measurement {
+    pub fn captured_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(self.captured_at_millis)
+            .single()
+            .unwrap_or_default()
+    }
+}
@@ -1,5 +1,12 @@
 pub mod config_request;
 pub mod config_response;
+pub mod measurement;
+pub mod mqtt_configuration;
 pub mod register_device;
+pub mod register_device_response;
+pub mod remote_command;
+pub mod request_configuration_errors;
 pub mod request_i_am_alive;
 pub mod request_submit;
+pub mod retry_configuration;
+pub mod temperature_unit;
@@ -0,0 +1,113 @@
+use crate::config::config::{
+    MQTT_DISCOVERY_PREFIX, MQTT_MEASUREMENT_TOPIC, MQTT_QOS, MQTT_REGISTRATION_TOPIC,
+    MQTT_STATUS_TOPIC,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// broker and topics of the MQTT transport; in the topics {mac} is replaced by the MAC
+// address of the device
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct MqttConfiguration {
+    #[serde(rename = "brokerUrl")]
+    pub broker_url: String,
+    #[serde(rename = "username", default)]
+    pub username: Option<String>,
+    #[serde(rename = "password", default)]
+    pub password: Option<String>,
+    #[serde(rename = "qos", default = "default_qos")]
+    pub qos: u8,
+    #[serde(rename = "measurementTopic", default = "default_measurement_topic")]
+    pub measurement_topic: String,
+    // retained "online" while the device is connected, "offline" (last will) otherwise
+    #[serde(rename = "statusTopic", default = "default_status_topic")]
+    pub status_topic: String,
+    #[serde(rename = "registrationTopic", default = "default_registration_topic")]
+    pub registration_topic: String,
+    // publishes the Home Assistant discovery configuration of the sensors
+    #[serde(rename = "homeAssistantDiscovery", default = "default_true")]
+    pub home_assistant_discovery: bool,
+    #[serde(rename = "discoveryPrefix", default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+impl MqttConfiguration {
+    // the broker with the default QoS and topics
+    pub fn new(broker_url: String) -> MqttConfiguration {
+        MqttConfiguration {
+            broker_url,
+            username: None,
+            password: None,
+            qos: default_qos(),
+            measurement_topic: default_measurement_topic(),
+            status_topic: default_status_topic(),
+            registration_topic: default_registration_topic(),
+            home_assistant_discovery: default_true(),
+            discovery_prefix: default_discovery_prefix(),
+        }
+    }
+}
+
+// the configuration is logged, the password is not
+impl fmt::Debug for MqttConfiguration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MqttConfiguration")
+            .field("broker_url", &self.broker_url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("qos", &self.qos)
+            .field("measurement_topic", &self.measurement_topic)
+            .field("status_topic", &self.status_topic)
+            .field("registration_topic", &self.registration_topic)
+            .field("home_assistant_discovery", &self.home_assistant_discovery)
+            .field("discovery_prefix", &self.discovery_prefix)
+            .finish()
+    }
+}
+
+fn default_qos() -> u8 {
+    MQTT_QOS
+}
+
+fn default_measurement_topic() -> String {
+    MQTT_MEASUREMENT_TOPIC.to_owned()
+}
+
+fn default_status_topic() -> String {
+    MQTT_STATUS_TOPIC.to_owned()
+}
+
+fn default_registration_topic() -> String {
+    MQTT_REGISTRATION_TOPIC.to_owned()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_discovery_prefix() -> String {
+    MQTT_DISCOVERY_PREFIX.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_redacts_the_password() {
+        let mut mqtt = MqttConfiguration::new("mqtts://broker:8883".to_owned());
+        mqtt.username = Some("station".to_owned());
+        mqtt.password = Some("s3cret".to_owned());
+        let debug = format!("{:?}", mqtt);
+        assert!(!debug.contains("s3cret"), "{}", debug);
+        assert!(
+            debug.contains("password: Some(\"<redacted>\")"),
+            "{}",
+            debug
+        );
+        assert!(debug.contains("username: Some(\"station\")"), "{}", debug);
+
+        mqtt.password = None;
+        assert!(format!("{:?}", mqtt).contains("password: None"));
+    }
+}
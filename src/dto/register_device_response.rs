@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+// body of the registration response, servers that do not authenticate the devices
+// do not send the token
+#[derive(Deserialize, Debug, Default)]
+pub struct RegisterDeviceResponse {
+    #[serde(default)]
+    pub token: Option<String>,
+}
@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+// command sent by the server in the response of the I-am-alive message; the server
+// sends it again until the device acknowledges it
+#[derive(Deserialize, Debug, Clone)]
+pub struct RemoteCommand {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub command_type: String,
+    // location of the firmware, for "startOta"
+    #[serde(default)]
+    pub url: Option<String>,
+    // the settings to store, by name, for "updateSettings"
+    #[serde(default)]
+    pub settings: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommandKind {
+    Reboot,
+    RefreshConfiguration,
+    TakeReading,
+    Identify,
+    ClearOfflineBuffer,
+    UpdateSettings,
+    StartOta,
+}
+
+impl RemoteCommand {
+    // None if the firmware does not know the command
+    pub fn kind(&self) -> Option<CommandKind> {
+        match self.command_type.as_str() {
+            "reboot" => Some(CommandKind::Reboot),
+            "refreshConfiguration" => Some(CommandKind::RefreshConfiguration),
+            "takeReading" => Some(CommandKind::TakeReading),
+            "identify" => Some(CommandKind::Identify),
+            "clearOfflineBuffer" => Some(CommandKind::ClearOfflineBuffer),
+            "updateSettings" => Some(CommandKind::UpdateSettings),
+            "startOta" => Some(CommandKind::StartOta),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub enum CommandStatus {
+    #[serde(rename = "done")]
+    Done,
+    #[serde(rename = "failed")]
+    Failed,
+    #[serde(rename = "unsupported")]
+    Unsupported,
+}
+
+// outcome of a command, sent with the next I-am-alive message
+#[derive(Serialize, Debug, Clone)]
+pub struct CommandAcknowledgement {
+    pub id: String,
+    pub status: CommandStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+// body of the I-am-alive response, servers without commands send an empty body
+#[derive(Deserialize, Debug, Default)]
+pub struct IAmAliveResponse {
+    #[serde(default)]
+    pub commands: Vec<RemoteCommand>,
+}
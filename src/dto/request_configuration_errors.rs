@@ -0,0 +1,41 @@
+use serde::Serialize;
+
+// a field of the configuration that the device did not accept
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldError {
+    // name of the field in the configuration JSON, e.g. "mqtt.brokerUrl"
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: String) -> FieldError {
+        FieldError {
+            field: field.to_owned(),
+            message,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RequestConfigurationErrors {
+    #[serde(rename = "macAddress")]
+    mac_address: String,
+    #[serde(rename = "configurationVersion")]
+    configuration_version: String,
+    errors: Vec<FieldError>,
+}
+
+impl RequestConfigurationErrors {
+    pub fn new(
+        mac_address: String,
+        configuration_version: String,
+        errors: Vec<FieldError>,
+    ) -> RequestConfigurationErrors {
+        RequestConfigurationErrors {
+            mac_address,
+            configuration_version,
+            errors,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::remote_command::CommandAcknowledgement;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -5,10 +6,22 @@ use serde::Serialize;
 pub struct RequestIAmAlive {
     #[serde(rename = "macAddress")]
     mac_address: String,
+    // outcome of the commands received with the previous responses
+    #[serde(
+        rename = "acknowledgedCommands",
+        skip_serializing_if = "<[_]>::is_empty"
+    )]
+    acknowledged_commands: Vec<CommandAcknowledgement>,
 }
 
 impl RequestIAmAlive {
-    pub fn new(mac_address: String) -> RequestIAmAlive {
-        RequestIAmAlive { mac_address }
+    pub fn new(
+        mac_address: String,
+        acknowledged_commands: Vec<CommandAcknowledgement>,
+    ) -> RequestIAmAlive {
+        RequestIAmAlive {
+            mac_address,
+            acknowledged_commands,
+        }
     }
 }
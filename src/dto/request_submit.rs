@@ -1,3 +1,5 @@
+use super::{measurement::Measurement, temperature_unit::TemperatureUnit};
+use chrono::SecondsFormat;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -6,28 +8,89 @@ pub struct RequestSubmit {
     #[serde(rename = "macAddress")]
     mac_address: String,
     temperature: Option<f32>,
+    #[serde(rename = "temperatureUnitOfMeasure")]
+    temperature_unit_of_measure: TemperatureUnit,
     humidity: Option<f32>,
     pressure: Option<f64>,
     lux: Option<f32>,
     light: Option<bool>,
+    // capture time, RFC 3339 in UTC
+    #[serde(rename = "measuredAt")]
+    measured_at: String,
+    #[serde(rename = "measuredAtMillis")]
+    measured_at_millis: i64,
+    // false if the capture time comes from a clock that was never synchronized
+    #[serde(rename = "clockSynchronized")]
+    clock_synchronized: bool,
 }
 
 impl RequestSubmit {
-    pub fn new(
-        mac_address: String,
-        temperature: Option<f32>,
-        humidity: Option<f32>,
-        pressure: Option<f64>,
-        lux: Option<f32>,
-        light: Option<bool>,
-    ) -> RequestSubmit {
+    pub fn new(mac_address: String, measurement: &Measurement) -> RequestSubmit {
+        let measured_at = measurement.captured_at();
         RequestSubmit {
             mac_address,
-            temperature,
-            humidity,
-            pressure,
-            lux,
-            light,
+            temperature: measurement.temperature,
+            temperature_unit_of_measure: measurement.temperature_unit,
+            humidity: measurement.humidity,
+            pressure: measurement.pressure,
+            lux: measurement.lux,
+            light: measurement.light,
+            measured_at: measured_at.to_rfc3339_opts(SecondsFormat::Millis, true),
+            measured_at_millis: measured_at.timestamp_millis(),
+            clock_synchronized: measurement.clock_synchronized,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn measurement(captured_at_millis: i64, clock_synchronized: bool) -> Measurement {
+        Measurement {
+            temperature: Some(70.7),
+            temperature_unit: TemperatureUnit::Fahrenheit,
+            humidity: Some(40.0),
+            pressure: Some(1013.25),
+            lux: None,
+            light: Some(true),
+            captured_at_millis,
+            clock_synchronized,
+        }
+    }
+
+    #[test]
+    fn serializes_the_capture_time_of_a_synchronized_clock() {
+        let request = RequestSubmit::new(
+            "02:00:00:00:00:01".to_owned(),
+            &measurement(1_700_000_000_123, true),
+        );
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({
+                "macAddress": "02:00:00:00:00:01",
+                "temperature": 70.7f32,
+                "temperatureUnitOfMeasure": "F",
+                "humidity": 40.0,
+                "pressure": 1013.25,
+                "lux": null,
+                "light": true,
+                "measuredAt": "2023-11-14T22:13:20.123Z",
+                "measuredAtMillis": 1_700_000_000_123i64,
+                "clockSynchronized": true
+            })
+        );
+    }
+
+    #[test]
+    fn flags_the_capture_time_of_a_clock_never_synchronized() {
+        // the clock counts from the epoch since the boot
+        let request =
+            RequestSubmit::new("02:00:00:00:00:01".to_owned(), &measurement(12_345, false));
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["measuredAt"], "1970-01-01T00:00:12.345Z");
+        assert_eq!(json["measuredAtMillis"], 12_345);
+        assert_eq!(json["clockSynchronized"], false);
+    }
+}
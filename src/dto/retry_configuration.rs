@@ -0,0 +1,75 @@
+use crate::config::config::{
+    CONFIGURATION_RETRY_POLICY, HEARTBEAT_RETRY_POLICY, REGISTRATION_RETRY_POLICY,
+    SUBMIT_RETRY_POLICY,
+};
+use serde::{Deserialize, Serialize};
+
+// retry policy of a call to the server: the attempts, the delay before the first retry
+// (doubled at each retry up to the maximum) and the randomized fraction of the delay
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicyConfiguration {
+    #[serde(rename = "maxAttempts")]
+    pub max_attempts: u32,
+    #[serde(rename = "baseDelayMillis")]
+    pub base_delay_millis: u64,
+    #[serde(rename = "maxDelayMillis")]
+    pub max_delay_millis: u64,
+    #[serde(rename = "jitter")]
+    pub jitter: f64,
+}
+
+impl RetryPolicyConfiguration {
+    pub const fn new(
+        max_attempts: u32,
+        base_delay_millis: u64,
+        max_delay_millis: u64,
+        jitter: f64,
+    ) -> RetryPolicyConfiguration {
+        RetryPolicyConfiguration {
+            max_attempts,
+            base_delay_millis,
+            max_delay_millis,
+            jitter,
+        }
+    }
+}
+
+// the retry policy of each call made to the server
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct RetryConfiguration {
+    #[serde(rename = "submit", default = "default_submit")]
+    pub submit: RetryPolicyConfiguration,
+    #[serde(rename = "heartbeat", default = "default_heartbeat")]
+    pub heartbeat: RetryPolicyConfiguration,
+    #[serde(rename = "configuration", default = "default_configuration")]
+    pub configuration: RetryPolicyConfiguration,
+    #[serde(rename = "registration", default = "default_registration")]
+    pub registration: RetryPolicyConfiguration,
+}
+
+impl Default for RetryConfiguration {
+    fn default() -> Self {
+        RetryConfiguration {
+            submit: default_submit(),
+            heartbeat: default_heartbeat(),
+            configuration: default_configuration(),
+            registration: default_registration(),
+        }
+    }
+}
+
+fn default_submit() -> RetryPolicyConfiguration {
+    SUBMIT_RETRY_POLICY
+}
+
+fn default_heartbeat() -> RetryPolicyConfiguration {
+    HEARTBEAT_RETRY_POLICY
+}
+
+fn default_configuration() -> RetryPolicyConfiguration {
+    CONFIGURATION_RETRY_POLICY
+}
+
+fn default_registration() -> RetryPolicyConfiguration {
+    REGISTRATION_RETRY_POLICY
+}
@@ -0,0 +1,104 @@
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum TemperatureUnit {
+    #[default]
+    #[serde(rename = "C")]
+    Celsius,
+    #[serde(rename = "F")]
+    Fahrenheit,
+    #[serde(rename = "K")]
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    // accepts the symbol ("C", "F", "K") or the name of the unit, case insensitive
+    pub fn parse(value: &str) -> anyhow::Result<TemperatureUnit> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "C" | "CELSIUS" => Ok(TemperatureUnit::Celsius),
+            "F" | "FAHRENHEIT" => Ok(TemperatureUnit::Fahrenheit),
+            "K" | "KELVIN" => Ok(TemperatureUnit::Kelvin),
+            _ => Err(Error::msg(format!(
+                "invalid temperature unit of measure: {:?}",
+                value
+            ))),
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+
+    // the sensors measure in Celsius
+    pub fn convert_celsius(&self, celsius: f32) -> f32 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            TemperatureUnit::Kelvin => celsius + 273.15,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_the_symbols_and_the_names() {
+        let accepted = [
+            ("C", TemperatureUnit::Celsius),
+            ("c", TemperatureUnit::Celsius),
+            ("Celsius", TemperatureUnit::Celsius),
+            (" CELSIUS ", TemperatureUnit::Celsius),
+            ("F", TemperatureUnit::Fahrenheit),
+            ("fahrenheit", TemperatureUnit::Fahrenheit),
+            ("K", TemperatureUnit::Kelvin),
+            ("Kelvin", TemperatureUnit::Kelvin),
+        ];
+        for (value, unit) in accepted {
+            assert_eq!(TemperatureUnit::parse(value).unwrap(), unit, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_the_unknown_units() {
+        for value in ["", "X", "°C", "Rankine", "CF"] {
+            assert!(TemperatureUnit::parse(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn symbols() {
+        assert_eq!(TemperatureUnit::Celsius.symbol(), "°C");
+        assert_eq!(TemperatureUnit::Fahrenheit.symbol(), "°F");
+        assert_eq!(TemperatureUnit::Kelvin.symbol(), "K");
+    }
+
+    #[test]
+    fn converts_from_celsius() {
+        assert_eq!(TemperatureUnit::Celsius.convert_celsius(21.5), 21.5);
+        assert_eq!(TemperatureUnit::Fahrenheit.convert_celsius(0.0), 32.0);
+        assert_eq!(TemperatureUnit::Fahrenheit.convert_celsius(-40.0), -40.0);
+        assert_eq!(TemperatureUnit::Fahrenheit.convert_celsius(100.0), 212.0);
+        assert_eq!(TemperatureUnit::Kelvin.convert_celsius(0.0), 273.15);
+        assert_eq!(TemperatureUnit::Kelvin.convert_celsius(-273.15), 0.0);
+    }
+
+    // the server receives the symbol of the unit
+    #[test]
+    fn serializes_the_symbol() {
+        assert_eq!(
+            serde_json::to_string(&TemperatureUnit::Fahrenheit).unwrap(),
+            "\"F\""
+        );
+        assert_eq!(
+            serde_json::from_str::<TemperatureUnit>("\"K\"").unwrap(),
+            TemperatureUnit::Kelvin
+        );
+    }
+}
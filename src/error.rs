@@ -0,0 +1,171 @@
+use std::{error::Error, fmt};
+
+pub type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+// errors of the communication with the server
+#[derive(Debug)]
+pub enum ClientError {
+    // the connection could not be opened or the request could not be sent
+    Connect(BoxError),
+    // the payload could not be written
+    Write(BoxError),
+    // the server did not answer in time
+    Timeout(BoxError),
+    // the server answered with a status that is not a success
+    HttpStatus(u16),
+    // the response body could not be read or is not valid UTF-8
+    BodyDecode(BoxError),
+    // the response body is longer than the given limit (bytes)
+    ResponseTooLarge(usize),
+    // the response body is not the expected JSON
+    JsonParse(serde_json::Error),
+    // the scheme of the URL is not allowed by the configuration or not available
+    UrlNotAllowed(String),
+}
+
+// HTTP statuses worth retrying: the server may answer differently a moment later
+const TRANSIENT_STATUSES: [u16; 7] = [408, 425, 429, 500, 502, 503, 504];
+
+impl ClientError {
+    // true if the same request may succeed if sent again
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ClientError::Connect(_) | ClientError::Write(_) | ClientError::Timeout(_) => true,
+            ClientError::HttpStatus(status) => TRANSIENT_STATUSES.contains(status),
+            ClientError::BodyDecode(_)
+            | ClientError::ResponseTooLarge(_)
+            | ClientError::JsonParse(_)
+            | ClientError::UrlNotAllowed(_) => false,
+        }
+    }
+
+    // true if the device should register again: the server does not know it (404) or
+    // does not accept its token (401)
+    pub fn needs_registration(&self) -> bool {
+        matches!(
+            self,
+            ClientError::HttpStatus(404) | ClientError::HttpStatus(401)
+        )
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Connect(e) => write!(f, "connection error: {}", e),
+            ClientError::Write(e) => write!(f, "connection error while trying to write: {}", e),
+            ClientError::Timeout(e) => write!(f, "timeout: {}", e),
+            ClientError::HttpStatus(status) => write!(f, "Invalid response status: {}", status),
+            ClientError::BodyDecode(e) => write!(f, "Error decoding response body: {}", e),
+            ClientError::ResponseTooLarge(limit) => {
+                write!(f, "response too large: more than {} bytes", limit)
+            }
+            ClientError::JsonParse(e) => write!(f, "Error parsing response body: {}", e),
+            ClientError::UrlNotAllowed(message) => write!(f, "URL not allowed: {}", message),
+        }
+    }
+}
+
+impl Error for ClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ClientError::Connect(e)
+            | ClientError::Write(e)
+            | ClientError::Timeout(e)
+            | ClientError::BodyDecode(e) => Some(e.as_ref()),
+            ClientError::JsonParse(e) => Some(e),
+            ClientError::HttpStatus(_)
+            | ClientError::ResponseTooLarge(_)
+            | ClientError::UrlNotAllowed(_) => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ClientError {
+    fn from(e: serde_json::Error) -> Self {
+        ClientError::JsonParse(e)
+    }
+}
+
+// errors of the hardware (sensors, LED and network link)
+#[cfg_attr(not(feature = "hal"), allow(dead_code))]
+#[derive(Debug)]
+pub enum PeripheralError {
+    Sensor {
+        sensor: &'static str,
+        source: BoxError,
+    },
+    Led(BoxError),
+    Network(BoxError),
+}
+
+impl PeripheralError {
+    #[cfg_attr(not(feature = "hal"), allow(dead_code))]
+    pub fn sensor(sensor: &'static str, source: impl Into<BoxError>) -> PeripheralError {
+        PeripheralError::Sensor {
+            sensor,
+            source: source.into(),
+        }
+    }
+}
+
+impl fmt::Display for PeripheralError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeripheralError::Sensor { sensor, source } => {
+                write!(f, "{} sensor error: {}", sensor, source)
+            }
+            PeripheralError::Led(e) => write!(f, "LED error: {}", e),
+            PeripheralError::Network(e) => write!(f, "network error: {}", e),
+        }
+    }
+}
+
+impl Error for PeripheralError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PeripheralError::Sensor { source, .. } => Some(source.as_ref()),
+            PeripheralError::Led(e) | PeripheralError::Network(e) => Some(e.as_ref()),
+        }
+    }
+}
+
+// errors of the update of the firmware
+#[derive(Debug)]
+pub enum FirmwareUpdateError {
+    // the image could not be downloaded
+    Download(ClientError),
+    // the image could not be written to the update partition, or it is not valid
+    #[cfg_attr(not(feature = "hal"), allow(dead_code))]
+    Flash(BoxError),
+    // the device cannot update its firmware, with the reason
+    NotSupported(&'static str),
+}
+
+impl fmt::Display for FirmwareUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FirmwareUpdateError::Download(e) => write!(f, "firmware download failed: {}", e),
+            FirmwareUpdateError::Flash(e) => write!(f, "firmware not installed: {}", e),
+            FirmwareUpdateError::NotSupported(reason) => {
+                write!(f, "firmware updates not supported: {}", reason)
+            }
+        }
+    }
+}
+
+impl Error for FirmwareUpdateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FirmwareUpdateError::Download(e) => Some(e),
+            FirmwareUpdateError::Flash(e) => Some(e.as_ref()),
+            FirmwareUpdateError::NotSupported(_) => None,
+        }
+    }
+}
+
+impl From<ClientError> for FirmwareUpdateError {
+    fn from(e: ClientError) -> Self {
+        FirmwareUpdateError::Download(e)
+    }
+}
@@ -0,0 +1,280 @@
+// Bosch BMP280/BME280 registers, settings and compensation formulas.
+// The formulas are the integer ones of the BMP280 datasheet (rev. 1.19, section 8.2),
+// the BME280 shares the same temperature and pressure compensation.
+
+pub const ADDRESS_PRIMARY: u8 = 0x76;
+pub const ADDRESS_SECONDARY: u8 = 0x77;
+
+pub const REGISTER_CALIBRATION: u8 = 0x88;
+pub const REGISTER_CHIP_ID: u8 = 0xD0;
+pub const REGISTER_CTRL_MEAS: u8 = 0xF4;
+pub const REGISTER_CONFIG: u8 = 0xF5;
+pub const REGISTER_PRESS_MSB: u8 = 0xF7;
+
+pub const CHIP_ID_BMP280: u8 = 0x58;
+pub const CHIP_ID_BME280: u8 = 0x60;
+
+pub const CALIBRATION_LENGTH: usize = 24;
+pub const MEASUREMENT_LENGTH: usize = 6;
+
+const MODE_FORCED: u8 = 0b01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Oversampling {
+    Skipped,
+    X1,
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+impl Oversampling {
+    fn bits(self) -> u8 {
+        match self {
+            Oversampling::Skipped => 0b000,
+            Oversampling::X1 => 0b001,
+            Oversampling::X2 => 0b010,
+            Oversampling::X4 => 0b011,
+            Oversampling::X8 => 0b100,
+            Oversampling::X16 => 0b101,
+        }
+    }
+
+    fn samples(self) -> u32 {
+        match self {
+            Oversampling::Skipped => 0,
+            Oversampling::X1 => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Filter {
+    Off,
+    X2,
+    X4,
+    X8,
+    X16,
+}
+
+impl Filter {
+    fn bits(self) -> u8 {
+        match self {
+            Filter::Off => 0b000,
+            Filter::X2 => 0b001,
+            Filter::X4 => 0b010,
+            Filter::X8 => 0b011,
+            Filter::X16 => 0b100,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Settings {
+    pub temperature_oversampling: Oversampling,
+    pub pressure_oversampling: Oversampling,
+    pub filter: Filter,
+}
+
+impl Default for Settings {
+    // "weather monitoring" profile suggested by the datasheet
+    fn default() -> Self {
+        Settings {
+            temperature_oversampling: Oversampling::X1,
+            pressure_oversampling: Oversampling::X1,
+            filter: Filter::Off,
+        }
+    }
+}
+
+impl Settings {
+    // value of the ctrl_meas register that triggers a single (forced) measurement
+    pub fn ctrl_meas_forced(&self) -> u8 {
+        (self.temperature_oversampling.bits() << 5)
+            | (self.pressure_oversampling.bits() << 2)
+            | MODE_FORCED
+    }
+
+    // value of the config register (standby time is not used in forced mode)
+    pub fn config(&self) -> u8 {
+        self.filter.bits() << 2
+    }
+
+    // maximum measurement time in microseconds (datasheet, section 9.1)
+    pub fn max_measurement_time_us(&self) -> u32 {
+        let mut time = 1250 + 2300 * self.temperature_oversampling.samples();
+        if self.pressure_oversampling != Oversampling::Skipped {
+            time += 2300 * self.pressure_oversampling.samples() + 575;
+        }
+        time
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub dig_t1: u16,
+    pub dig_t2: i16,
+    pub dig_t3: i16,
+    pub dig_p1: u16,
+    pub dig_p2: i16,
+    pub dig_p3: i16,
+    pub dig_p4: i16,
+    pub dig_p5: i16,
+    pub dig_p6: i16,
+    pub dig_p7: i16,
+    pub dig_p8: i16,
+    pub dig_p9: i16,
+}
+
+impl Calibration {
+    // parses the calibration registers 0x88..0x9F (little endian words)
+    pub fn from_bytes(bytes: &[u8; CALIBRATION_LENGTH]) -> Calibration {
+        let unsigned = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]);
+        let signed = |index: usize| i16::from_le_bytes([bytes[index], bytes[index + 1]]);
+        Calibration {
+            dig_t1: unsigned(0),
+            dig_t2: signed(2),
+            dig_t3: signed(4),
+            dig_p1: unsigned(6),
+            dig_p2: signed(8),
+            dig_p3: signed(10),
+            dig_p4: signed(12),
+            dig_p5: signed(14),
+            dig_p6: signed(16),
+            dig_p7: signed(18),
+            dig_p8: signed(20),
+            dig_p9: signed(22),
+        }
+    }
+
+    // returns the temperature in Celsius and the t_fine value needed by the pressure compensation
+    pub fn compensate_temperature(&self, adc_t: i32) -> (f64, i32) {
+        let dig_t1 = self.dig_t1 as i32;
+        let dig_t2 = self.dig_t2 as i32;
+        let dig_t3 = self.dig_t3 as i32;
+        let var1 = (((adc_t >> 3) - (dig_t1 << 1)) * dig_t2) >> 11;
+        let var2 = (((((adc_t >> 4) - dig_t1) * ((adc_t >> 4) - dig_t1)) >> 12) * dig_t3) >> 14;
+        let t_fine = var1 + var2;
+        let temperature = (t_fine * 5 + 128) >> 8;
+        (temperature as f64 / 100.0, t_fine)
+    }
+
+    // returns the pressure in Pascal, None if the calibration would lead to a division by zero
+    pub fn compensate_pressure(&self, adc_p: i32, t_fine: i32) -> Option<f64> {
+        self.compensate_pressure_q24_8(adc_p, t_fine)
+            .map(|pressure| pressure as f64 / 256.0)
+    }
+
+    // the 64-bit integer algorithm of the datasheet: the pressure in Pascal as an
+    // unsigned Q24.8 fixed-point value
+    fn compensate_pressure_q24_8(&self, adc_p: i32, t_fine: i32) -> Option<u32> {
+        let mut var1 = t_fine as i64 - 128000;
+        let mut var2 = var1 * var1 * self.dig_p6 as i64;
+        var2 += (var1 * self.dig_p5 as i64) << 17;
+        var2 += (self.dig_p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.dig_p3 as i64) >> 8) + ((var1 * self.dig_p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.dig_p1 as i64) >> 33;
+        if var1 == 0 {
+            return None;
+        }
+        let mut pressure = 1048576 - adc_p as i64;
+        pressure = (((pressure << 31) - var2) * 3125) / var1;
+        var1 = (self.dig_p9 as i64 * (pressure >> 13) * (pressure >> 13)) >> 25;
+        var2 = (self.dig_p8 as i64 * pressure) >> 19;
+        pressure = ((pressure + var1 + var2) >> 8) + ((self.dig_p7 as i64) << 4);
+        Some(pressure as u32)
+    }
+}
+
+// extracts the raw (adc_p, adc_t) values from the registers 0xF7..0xFC
+pub fn raw_values(bytes: &[u8; MEASUREMENT_LENGTH]) -> (i32, i32) {
+    let adc_p = ((bytes[0] as i32) << 12) | ((bytes[1] as i32) << 4) | ((bytes[2] as i32) >> 4);
+    let adc_t = ((bytes[3] as i32) << 12) | ((bytes[4] as i32) << 4) | ((bytes[5] as i32) >> 4);
+    (adc_p, adc_t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // calibration and raw values of the example of the datasheet (section 3.12)
+    const CALIBRATION: Calibration = Calibration {
+        dig_t1: 27504,
+        dig_t2: 26435,
+        dig_t3: -1000,
+        dig_p1: 36477,
+        dig_p2: -10685,
+        dig_p3: 3024,
+        dig_p4: 2855,
+        dig_p5: 140,
+        dig_p6: -7,
+        dig_p7: 15500,
+        dig_p8: -14600,
+        dig_p9: 6000,
+    };
+    const ADC_T: i32 = 519888;
+    const ADC_P: i32 = 415148;
+
+    #[test]
+    fn compensates_the_datasheet_example() {
+        let (temperature, t_fine) = CALIBRATION.compensate_temperature(ADC_T);
+        assert_eq!(t_fine, 128422);
+        // 2508 hundredths of a degree
+        assert_eq!(temperature, 25.08);
+        // the datasheet gives 100653.27 Pa for the floating point formula, the integer
+        // one is exact: 25767233 / 256 = 100653.25390625 Pa
+        assert_eq!(
+            CALIBRATION.compensate_pressure_q24_8(ADC_P, t_fine),
+            Some(25767233)
+        );
+        assert_eq!(
+            CALIBRATION.compensate_pressure(ADC_P, t_fine),
+            Some(100653.25390625)
+        );
+    }
+
+    #[test]
+    fn pressure_is_none_without_dig_p1() {
+        let calibration = Calibration {
+            dig_p1: 0,
+            ..CALIBRATION
+        };
+        assert_eq!(calibration.compensate_pressure(ADC_P, 128422), None);
+    }
+
+    #[test]
+    fn parses_the_registers() {
+        let words: [i32; 12] = [
+            27504, 26435, -1000, 36477, -10685, 3024, 2855, 140, -7, 15500, -14600, 6000,
+        ];
+        let mut bytes = [0u8; CALIBRATION_LENGTH];
+        for (index, word) in words.iter().enumerate() {
+            bytes[index * 2..index * 2 + 2].copy_from_slice(&(*word as u16).to_le_bytes());
+        }
+        assert_eq!(Calibration::from_bytes(&bytes), CALIBRATION);
+
+        // 20-bit values, MSB first, the low nibble of the XLSB register
+        let measurement = [
+            (ADC_P >> 12) as u8,
+            (ADC_P >> 4) as u8,
+            (ADC_P << 4) as u8,
+            (ADC_T >> 12) as u8,
+            (ADC_T >> 4) as u8,
+            (ADC_T << 4) as u8,
+        ];
+        assert_eq!(raw_values(&measurement), (ADC_P, ADC_T));
+    }
+
+    #[test]
+    fn register_values_of_the_settings() {
+        let settings = Settings::default();
+        assert_eq!(settings.ctrl_meas_forced(), 0b0010_0101);
+        assert_eq!(settings.config(), 0);
+        assert_eq!(settings.max_measurement_time_us(), 1250 + 2300 + 2300 + 575);
+    }
+}
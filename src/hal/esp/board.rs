@@ -0,0 +1,104 @@
+use super::{
+    i2c::EspI2cBus,
+    led::EspStatusLed,
+    network::EspNetworkLink,
+    pressure::EspPressureSensor,
+    sensor::{EspLightSensor, EspTemperatureHumiditySensor},
+    system::EspSystemControl,
+};
+use crate::{
+    hal::{bmp280, i2c_bus::I2cBusManager, sensor::PressureSensor},
+    service::peripheral_service::PeripheralService,
+};
+use ::dht11::Dht11;
+use esp_idf_hal::{
+    delay::{self, Delay},
+    gpio::PinDriver,
+    i2c::{I2cConfig, I2cDriver},
+    peripherals::Peripherals,
+};
+use esp_idf_svc::{
+    eventloop::EspSystemEventLoop,
+    nvs::EspDefaultNvsPartition,
+    wifi::{BlockingWifi, EspWifi},
+};
+use log::{error, info, warn};
+
+// address of the BH1750 with the ADDR pin low
+const BH1750_ADDRESS: u8 = 0x23;
+
+// takes the ESP32 peripherals and wires them to the peripheral service
+pub fn build_peripheral_service(
+    nvs: EspDefaultNvsPartition,
+    wifi_ssid: &str,
+    wifi_password: &str,
+) -> PeripheralService {
+    let peripherals = Peripherals::take().unwrap();
+    let led = PinDriver::output(peripherals.pins.gpio5).unwrap();
+
+    let sys_loop = EspSystemEventLoop::take().unwrap();
+
+    let wifi = BlockingWifi::wrap(
+        EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs)).unwrap(),
+        sys_loop,
+    )
+    .unwrap();
+
+    info!("configuring I2C bus...");
+    let sda = peripherals.pins.gpio21;
+    let scl = peripherals.pins.gpio22;
+    let config = I2cConfig::new().baudrate(400000.into());
+    let i2c_bus = I2cBusManager::new(EspI2cBus::new(
+        I2cDriver::new(peripherals.i2c0, sda, scl, &config).unwrap(),
+    ));
+    let i2c_devices = i2c_bus.scan();
+    if !i2c_devices.contains(&BH1750_ADDRESS) {
+        warn!(
+            "[i2c scan]: no light sensor found at 0x{:02x}",
+            BH1750_ADDRESS
+        );
+    }
+    if !i2c_devices.contains(&bmp280::ADDRESS_PRIMARY)
+        && !i2c_devices.contains(&bmp280::ADDRESS_SECONDARY)
+    {
+        warn!("[i2c scan]: no pressure sensor found");
+    }
+
+    info!("configuring light sensor...");
+    let bh1750 =
+        bh1750_ehal::BH1750::new(i2c_bus.acquire(), delay::Ets, bh1750_ehal::Address::ADDR_L)
+            .unwrap();
+    info!("configuration of light sensor completed");
+
+    info!("configuring pressure sensor...");
+    let pressure_sensor: Option<Box<dyn PressureSensor>> =
+        match EspPressureSensor::probe(i2c_bus.acquire(), bmp280::Settings::default()) {
+            Ok(sensor) => {
+                info!("configuration of pressure sensor completed");
+                Some(Box::new(sensor))
+            }
+            Err(e) => {
+                error!("pressure sensor not available: {}", e);
+                None
+            }
+        };
+
+    let pin = PinDriver::input_output_od(peripherals.pins.gpio15);
+
+    let mut temperature_and_humidity_sensor = Dht11::new(pin.unwrap());
+    warn!(
+        "{:?}",
+        temperature_and_humidity_sensor.perform_measurement(&mut Delay::new(80))
+    );
+
+    PeripheralService::new(
+        Box::new(EspStatusLed::new(led)),
+        Box::new(EspLightSensor::new(bh1750)),
+        Box::new(EspTemperatureHumiditySensor::new(
+            temperature_and_humidity_sensor,
+        )),
+        pressure_sensor,
+        Box::new(EspNetworkLink::new(wifi, wifi_ssid, wifi_password)),
+        Box::new(EspSystemControl),
+    )
+}
@@ -0,0 +1,224 @@
+use crate::{
+    error::{BoxError, ClientError},
+    hal::{
+        http::{read_body, HttpResponse, HttpSettings, HttpTransport},
+        tls::certificate_matches,
+    },
+};
+use embedded_svc::{
+    http::{client::Client as HttpClient, Headers},
+    io::{Read, Write},
+};
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+use esp_idf_sys::{
+    esp, esp_crt_bundle_attach, esp_err_t, esp_tls_set_global_ca_store, mbedtls_ssl_conf_verify,
+    mbedtls_ssl_config, mbedtls_x509_crt, EspError, ESP_ERR_HTTP_EAGAIN, ESP_ERR_TIMEOUT, ESP_OK,
+    MBEDTLS_X509_BADCERT_NOT_TRUSTED,
+};
+use log::{error, info};
+use std::{
+    ffi::{c_void, CString},
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+// fingerprint checked by verify_pinned_certificate, the mbedtls callbacks have no context
+static PINNED_SHA256: OnceLock<[u8; 32]> = OnceLock::new();
+
+// long-lived client: the ESP-IDF HTTP client keeps the connection alive between the
+// requests and decodes the chunked transfer encoding
+pub struct EspHttpTransport {
+    settings: HttpSettings,
+    client: Option<HttpClient<EspHttpConnection>>,
+    last_used: Instant,
+}
+
+impl EspHttpTransport {
+    pub fn new(settings: HttpSettings) -> Self {
+        EspHttpTransport {
+            settings,
+            client: None,
+            last_used: Instant::now(),
+        }
+    }
+
+    fn send(
+        &mut self,
+        url: &str,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+    ) -> Result<HttpResponse, ClientError> {
+        if self.client.is_none() {
+            let configuration = connection_configuration(&self.settings)?;
+            let connection = EspHttpConnection::new(&configuration)
+                .map_err(|e| ClientError::Connect(e.into()))?;
+            self.client = Some(HttpClient::wrap(connection));
+        }
+        let client = self.client.as_mut().unwrap();
+
+        let request = client.post(url, headers);
+
+        if let Err(e) = request {
+            error!("connection error: {:?}", e);
+            return Err(classify(e.0, ClientError::Connect));
+        }
+        let mut request = request.unwrap();
+
+        if let Err(e) = request.write_all(payload) {
+            error!("connection error while trying to write all");
+            return Err(classify(e.0, ClientError::Write));
+        }
+        if let Err(e) = request.flush() {
+            error!("connection error while trying to flush");
+            return Err(classify(e.0, ClientError::Write));
+        }
+        info!("-> POST {}", url);
+        let response = request.submit();
+        if let Err(e) = response {
+            error!("connection error while trying to read response");
+            return Err(classify(e.0, ClientError::Connect));
+        }
+        let mut response = response.unwrap();
+
+        let status = response.status();
+        info!("<- {}", status);
+        let content_length = response.content_len();
+        let etag = response.header("ETag").map(str::to_owned);
+        let body = read_body(content_length, self.settings.max_body_size, |buf| {
+            response
+                .read(buf)
+                .map_err(|e| classify(e.0, ClientError::BodyDecode))
+        });
+        if let Err(e) = &body {
+            error!("error while trying to read response: {}", e);
+        }
+        Ok(HttpResponse {
+            status,
+            body: body?,
+            etag,
+        })
+    }
+}
+
+impl HttpTransport for EspHttpTransport {
+    fn post(
+        &mut self,
+        url: &str,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+    ) -> Result<HttpResponse, ClientError> {
+        self.settings.check_scheme(url)?;
+        if self.client.is_some() && self.last_used.elapsed() >= self.settings.keep_alive_idle {
+            info!("connection idle for too long, reconnecting");
+            self.client = None;
+        }
+        let reused = self.client.is_some();
+
+        let mut result = self.send(url, headers, payload);
+        // the server may have closed the kept alive connection: retry on a new one
+        if reused && matches!(result, Err(ClientError::Connect(_) | ClientError::Write(_))) {
+            info!("kept alive connection broken, reconnecting");
+            self.client = None;
+            result = self.send(url, headers, payload);
+        }
+        // after an error the state of the connection is unknown
+        if result.is_err() {
+            self.client = None;
+        }
+        self.last_used = Instant::now();
+        result
+    }
+}
+
+// configuration of a new connection, with the verification of the server certificate
+pub(crate) fn connection_configuration(
+    settings: &HttpSettings,
+) -> Result<Configuration, ClientError> {
+    let mut configuration = Configuration {
+        timeout: Some(settings.timeout),
+        ..Default::default()
+    };
+    let tls = settings.tls;
+    if let Some(fingerprint) = tls.server_certificate_sha256 {
+        let _ = PINNED_SHA256.set(fingerprint);
+        configuration.crt_bundle_attach = Some(attach_pinned_certificate);
+    } else if let Some(pem) = tls.ca_certificate_pem {
+        install_ca_certificate(pem)?;
+        configuration.use_global_ca_store = true;
+    } else {
+        configuration.crt_bundle_attach = Some(esp_crt_bundle_attach);
+    }
+    Ok(configuration)
+}
+
+// set once the CA certificate is in the global store, shared by HTTP and MQTT
+static CA_STORE_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+// the CA certificate is added to the global store of esp-tls, that wants it NUL
+// terminated; only the first call installs it
+pub(crate) fn install_ca_certificate(pem: &str) -> Result<(), ClientError> {
+    if CA_STORE_INSTALLED.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let pem =
+        CString::new(pem.trim_end_matches('\0')).map_err(|e| ClientError::Connect(e.into()))?;
+    let pem = pem.as_bytes_with_nul();
+    esp!(unsafe { esp_tls_set_global_ca_store(pem.as_ptr(), pem.len() as u32) })
+        .map_err(|e| ClientError::Connect(e.into()))?;
+    CA_STORE_INSTALLED.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+// attaches the certificate bundle, so that mbedtls verifies the certificates of the
+// server, then replaces its verification with the check of the pinned fingerprint
+unsafe extern "C" fn attach_pinned_certificate(conf: *mut c_void) -> esp_err_t {
+    let result = esp_crt_bundle_attach(conf);
+    if result != ESP_OK as esp_err_t {
+        return result;
+    }
+    mbedtls_ssl_conf_verify(
+        conf as *mut mbedtls_ssl_config,
+        Some(verify_pinned_certificate),
+        ptr::null_mut(),
+    );
+    ESP_OK as esp_err_t
+}
+
+// called by mbedtls for each certificate of the chain, the server one has depth 0:
+// only that one is pinned, the rest of the chain is not relevant
+unsafe extern "C" fn verify_pinned_certificate(
+    _context: *mut c_void,
+    certificate: *mut mbedtls_x509_crt,
+    depth: i32,
+    flags: *mut u32,
+) -> i32 {
+    if depth > 0 {
+        *flags = 0;
+        return 0;
+    }
+    let raw = &(*certificate).raw;
+    let der = std::slice::from_raw_parts(raw.p, raw.len as usize);
+    let pinned = PINNED_SHA256
+        .get()
+        .is_some_and(|fingerprint| certificate_matches(der, fingerprint));
+    if pinned {
+        *flags = 0;
+    } else {
+        error!("the certificate of the server does not match the pinned fingerprint");
+        *flags = MBEDTLS_X509_BADCERT_NOT_TRUSTED as u32;
+    }
+    0
+}
+
+// timeouts are reported as such, the other errors with the kind given by the caller
+pub(crate) fn classify(e: EspError, kind: fn(BoxError) -> ClientError) -> ClientError {
+    let code = e.code();
+    if code == ESP_ERR_TIMEOUT as i32 || code == ESP_ERR_HTTP_EAGAIN as i32 {
+        return ClientError::Timeout(e.into());
+    }
+    kind(e.into())
+}
@@ -0,0 +1,50 @@
+use crate::config::config::I2C_TIMEOUT_MILLIS;
+use embedded_hal_0_2::blocking::i2c::{Read, Write, WriteRead};
+use esp_idf_hal::{delay::TickType, i2c::I2cDriver};
+use esp_idf_sys::{EspError, TickType_t};
+use std::time::Duration;
+
+// blocking I2C bus of the ESP32, shared between the devices by the I2C bus manager
+pub struct EspI2cBus {
+    i2c: I2cDriver<'static>,
+    // of each transaction, in FreeRTOS ticks
+    timeout: TickType_t,
+}
+
+impl EspI2cBus {
+    pub fn new(i2c: I2cDriver<'static>) -> Self {
+        EspI2cBus {
+            i2c,
+            timeout: TickType::from(Duration::from_millis(I2C_TIMEOUT_MILLIS)).ticks(),
+        }
+    }
+}
+
+impl Read for EspI2cBus {
+    type Error = EspError;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.i2c.read(address, buffer, self.timeout)
+    }
+}
+
+impl Write for EspI2cBus {
+    type Error = EspError;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.i2c.write(address, bytes, self.timeout)
+    }
+}
+
+impl WriteRead for EspI2cBus {
+    type Error = EspError;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        self.i2c.write_read(address, bytes, buffer, self.timeout)
+    }
+}
@@ -0,0 +1,26 @@
+use crate::{error::PeripheralError, hal::led::StatusLed};
+use esp_idf_hal::gpio::{Gpio5, Output, PinDriver};
+
+pub struct EspStatusLed {
+    led: PinDriver<'static, Gpio5, Output>,
+}
+
+impl EspStatusLed {
+    pub fn new(led: PinDriver<'static, Gpio5, Output>) -> Self {
+        EspStatusLed { led }
+    }
+}
+
+impl StatusLed for EspStatusLed {
+    fn set_high(&mut self) -> Result<(), PeripheralError> {
+        self.led
+            .set_high()
+            .map_err(|e| PeripheralError::Led(e.into()))
+    }
+
+    fn set_low(&mut self) -> Result<(), PeripheralError> {
+        self.led
+            .set_low()
+            .map_err(|e| PeripheralError::Led(e.into()))
+    }
+}
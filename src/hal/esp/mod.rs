@@ -0,0 +1,11 @@
+pub mod board;
+pub mod http;
+pub mod i2c;
+pub mod led;
+pub mod mqtt;
+pub mod network;
+pub mod ota;
+pub mod pressure;
+pub mod sensor;
+pub mod storage;
+pub mod system;
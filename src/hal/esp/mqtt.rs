@@ -0,0 +1,151 @@
+use crate::{
+    error::{BoxError, ClientError},
+    hal::{
+        esp::http::install_ca_certificate,
+        mqtt::{MqttConnection, MqttConnector, MqttMessage, MqttOptions, QoS},
+        tls::TlsSettings,
+    },
+    util::thread_util,
+};
+use embedded_svc::mqtt::client::{Event, QoS as EspQoS};
+use esp_idf_svc::mqtt::client::{EspMqttClient, LwtConfiguration, MqttClientConfiguration};
+use esp_idf_sys::esp_crt_bundle_attach;
+use log::{error, info, warn};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+// pause between the checks of the first connection
+const CONNECT_POLL: Duration = Duration::from_millis(100);
+
+pub struct EspMqttConnector {
+    tls: TlsSettings,
+}
+
+impl EspMqttConnector {
+    pub fn new(tls: TlsSettings) -> Self {
+        EspMqttConnector { tls }
+    }
+}
+
+impl MqttConnector for EspMqttConnector {
+    fn connect(&self, options: &MqttOptions) -> Result<Box<dyn MqttConnection>, ClientError> {
+        options.check_scheme()?;
+        let last_will = options.last_will.as_ref().map(|message| LwtConfiguration {
+            topic: &message.topic,
+            payload: &message.payload,
+            qos: esp_qos(message.qos),
+            retain: message.retain,
+        });
+        let mut configuration = MqttClientConfiguration {
+            client_id: Some(&options.client_id),
+            username: options.username.as_deref(),
+            password: options.password.as_deref(),
+            keep_alive_interval: Some(options.keep_alive),
+            network_timeout: options.timeout,
+            lwt: last_will,
+            ..Default::default()
+        };
+        // the broker is verified with the CA of the server or with the certificate
+        // bundle, the pinned fingerprint is the one of the HTTP server
+        if let Some(pem) = self.tls.ca_certificate_pem {
+            install_ca_certificate(pem)?;
+            configuration.use_global_ca_store = true;
+        } else {
+            configuration.crt_bundle_attach = Some(esp_crt_bundle_attach);
+        }
+
+        let connected = Arc::new(AtomicBool::new(false));
+        let reconnected = Arc::new(AtomicBool::new(false));
+        let callback_connected = connected.clone();
+        let callback_reconnected = reconnected.clone();
+        // esp-mqtt runs the connection in its own task and reconnects by itself
+        let client =
+            EspMqttClient::new(
+                &options.broker_url,
+                &configuration,
+                move |event| match event {
+                    Ok(Event::Connected(_)) => {
+                        info!("[mqtt]: connected to the broker");
+                        callback_connected.store(true, Ordering::SeqCst);
+                        callback_reconnected.store(true, Ordering::SeqCst);
+                    }
+                    Ok(Event::Disconnected) => {
+                        warn!("[mqtt]: disconnected from the broker");
+                        callback_connected.store(false, Ordering::SeqCst);
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!("[mqtt]: {}", e),
+                },
+            )
+            .map_err(|e| ClientError::Connect(e.into()))?;
+
+        let start = Instant::now();
+        while !connected.load(Ordering::SeqCst) && start.elapsed() < options.timeout {
+            thread_util::sleep_time(CONNECT_POLL.as_millis() as u64);
+        }
+        if !connected.load(Ordering::SeqCst) {
+            // the client keeps trying, the messages are sent once it is connected
+            warn!("[mqtt]: broker not reachable yet");
+        }
+        Ok(Box::new(EspMqttConnection {
+            client,
+            connected,
+            reconnected,
+            birth: options.birth.clone(),
+        }))
+    }
+}
+
+pub struct EspMqttConnection {
+    client: EspMqttClient<'static>,
+    connected: Arc<AtomicBool>,
+    // set at each connection, until the birth message is published
+    reconnected: Arc<AtomicBool>,
+    birth: Option<MqttMessage>,
+}
+
+impl EspMqttConnection {
+    fn send(&mut self, message: &MqttMessage) -> Result<(), ClientError> {
+        self.client
+            .publish(
+                &message.topic,
+                esp_qos(message.qos),
+                message.retain,
+                &message.payload,
+            )
+            .map_err(|e| ClientError::Write(e.into()))?;
+        info!("-> MQTT {}", message.topic);
+        Ok(())
+    }
+}
+
+impl MqttConnection for EspMqttConnection {
+    fn publish(&mut self, message: &MqttMessage) -> Result<(), ClientError> {
+        if !self.connected.load(Ordering::SeqCst) {
+            let message: BoxError = "not connected to the broker".into();
+            return Err(ClientError::Connect(message));
+        }
+        if self.reconnected.swap(false, Ordering::SeqCst) {
+            if let Some(birth) = self.birth.clone() {
+                if let Err(e) = self.send(&birth) {
+                    self.reconnected.store(true, Ordering::SeqCst);
+                    return Err(e);
+                }
+            }
+        }
+        self.send(message)
+    }
+}
+
+fn esp_qos(qos: QoS) -> EspQoS {
+    match qos {
+        QoS::AtMostOnce => EspQoS::AtMostOnce,
+        QoS::AtLeastOnce => EspQoS::AtLeastOnce,
+        QoS::ExactlyOnce => EspQoS::ExactlyOnce,
+    }
+}
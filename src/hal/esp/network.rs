@@ -0,0 +1,106 @@
+use crate::{
+    config::config::CLOCK_SYNC_TIMEOUT_SECONDS, error::PeripheralError, hal::network::NetworkLink,
+    util::thread_util,
+};
+use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
+use esp_idf_svc::{
+    sntp::{EspSntp, SyncStatus},
+    wifi::{BlockingWifi, EspWifi, WifiDeviceId},
+};
+use esp_idf_sys::EspError;
+use log::{error, info};
+use std::time::{Duration, Instant};
+
+pub struct EspNetworkLink {
+    wifi: BlockingWifi<EspWifi<'static>>,
+    wifi_ssid: String,
+    wifi_password: String,
+    // kept alive, SNTP keeps correcting the clock (also after a timeout)
+    sntp: Option<EspSntp<'static>>,
+}
+
+impl EspNetworkLink {
+    pub fn new(wifi: BlockingWifi<EspWifi<'static>>, wifi_ssid: &str, wifi_password: &str) -> Self {
+        EspNetworkLink {
+            wifi,
+            wifi_ssid: wifi_ssid.to_owned(),
+            wifi_password: wifi_password.to_owned(),
+            sntp: None,
+        }
+    }
+}
+
+impl NetworkLink for EspNetworkLink {
+    fn connect(&mut self) -> Result<(), PeripheralError> {
+        connect_wifi(&mut self.wifi, &self.wifi_ssid, &self.wifi_password)
+            .map_err(|e| PeripheralError::Network(e.into()))
+    }
+
+    fn is_connected(&self) -> Result<bool, PeripheralError> {
+        self.wifi
+            .is_connected()
+            .map_err(|e| PeripheralError::Network(e.into()))
+    }
+
+    fn get_mac(&self) -> Result<[u8; 6], PeripheralError> {
+        self.wifi
+            .wifi()
+            .driver()
+            .get_mac(WifiDeviceId::Sta)
+            .map_err(|e| PeripheralError::Network(e.into()))
+    }
+
+    fn synchronize_clock(&mut self) -> Result<(), PeripheralError> {
+        if self.sntp.is_none() {
+            let sntp = EspSntp::new_default();
+            if let Err(e) = sntp {
+                error!("unable to set system time");
+                return Err(PeripheralError::Network(e.into()));
+            }
+            self.sntp = sntp.ok();
+        }
+        let sntp = self.sntp.as_ref().unwrap();
+        info!("SNTP initialized, waiting for status!");
+        // a server without NTP access (e.g. on the LAN only) must not block the boot
+        let timeout = Duration::from_secs(CLOCK_SYNC_TIMEOUT_SECONDS);
+        let started = Instant::now();
+        while sntp.get_sync_status() != SyncStatus::Completed {
+            if started.elapsed() >= timeout {
+                let message = format!(
+                    "the clock was not synchronized within {} seconds",
+                    CLOCK_SYNC_TIMEOUT_SECONDS
+                );
+                return Err(PeripheralError::Network(message.into()));
+            }
+            thread_util::sleep_short();
+        }
+        Ok(())
+    }
+}
+
+fn connect_wifi(
+    wifi: &mut BlockingWifi<EspWifi<'static>>,
+    ssid: &str,
+    password: &str,
+) -> Result<(), EspError> {
+    let wifi_configuration: Configuration = Configuration::Client(ClientConfiguration {
+        ssid: ssid.into(),
+        bssid: None,
+        auth_method: AuthMethod::WPA2Personal,
+        password: password.into(),
+        channel: None,
+    });
+    info!("Connecting to SSID: {}", ssid);
+    wifi.set_configuration(&wifi_configuration)?;
+
+    wifi.start()?;
+    info!("Wifi started");
+
+    wifi.connect()?;
+    info!("Wifi connected: {}", ssid);
+
+    wifi.wait_netif_up()?;
+    info!("Wifi netif up");
+
+    Ok(())
+}
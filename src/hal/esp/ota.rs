@@ -0,0 +1,93 @@
+use crate::{
+    error::{ClientError, FirmwareUpdateError},
+    hal::{
+        esp::http::{classify, connection_configuration},
+        http::HttpSettings,
+        ota::FirmwareUpdater,
+    },
+};
+use embedded_svc::http::{client::Client as HttpClient, Headers, Method};
+use esp_idf_svc::{http::client::EspHttpConnection, ota::EspOta};
+use log::{error, info};
+
+// the image is written to the flash in chunks of this size
+const CHUNK_BYTES: usize = 4096;
+
+// streams the image from the server into the OTA partition that is not running, the
+// image does not fit in RAM. The partition table must have two OTA partitions, see
+// partitions.csv.
+pub struct EspFirmwareUpdater {
+    settings: HttpSettings,
+}
+
+impl EspFirmwareUpdater {
+    pub fn new(settings: HttpSettings) -> Self {
+        EspFirmwareUpdater { settings }
+    }
+}
+
+impl FirmwareUpdater for EspFirmwareUpdater {
+    fn update(&mut self, url: &str, headers: &[(&str, &str)]) -> Result<(), FirmwareUpdateError> {
+        self.settings.check_scheme(url)?;
+        let configuration = connection_configuration(&self.settings)?;
+        let connection =
+            EspHttpConnection::new(&configuration).map_err(|e| ClientError::Connect(e.into()))?;
+        let mut client = HttpClient::wrap(connection);
+
+        info!("[ota]: -> GET {}", url);
+        let request = client
+            .request(Method::Get, url, headers)
+            .map_err(|e| classify(e.0, ClientError::Connect))?;
+        let mut response = request
+            .submit()
+            .map_err(|e| classify(e.0, ClientError::Connect))?;
+        let status = response.status();
+        info!("[ota]: <- {}", status);
+        if !(200..=299).contains(&status) {
+            return Err(ClientError::HttpStatus(status).into());
+        }
+        let size = response.content_len();
+        info!("[ota]: downloading {:?} bytes...", size);
+
+        let mut ota = EspOta::new().map_err(|e| FirmwareUpdateError::Flash(e.into()))?;
+        // dropping the update without completing it aborts it, the running firmware
+        // stays the one to boot
+        let mut update = ota
+            .initiate_update()
+            .map_err(|e| FirmwareUpdateError::Flash(e.into()))?;
+        let mut buf = vec![0u8; CHUNK_BYTES];
+        let mut written = 0u64;
+        loop {
+            let bytes_read = response
+                .read(&mut buf)
+                .map_err(|e| classify(e.0, ClientError::BodyDecode))?;
+            if bytes_read == 0 {
+                break;
+            }
+            update
+                .write(&buf[..bytes_read])
+                .map_err(|e| FirmwareUpdateError::Flash(e.into()))?;
+            written += bytes_read as u64;
+        }
+        if size.is_some_and(|size| size != written) {
+            error!("[ota]: {} bytes received, {:?} expected", written, size);
+            let message = format!("the download ended after {} bytes", written);
+            return Err(ClientError::BodyDecode(message.into()).into());
+        }
+        // checks the image before selecting it for the next boot
+        update
+            .complete()
+            .map_err(|e| FirmwareUpdateError::Flash(e.into()))?;
+        info!(
+            "[ota]: {} bytes installed, used from the next restart",
+            written
+        );
+        Ok(())
+    }
+
+    fn mark_running_valid(&mut self) -> Result<(), FirmwareUpdateError> {
+        EspOta::new()
+            .and_then(|mut ota| ota.mark_running_slot_valid())
+            .map_err(|e| FirmwareUpdateError::Flash(e.into()))
+    }
+}
@@ -0,0 +1,88 @@
+use super::i2c::EspI2cBus;
+use crate::{
+    error::PeripheralError,
+    hal::{
+        bmp280::{self, Calibration, Settings},
+        i2c_bus::I2cProxy,
+        sensor::PressureSensor,
+    },
+    util::thread_util,
+};
+use embedded_hal_0_2::blocking::i2c::{Write, WriteRead};
+use log::info;
+
+const SENSOR: &str = "BMP280/BME280";
+
+// BMP280/BME280 pressure sensor driven in forced mode
+pub struct EspPressureSensor {
+    i2c: I2cProxy<EspI2cBus>,
+    address: u8,
+    calibration: Calibration,
+    settings: Settings,
+}
+
+impl EspPressureSensor {
+    // looks for the sensor on both the possible addresses and loads its calibration
+    pub fn probe(
+        mut i2c: I2cProxy<EspI2cBus>,
+        settings: Settings,
+    ) -> Result<Self, PeripheralError> {
+        for address in [bmp280::ADDRESS_PRIMARY, bmp280::ADDRESS_SECONDARY] {
+            let mut chip_id = [0u8; 1];
+            if i2c
+                .write_read(address, &[bmp280::REGISTER_CHIP_ID], &mut chip_id)
+                .is_err()
+            {
+                continue;
+            }
+            if chip_id[0] != bmp280::CHIP_ID_BMP280 && chip_id[0] != bmp280::CHIP_ID_BME280 {
+                continue;
+            }
+            info!(
+                "pressure sensor found at 0x{:02x} (chip id: 0x{:02x})",
+                address, chip_id[0]
+            );
+
+            let mut calibration = [0u8; bmp280::CALIBRATION_LENGTH];
+            i2c.write_read(address, &[bmp280::REGISTER_CALIBRATION], &mut calibration)
+                .map_err(|e| PeripheralError::sensor(SENSOR, e))?;
+            i2c.write(address, &[bmp280::REGISTER_CONFIG, settings.config()])
+                .map_err(|e| PeripheralError::sensor(SENSOR, e))?;
+
+            return Ok(EspPressureSensor {
+                i2c,
+                address,
+                calibration: Calibration::from_bytes(&calibration),
+                settings,
+            });
+        }
+        Err(PeripheralError::sensor(SENSOR, "not found"))
+    }
+}
+
+impl PressureSensor for EspPressureSensor {
+    fn read_pressure(&mut self) -> Result<f64, PeripheralError> {
+        self.i2c
+            .write(
+                self.address,
+                &[bmp280::REGISTER_CTRL_MEAS, self.settings.ctrl_meas_forced()],
+            )
+            .map_err(|e| PeripheralError::sensor(SENSOR, e))?;
+        thread_util::sleep_time((self.settings.max_measurement_time_us() as u64 + 999) / 1000);
+
+        let mut measurement = [0u8; bmp280::MEASUREMENT_LENGTH];
+        self.i2c
+            .write_read(
+                self.address,
+                &[bmp280::REGISTER_PRESS_MSB],
+                &mut measurement,
+            )
+            .map_err(|e| PeripheralError::sensor(SENSOR, e))?;
+        let (adc_p, adc_t) = bmp280::raw_values(&measurement);
+        let (_, t_fine) = self.calibration.compensate_temperature(adc_t);
+        match self.calibration.compensate_pressure(adc_p, t_fine) {
+            Some(pascal) => Ok(pascal / 100.0),
+            None => Err(PeripheralError::sensor(SENSOR, "invalid calibration")),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use super::i2c::EspI2cBus;
+use crate::{
+    error::PeripheralError,
+    hal::{
+        i2c_bus::I2cProxy,
+        sensor::{LightSensor, TemperatureHumiditySensor},
+    },
+};
+use ::dht11::Dht11;
+use bh1750_ehal::BH1750;
+use esp_idf_hal::{
+    delay::{Delay, Ets},
+    gpio::{Gpio15, InputOutput, PinDriver},
+};
+
+pub struct EspLightSensor {
+    bh1750: BH1750<I2cProxy<EspI2cBus>, Ets>,
+}
+
+impl EspLightSensor {
+    pub fn new(bh1750: BH1750<I2cProxy<EspI2cBus>, Ets>) -> Self {
+        EspLightSensor { bh1750 }
+    }
+}
+
+impl LightSensor for EspLightSensor {
+    fn read_lux(&mut self) -> Result<f32, PeripheralError> {
+        self.bh1750
+            .start_measurement(bh1750_ehal::ContinuesMeasurement::HIHGT_RES2);
+
+        let value = self
+            .bh1750
+            .get_measurement(bh1750_ehal::ContinuesMeasurement::HIHGT_RES2);
+        Ok(value as f32)
+    }
+}
+
+pub struct EspTemperatureHumiditySensor {
+    dht11: Dht11<PinDriver<'static, Gpio15, InputOutput>>,
+}
+
+impl EspTemperatureHumiditySensor {
+    pub fn new(dht11: Dht11<PinDriver<'static, Gpio15, InputOutput>>) -> Self {
+        EspTemperatureHumiditySensor { dht11 }
+    }
+}
+
+impl TemperatureHumiditySensor for EspTemperatureHumiditySensor {
+    fn read_temperature_and_humidity(&mut self) -> Result<(f32, f32), PeripheralError> {
+        match self.dht11.perform_measurement(&mut Delay::new(80)) {
+            Ok(r) => Ok(((r.temperature / 10) as f32, (r.humidity / 10) as f32)),
+            Err(dht11::Error::Gpio(e)) => Err(PeripheralError::sensor("DHT11", e)),
+            Err(e) => Err(PeripheralError::sensor("DHT11", format!("{:?}", e))),
+        }
+    }
+}
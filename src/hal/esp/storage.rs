@@ -0,0 +1,61 @@
+use crate::hal::storage::{KeyValueStorage, StorageProvider};
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+pub struct EspNvsStorage {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl KeyValueStorage for EspNvsStorage {
+    fn read(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        // always a blob: set_raw would store the values shorter than 8 bytes as an
+        // integer, that blob_len and get_blob do not find
+        let length = self.nvs.blob_len(key)?;
+        if length.is_none() {
+            return Ok(None);
+        }
+        let mut buffer = vec![0u8; length.unwrap()];
+        let value = self.nvs.get_blob(key, &mut buffer)?;
+        Ok(value.map(|value| value.to_vec()))
+    }
+
+    fn write(&mut self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.nvs.set_blob(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> anyhow::Result<()> {
+        self.nvs.remove(key)?;
+        Ok(())
+    }
+}
+
+pub struct EspNvsStorageProvider {
+    partition: EspDefaultNvsPartition,
+}
+
+impl EspNvsStorageProvider {
+    pub fn new(partition: EspDefaultNvsPartition) -> Self {
+        EspNvsStorageProvider { partition }
+    }
+}
+
+impl StorageProvider for EspNvsStorageProvider {
+    fn open(&mut self, namespace: &str) -> anyhow::Result<Box<dyn KeyValueStorage>> {
+        let nvs = EspNvs::new(self.partition.clone(), namespace, true)?;
+        Ok(Box::new(EspNvsStorage { nvs }))
+    }
+}
+
+// on the board: cargo test, flashed with the espflash runner
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::storage::contract;
+
+    #[test]
+    fn round_trips_values_of_any_length() {
+        let partition = EspDefaultNvsPartition::take().unwrap();
+        let mut storage = EspNvsStorageProvider::new(partition).open("test").unwrap();
+        contract::round_trips_values_of_any_length(storage.as_mut());
+    }
+}
@@ -0,0 +1,17 @@
+use crate::hal::system::SystemControl;
+use esp_idf_hal::reset;
+use esp_idf_sys::esp_random;
+
+pub struct EspSystemControl;
+
+impl SystemControl for EspSystemControl {
+    fn restart(&mut self) {
+        reset::restart();
+    }
+
+    // true random numbers while the radio is on, the Wi-Fi is connected before
+    fn random(&mut self) -> u64 {
+        let (high, low) = unsafe { (esp_random(), esp_random()) };
+        ((high as u64) << 32) | low as u64
+    }
+}
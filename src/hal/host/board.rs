@@ -0,0 +1,44 @@
+use super::{
+    led::HostStatusLed,
+    network::HostNetworkLink,
+    sensor::{StaticLightSensor, StaticPressureSensor, StaticTemperatureHumiditySensor},
+    system::HostSystemControl,
+};
+use crate::service::peripheral_service::PeripheralService;
+
+pub const DEFAULT_MAC_ADDRESS: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+// builds a peripheral service backed by host implementations
+pub fn build_peripheral_service(mac: [u8; 6]) -> PeripheralService {
+    PeripheralService::new(
+        Box::new(HostStatusLed::new()),
+        Box::new(StaticLightSensor::new(120.0)),
+        Box::new(StaticTemperatureHumiditySensor::new(21.0, 45.0)),
+        Some(Box::new(StaticPressureSensor::new(1013.25))),
+        Box::new(HostNetworkLink::new(mac)),
+        Box::new(HostSystemControl),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peripheral_service_reads_the_host_peripherals() {
+        let mut peripheral_service = build_peripheral_service(DEFAULT_MAC_ADDRESS);
+        assert_eq!(peripheral_service.get_mac_address(), "02:00:00:00:00:01");
+        assert!(peripheral_service.retry_wifi_connection_if_necessary_and_return_status());
+        assert!(peripheral_service.synchronize_clock().is_ok());
+        assert_eq!(peripheral_service.get_lux_measure().unwrap(), 120.0);
+        assert_eq!(
+            peripheral_service.get_temperature_and_humidity().unwrap(),
+            (21.0, 45.0)
+        );
+        assert!(peripheral_service.has_pressure_sensor());
+        assert_eq!(
+            peripheral_service.get_pressure_measure().unwrap().unwrap(),
+            1013.25
+        );
+    }
+}
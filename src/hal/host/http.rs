@@ -0,0 +1,493 @@
+use crate::{
+    error::{BoxError, ClientError},
+    hal::http::{read_body, HttpResponse, HttpSettings, HttpTransport},
+};
+use log::info;
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Instant,
+};
+
+// limit of the status line and of the headers together
+const MAX_HEAD_BYTES: usize = 8192;
+
+struct Connection {
+    authority: String,
+    reader: BufReader<TcpStream>,
+    last_used: Instant,
+}
+
+// minimal HTTP/1.1 client over std TcpStream, without TLS; the connection is kept alive between the
+// requests to the same server
+pub struct HostHttpTransport {
+    settings: HttpSettings,
+    connection: Option<Connection>,
+}
+
+impl HostHttpTransport {
+    pub fn new(settings: HttpSettings) -> Self {
+        HostHttpTransport {
+            settings,
+            connection: None,
+        }
+    }
+
+    // the open connection if it can be reused for the authority, a new one otherwise
+    fn connection(&mut self, authority: &str) -> Result<&mut Connection, ClientError> {
+        let reusable = self.connection.as_ref().is_some_and(|connection| {
+            connection.authority == authority
+                && connection.last_used.elapsed() < self.settings.keep_alive_idle
+                && is_open(connection.reader.get_ref())
+        });
+        if !reusable {
+            let stream = connect(authority, &self.settings)?;
+            self.connection = Some(Connection {
+                authority: authority.to_owned(),
+                reader: BufReader::new(stream),
+                last_used: Instant::now(),
+            });
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+
+    fn send(
+        &mut self,
+        authority: &str,
+        request: &[u8],
+        payload: &[u8],
+    ) -> Result<(HttpResponse, bool), ClientError> {
+        let max_body_size = self.settings.max_body_size;
+        let connection = self.connection(authority)?;
+        let stream = connection.reader.get_mut();
+        stream
+            .write_all(request)
+            .and_then(|_| stream.write_all(payload))
+            .and_then(|_| stream.flush())
+            .map_err(|e| ClientError::Write(e.into()))?;
+        connection.last_used = Instant::now();
+        read_response(&mut connection.reader, max_body_size)
+    }
+}
+
+impl HttpTransport for HostHttpTransport {
+    fn post(
+        &mut self,
+        url: &str,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+    ) -> Result<HttpResponse, ClientError> {
+        self.settings.check_scheme(url)?;
+        let (authority, path) = split_url(url)?;
+        let mut request = format!("POST {} HTTP/1.1\r\nhost: {}\r\n", path, authority);
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+
+        let reused = self
+            .connection
+            .as_ref()
+            .is_some_and(|connection| connection.authority == authority);
+        info!("-> POST {}", url);
+        let mut result = self.send(authority, request.as_bytes(), payload);
+        // the server may have closed the kept alive connection: retry on a new one
+        if reused && matches!(result, Err(ClientError::Write(_))) {
+            info!("kept alive connection broken, reconnecting");
+            self.connection = None;
+            result = self.send(authority, request.as_bytes(), payload);
+        }
+
+        match result {
+            Ok((response, keep_alive)) => {
+                if !keep_alive {
+                    self.connection = None;
+                }
+                info!("<- {}", response.status);
+                Ok(response)
+            }
+            Err(e) => {
+                // after an error the state of the connection is unknown
+                self.connection = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+fn connect(authority: &str, settings: &HttpSettings) -> Result<TcpStream, ClientError> {
+    let addresses = authority
+        .to_socket_addrs()
+        .map_err(|e| ClientError::Connect(e.into()))?;
+    let mut last_error: BoxError = format!("no address found for {}", authority).into();
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, settings.timeout) {
+            Ok(stream) => {
+                stream
+                    .set_read_timeout(Some(settings.timeout))
+                    .and_then(|_| stream.set_write_timeout(Some(settings.timeout)))
+                    .map_err(|e| ClientError::Connect(e.into()))?;
+                return Ok(stream);
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => {
+                return Err(ClientError::Timeout(e.into()))
+            }
+            Err(e) => last_error = e.into(),
+        }
+    }
+    Err(ClientError::Connect(last_error))
+}
+
+// false if the server closed the connection (or sent something unexpected) while idle
+fn is_open(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let open = matches!(stream.peek(&mut [0u8; 1]), Err(e) if e.kind() == ErrorKind::WouldBlock);
+    stream.set_nonblocking(false).is_ok() && open
+}
+
+fn read_error(e: io::Error) -> ClientError {
+    match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => ClientError::Timeout(e.into()),
+        _ => ClientError::BodyDecode(e.into()),
+    }
+}
+
+fn malformed(message: &str) -> ClientError {
+    let message: BoxError = format!("malformed response: {}", message).into();
+    ClientError::BodyDecode(message)
+}
+
+// only plain HTTP: there is no TLS implementation on the host
+fn split_url(url: &str) -> Result<(&str, &str), ClientError> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        ClientError::UrlNotAllowed(format!(
+            "HTTPS is not available on the host (no TLS), an http:// URL is needed: {}",
+            url
+        ))
+    })?;
+    match rest.find('/') {
+        Some(index) => Ok((&rest[..index], &rest[index..])),
+        None => Ok((rest, "/")),
+    }
+}
+
+// reads the status, the headers and the body (by length, chunked or until the end),
+// returns also whether the connection can be reused
+fn read_response(
+    reader: &mut impl BufRead,
+    max_body_size: usize,
+) -> Result<(HttpResponse, bool), ClientError> {
+    let mut head_size = 0;
+    let status_line = read_line(reader, &mut head_size)?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| malformed("missing status"))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    let mut close = false;
+    let mut etag = None;
+    loop {
+        let line = read_line(reader, &mut head_size)?;
+        if line.is_empty() {
+            break;
+        }
+        let (name, value) = line.split_once(':').ok_or_else(|| malformed("header"))?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            let length = value
+                .parse::<u64>()
+                .map_err(|_| malformed("content-length"))?;
+            content_length = Some(length);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value
+                .rsplit(',')
+                .next()
+                .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"));
+        } else if name.eq_ignore_ascii_case("connection") {
+            close = value
+                .split(',')
+                .any(|option| option.trim().eq_ignore_ascii_case("close"));
+        } else if name.eq_ignore_ascii_case("etag") {
+            etag = Some(value.to_owned());
+        }
+    }
+
+    // these statuses have no body, the transfer encoding wins over the length (RFC 9112, 6.3)
+    let without_body = status == 204 || status == 304 || (100..200).contains(&status);
+    let body = if without_body {
+        Vec::new()
+    } else if chunked {
+        read_chunked_body(reader, max_body_size)?
+    } else {
+        read_body(content_length, max_body_size, |buf| {
+            reader.read(buf).map_err(read_error)
+        })?
+    };
+    // a body without length ends with the connection
+    let keep_alive = !close && (without_body || chunked || content_length.is_some());
+    Ok((HttpResponse { status, body, etag }, keep_alive))
+}
+
+fn read_chunked_body(reader: &mut impl BufRead, max_size: usize) -> Result<Vec<u8>, ClientError> {
+    let mut body = Vec::new();
+    loop {
+        let line = read_line(reader, &mut 0)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16).map_err(|_| malformed("chunk size"))?;
+        if size == 0 {
+            break;
+        }
+        if body.len() as u64 + size > max_size as u64 {
+            return Err(ClientError::ResponseTooLarge(max_size));
+        }
+        let chunk = read_body(Some(size), max_size, |buf| {
+            reader.read(buf).map_err(read_error)
+        })?;
+        body.extend_from_slice(&chunk);
+        if !read_line(reader, &mut 0)?.is_empty() {
+            return Err(malformed("chunk not terminated"));
+        }
+    }
+    // trailers are ignored
+    let mut head_size = 0;
+    while !read_line(reader, &mut head_size)?.is_empty() {}
+    Ok(body)
+}
+
+// reads a line without its CRLF, size counts the bytes read to bound the head
+fn read_line(reader: &mut impl BufRead, size: &mut usize) -> Result<String, ClientError> {
+    let mut line = Vec::new();
+    let limit = (MAX_HEAD_BYTES - (*size).min(MAX_HEAD_BYTES)) as u64;
+    let bytes_read = reader
+        .by_ref()
+        .take(limit)
+        .read_until(b'\n', &mut line)
+        .map_err(read_error)?;
+    *size += bytes_read;
+    if !line.ends_with(b"\n") {
+        if bytes_read as u64 == limit {
+            return Err(malformed("headers too large"));
+        }
+        return Err(malformed("unexpected end of the response"));
+    }
+    line.pop();
+    if line.ends_with(b"\r") {
+        line.pop();
+    }
+    String::from_utf8(line).map_err(|_| malformed("headers"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, net::TcpListener, thread};
+
+    fn parse(response: &str, max_body_size: usize) -> Result<(HttpResponse, bool), ClientError> {
+        read_response(
+            &mut Cursor::new(response.as_bytes().to_vec()),
+            max_body_size,
+        )
+    }
+
+    fn is_malformed(result: Result<(HttpResponse, bool), ClientError>, message: &str) -> bool {
+        match result {
+            Err(ClientError::BodyDecode(e)) => {
+                e.to_string() == format!("malformed response: {}", message)
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn reads_a_body_by_length() {
+        let (response, keep_alive) = parse(
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nETag: \"v1\"\r\n\r\nhello",
+            100,
+        )
+        .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+        assert_eq!(response.etag.as_deref(), Some("\"v1\""));
+        assert!(keep_alive);
+    }
+
+    #[test]
+    fn reads_a_chunked_body() {
+        let (response, keep_alive) = parse(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 99\r\n\r\n\
+             5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: ignored\r\n\r\n",
+            100,
+        )
+        .unwrap();
+        assert_eq!(response.body, b"hello, world");
+        assert!(keep_alive);
+    }
+
+    #[test]
+    fn reads_until_the_end_without_length() {
+        let (response, keep_alive) = parse("HTTP/1.0 200 OK\r\n\r\nuntil the end", 100).unwrap();
+        assert_eq!(response.body, b"until the end");
+        // the end of the body is the end of the connection
+        assert!(!keep_alive);
+    }
+
+    #[test]
+    fn statuses_without_body() {
+        let (response, keep_alive) = parse("HTTP/1.1 204 No Content\r\n\r\n", 100).unwrap();
+        assert_eq!(response.status, 204);
+        assert!(response.body.is_empty());
+        assert!(keep_alive);
+    }
+
+    #[test]
+    fn connection_close_is_not_reused() {
+        let (_, keep_alive) = parse(
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: keep-alive, close\r\n\r\nok",
+            100,
+        )
+        .unwrap();
+        assert!(!keep_alive);
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_maximum() {
+        let length = parse(
+            "HTTP/1.1 200 OK\r\nContent-Length: 11\r\n\r\nhello world",
+            10,
+        );
+        assert!(matches!(length, Err(ClientError::ResponseTooLarge(10))));
+        let chunked = parse(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n6\r\nhello \r\n5\r\nworld\r\n0\r\n\r\n",
+            10,
+        );
+        assert!(matches!(chunked, Err(ClientError::ResponseTooLarge(10))));
+        let until_the_end = parse("HTTP/1.1 200 OK\r\n\r\nhello world", 10);
+        assert!(matches!(
+            until_the_end,
+            Err(ClientError::ResponseTooLarge(10))
+        ));
+    }
+
+    #[test]
+    fn fails_if_the_connection_ends_early() {
+        let length = parse("HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello", 100);
+        assert!(matches!(length, Err(ClientError::BodyDecode(_))));
+        let chunk = parse(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\na\r\nhello",
+            100,
+        );
+        assert!(matches!(chunk, Err(ClientError::BodyDecode(_))));
+        let last_chunk = parse(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n",
+            100,
+        );
+        assert!(is_malformed(last_chunk, "unexpected end of the response"));
+        let head = parse("HTTP/1.1 200 OK\r\nContent-Len", 100);
+        assert!(is_malformed(head, "unexpected end of the response"));
+    }
+
+    #[test]
+    fn rejects_malformed_responses() {
+        assert!(is_malformed(
+            parse("HTTP/1.1 OK\r\n\r\n", 100),
+            "missing status"
+        ));
+        assert!(is_malformed(
+            parse("HTTP/1.1 200 OK\r\nno colon\r\n\r\n", 100),
+            "header"
+        ));
+        assert!(is_malformed(
+            parse(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nxyz\r\n",
+                100
+            ),
+            "chunk size"
+        ));
+        assert!(is_malformed(
+            parse(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhello\r\n0\r\n\r\n",
+                100
+            ),
+            "chunk not terminated"
+        ));
+        let huge_header = format!(
+            "HTTP/1.1 200 OK\r\nX-Big: {}\r\n\r\n",
+            "a".repeat(MAX_HEAD_BYTES)
+        );
+        assert!(is_malformed(parse(&huge_header, 100), "headers too large"));
+    }
+
+    #[test]
+    fn splits_the_url() {
+        assert_eq!(
+            split_url("http://localhost:8080/api/v1?x=1").unwrap(),
+            ("localhost:8080", "/api/v1?x=1")
+        );
+        assert_eq!(split_url("http://localhost").unwrap(), ("localhost", "/"));
+        assert!(matches!(
+            split_url("https://localhost/api"),
+            Err(ClientError::UrlNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn posts_and_reuses_the_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        // a single connection serving two requests
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .unwrap()
+                    .parse()
+                    .unwrap();
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push((head, body));
+                reader
+                    .get_mut()
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                    .unwrap();
+            }
+            requests
+        });
+
+        let mut transport = HostHttpTransport::new(HttpSettings {
+            allow_plain_http: true,
+            ..Default::default()
+        });
+        let url = format!("http://{}/api/submit", address);
+        for payload in [&b"first"[..], &b"second"[..]] {
+            let length = payload.len().to_string();
+            let response = transport
+                .post(&url, &[("content-length", &length)], payload)
+                .unwrap();
+            assert_eq!((response.status, response.body), (200, b"ok".to_vec()));
+        }
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].0.starts_with("POST /api/submit HTTP/1.1\r\n"));
+        assert!(requests[0].0.contains(&format!("host: {}\r\n", address)));
+        assert_eq!(requests[0].1, b"first");
+        assert_eq!(requests[1].1, b"second");
+    }
+}
@@ -0,0 +1,23 @@
+use crate::{error::PeripheralError, hal::led::StatusLed};
+use log::debug;
+
+// LED that only logs its state changes
+pub struct HostStatusLed;
+
+impl HostStatusLed {
+    pub fn new() -> Self {
+        HostStatusLed
+    }
+}
+
+impl StatusLed for HostStatusLed {
+    fn set_high(&mut self) -> Result<(), PeripheralError> {
+        debug!("[led]: on");
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), PeripheralError> {
+        debug!("[led]: off");
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+pub mod board;
+pub mod http;
+pub mod led;
+pub mod mqtt;
+pub mod network;
+pub mod ota;
+pub mod sensor;
+pub mod storage;
+pub mod system;
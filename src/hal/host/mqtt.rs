@@ -0,0 +1,285 @@
+use crate::{
+    error::{BoxError, ClientError},
+    hal::mqtt::{MqttConnection, MqttConnector, MqttMessage, MqttOptions, QoS},
+};
+use log::{info, warn};
+use std::{
+    io::{self, BufReader, ErrorKind, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Instant,
+};
+
+const DEFAULT_PORT: u16 = 1883;
+
+// packet types of MQTT 3.1.1, in the high nibble of the first byte
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const PUBREC: u8 = 0x50;
+const PUBREL: u8 = 0x62;
+const PUBCOMP: u8 = 0x70;
+const PINGREQ: u8 = 0xc0;
+const PINGRESP: u8 = 0xd0;
+
+pub struct HostMqttConnector;
+
+impl MqttConnector for HostMqttConnector {
+    fn connect(&self, options: &MqttOptions) -> Result<Box<dyn MqttConnection>, ClientError> {
+        options.check_scheme()?;
+        let mut connection = HostMqttConnection {
+            options: options.clone(),
+            stream: None,
+            last_used: Instant::now(),
+            next_packet_id: 1,
+        };
+        connection.ensure_connected()?;
+        Ok(Box::new(connection))
+    }
+}
+
+// minimal MQTT 3.1.1 publisher over std TcpStream, without TLS; a lost connection is
+// opened again at the next message
+pub struct HostMqttConnection {
+    options: MqttOptions,
+    stream: Option<BufReader<TcpStream>>,
+    last_used: Instant,
+    next_packet_id: u16,
+}
+
+impl HostMqttConnection {
+    fn ensure_connected(&mut self) -> Result<(), ClientError> {
+        // the broker closes a connection silent for longer than the keep alive
+        if self.stream.is_some() && self.last_used.elapsed() >= self.options.keep_alive {
+            if let Err(e) = self.ping() {
+                warn!("[mqtt]: connection lost ({}), reconnecting", e);
+                self.stream = None;
+            }
+        }
+        if self.stream.is_some() {
+            return Ok(());
+        }
+        let mut stream = BufReader::new(open(&self.options)?);
+        write_packet(stream.get_mut(), CONNECT, &connect_packet(&self.options))
+            .map_err(|e| ClientError::Write(e.into()))?;
+        let (packet_type, body) = read_packet(&mut stream).map_err(read_error)?;
+        if packet_type != CONNACK || body.len() != 2 {
+            return Err(protocol_error("CONNACK expected"));
+        }
+        if body[1] != 0 {
+            let message: BoxError =
+                format!("connection refused by the broker, code {}", body[1]).into();
+            return Err(ClientError::Connect(message));
+        }
+        info!("[mqtt]: connected to {}", self.options.broker_url);
+        self.stream = Some(stream);
+        self.last_used = Instant::now();
+        if let Some(birth) = self.options.birth.clone() {
+            self.send(&birth)?;
+        }
+        Ok(())
+    }
+
+    fn ping(&mut self) -> Result<(), ClientError> {
+        let stream = self.stream.as_mut().unwrap();
+        write_packet(stream.get_mut(), PINGREQ, &[]).map_err(|e| ClientError::Write(e.into()))?;
+        self.expect(PINGRESP, None)
+    }
+
+    fn send(&mut self, message: &MqttMessage) -> Result<(), ClientError> {
+        let packet_id = match message.qos {
+            QoS::AtMostOnce => None,
+            _ => Some(self.take_packet_id()),
+        };
+        let mut body = encode_string(&message.topic);
+        if let Some(packet_id) = packet_id {
+            body.extend_from_slice(&packet_id.to_be_bytes());
+        }
+        body.extend_from_slice(&message.payload);
+        let flags = (qos_level(message.qos) << 1) | message.retain as u8;
+
+        let stream = self.stream.as_mut().unwrap();
+        write_packet(stream.get_mut(), PUBLISH | flags, &body)
+            .map_err(|e| ClientError::Write(e.into()))?;
+        match message.qos {
+            QoS::AtMostOnce => {}
+            QoS::AtLeastOnce => self.expect(PUBACK, packet_id)?,
+            QoS::ExactlyOnce => {
+                self.expect(PUBREC, packet_id)?;
+                let stream = self.stream.as_mut().unwrap();
+                write_packet(stream.get_mut(), PUBREL, &packet_id.unwrap().to_be_bytes())
+                    .map_err(|e| ClientError::Write(e.into()))?;
+                self.expect(PUBCOMP, packet_id)?;
+            }
+        }
+        self.last_used = Instant::now();
+        info!("-> MQTT {}", message.topic);
+        Ok(())
+    }
+
+    // waits for the packet, skipping the others (e.g. acknowledgements of old messages)
+    fn expect(&mut self, packet_type: u8, packet_id: Option<u16>) -> Result<(), ClientError> {
+        let stream = self.stream.as_mut().unwrap();
+        loop {
+            let (received_type, body) = read_packet(stream).map_err(read_error)?;
+            let received_id = (body.len() >= 2).then(|| u16::from_be_bytes([body[0], body[1]]));
+            if received_type == packet_type && (packet_id.is_none() || received_id == packet_id) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn take_packet_id(&mut self) -> u16 {
+        let packet_id = self.next_packet_id;
+        // 0 is not a valid packet identifier
+        self.next_packet_id = self.next_packet_id.checked_add(1).unwrap_or(1);
+        packet_id
+    }
+}
+
+impl MqttConnection for HostMqttConnection {
+    fn publish(&mut self, message: &MqttMessage) -> Result<(), ClientError> {
+        let result = self.ensure_connected().and_then(|_| self.send(message));
+        // after an error the state of the connection is unknown
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+}
+
+// only plain MQTT: there is no TLS implementation on the host
+fn open(options: &MqttOptions) -> Result<TcpStream, ClientError> {
+    let authority = options.broker_url.strip_prefix("mqtt://").ok_or_else(|| {
+        let message: BoxError = format!(
+            "unsupported broker URL (MQTT over TLS needs the ESP): {}",
+            options.broker_url
+        )
+        .into();
+        ClientError::Connect(message)
+    })?;
+    let authority = authority.trim_end_matches('/');
+    let address = if authority.contains(':') {
+        authority.to_owned()
+    } else {
+        format!("{}:{}", authority, DEFAULT_PORT)
+    };
+    let addresses = address
+        .to_socket_addrs()
+        .map_err(|e| ClientError::Connect(e.into()))?;
+    let mut last_error: BoxError = format!("no address found for {}", address).into();
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, options.timeout) {
+            Ok(stream) => {
+                stream
+                    .set_read_timeout(Some(options.timeout))
+                    .and_then(|_| stream.set_write_timeout(Some(options.timeout)))
+                    .map_err(|e| ClientError::Connect(e.into()))?;
+                return Ok(stream);
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => {
+                return Err(ClientError::Timeout(e.into()))
+            }
+            Err(e) => last_error = e.into(),
+        }
+    }
+    Err(ClientError::Connect(last_error))
+}
+
+fn connect_packet(options: &MqttOptions) -> Vec<u8> {
+    let mut flags = 0x02; // clean session
+    if let Some(last_will) = &options.last_will {
+        flags |= 0x04 | (qos_level(last_will.qos) << 3) | ((last_will.retain as u8) << 5);
+    }
+    if options.username.is_some() {
+        flags |= 0x80;
+    }
+    if options.password.is_some() {
+        flags |= 0x40;
+    }
+    let mut body = encode_string("MQTT");
+    body.push(4); // protocol level of 3.1.1
+    body.push(flags);
+    let keep_alive = options.keep_alive.as_secs().min(u16::MAX as u64) as u16;
+    body.extend_from_slice(&keep_alive.to_be_bytes());
+    body.extend(encode_string(&options.client_id));
+    if let Some(last_will) = &options.last_will {
+        body.extend(encode_string(&last_will.topic));
+        body.extend_from_slice(&(last_will.payload.len() as u16).to_be_bytes());
+        body.extend_from_slice(&last_will.payload);
+    }
+    if let Some(username) = &options.username {
+        body.extend(encode_string(username));
+    }
+    if let Some(password) = &options.password {
+        body.extend(encode_string(password));
+    }
+    body
+}
+
+fn qos_level(qos: QoS) -> u8 {
+    match qos {
+        QoS::AtMostOnce => 0,
+        QoS::AtLeastOnce => 1,
+        QoS::ExactlyOnce => 2,
+    }
+}
+
+fn encode_string(value: &str) -> Vec<u8> {
+    let mut encoded = (value.len() as u16).to_be_bytes().to_vec();
+    encoded.extend_from_slice(value.as_bytes());
+    encoded
+}
+
+fn write_packet(stream: &mut TcpStream, first_byte: u8, body: &[u8]) -> io::Result<()> {
+    let mut packet = vec![first_byte];
+    // remaining length: 7 bits per byte, the high bit tells that another byte follows
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 128) as u8;
+        length /= 128;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    stream.write_all(&packet)?;
+    stream.flush()
+}
+
+// returns the packet type (without the flags) and the rest of the packet
+fn read_packet(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte)?;
+    let packet_type = byte[0] & 0xf0;
+    let mut length = 0usize;
+    for shift in [0, 7, 14, 21] {
+        stream.read_exact(&mut byte)?;
+        length |= ((byte[0] & 0x7f) as usize) << shift;
+        if byte[0] & 0x80 == 0 {
+            let mut body = vec![0u8; length];
+            stream.read_exact(&mut body)?;
+            return Ok((packet_type, body));
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::InvalidData,
+        "malformed remaining length",
+    ))
+}
+
+fn read_error(e: io::Error) -> ClientError {
+    match e.kind() {
+        ErrorKind::TimedOut | ErrorKind::WouldBlock => ClientError::Timeout(e.into()),
+        _ => ClientError::Connect(e.into()),
+    }
+}
+
+fn protocol_error(message: &str) -> ClientError {
+    let message: BoxError = format!("unexpected answer of the broker: {}", message).into();
+    ClientError::Connect(message)
+}
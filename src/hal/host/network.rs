@@ -0,0 +1,45 @@
+use crate::{error::PeripheralError, hal::network::NetworkLink};
+
+// the host is considered always connected, its clock is already synchronized
+pub struct HostNetworkLink {
+    mac: [u8; 6],
+}
+
+impl HostNetworkLink {
+    pub fn new(mac: [u8; 6]) -> Self {
+        HostNetworkLink { mac }
+    }
+}
+
+impl NetworkLink for HostNetworkLink {
+    fn connect(&mut self) -> Result<(), PeripheralError> {
+        Ok(())
+    }
+
+    fn is_connected(&self) -> Result<bool, PeripheralError> {
+        Ok(true)
+    }
+
+    fn get_mac(&self) -> Result<[u8; 6], PeripheralError> {
+        Ok(self.mac)
+    }
+
+    fn synchronize_clock(&mut self) -> Result<(), PeripheralError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn always_connected_with_the_given_mac() {
+        let mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut network: Box<dyn NetworkLink> = Box::new(HostNetworkLink::new(mac));
+        assert!(network.connect().is_ok());
+        assert!(network.is_connected().unwrap());
+        assert_eq!(network.get_mac().unwrap(), mac);
+        assert!(network.synchronize_clock().is_ok());
+    }
+}
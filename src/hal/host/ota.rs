@@ -0,0 +1,38 @@
+use crate::{error::FirmwareUpdateError, hal::ota::FirmwareUpdater};
+use log::warn;
+
+// the host runs the firmware as a process, there is no partition to update
+pub struct HostFirmwareUpdater;
+
+impl FirmwareUpdater for HostFirmwareUpdater {
+    fn update(&mut self, url: &str, _: &[(&str, &str)]) -> Result<(), FirmwareUpdateError> {
+        warn!(
+            "[ota]: update to {} requested, not possible on the host",
+            url
+        );
+        Err(FirmwareUpdateError::NotSupported(
+            "the host has no OTA partitions",
+        ))
+    }
+
+    // nothing can be rolled back
+    fn mark_running_valid(&mut self) -> Result<(), FirmwareUpdateError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn update_is_refused() {
+        let result = HostFirmwareUpdater.update("http://localhost:8080/firmware.bin", &[]);
+        assert!(matches!(result, Err(FirmwareUpdateError::NotSupported(_))));
+    }
+
+    #[test]
+    fn running_firmware_is_always_valid() {
+        assert!(HostFirmwareUpdater.mark_running_valid().is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{
+    error::PeripheralError,
+    hal::sensor::{LightSensor, PressureSensor, TemperatureHumiditySensor},
+};
+
+// light sensor that always returns the same value
+pub struct StaticLightSensor {
+    lux: f32,
+}
+
+impl StaticLightSensor {
+    pub fn new(lux: f32) -> Self {
+        StaticLightSensor { lux }
+    }
+}
+
+impl LightSensor for StaticLightSensor {
+    fn read_lux(&mut self) -> Result<f32, PeripheralError> {
+        Ok(self.lux)
+    }
+}
+
+// temperature and humidity sensor that always returns the same values
+pub struct StaticTemperatureHumiditySensor {
+    temperature: f32,
+    humidity: f32,
+}
+
+impl StaticTemperatureHumiditySensor {
+    pub fn new(temperature: f32, humidity: f32) -> Self {
+        StaticTemperatureHumiditySensor {
+            temperature,
+            humidity,
+        }
+    }
+}
+
+impl TemperatureHumiditySensor for StaticTemperatureHumiditySensor {
+    fn read_temperature_and_humidity(&mut self) -> Result<(f32, f32), PeripheralError> {
+        Ok((self.temperature, self.humidity))
+    }
+}
+
+// pressure sensor that always returns the same value
+pub struct StaticPressureSensor {
+    pressure: f64,
+}
+
+impl StaticPressureSensor {
+    pub fn new(pressure: f64) -> Self {
+        StaticPressureSensor { pressure }
+    }
+}
+
+impl PressureSensor for StaticPressureSensor {
+    fn read_pressure(&mut self) -> Result<f64, PeripheralError> {
+        Ok(self.pressure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn return_the_given_values() {
+        let mut light: Box<dyn LightSensor> = Box::new(StaticLightSensor::new(120.0));
+        let mut temperature_humidity: Box<dyn TemperatureHumiditySensor> =
+            Box::new(StaticTemperatureHumiditySensor::new(21.0, 45.0));
+        let mut pressure: Box<dyn PressureSensor> = Box::new(StaticPressureSensor::new(1013.25));
+        for _ in 0..2 {
+            assert_eq!(light.read_lux().unwrap(), 120.0);
+            assert_eq!(
+                temperature_humidity
+                    .read_temperature_and_humidity()
+                    .unwrap(),
+                (21.0, 45.0)
+            );
+            assert_eq!(pressure.read_pressure().unwrap(), 1013.25);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::hal::storage::{KeyValueStorage, StorageProvider};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+type Namespace = Rc<RefCell<HashMap<String, Vec<u8>>>>;
+
+// volatile storage, the content is lost when the process terminates
+pub struct InMemoryStorage {
+    values: Namespace,
+}
+
+impl KeyValueStorage for InMemoryStorage {
+    fn read(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.values.borrow().get(key).cloned())
+    }
+
+    fn write(&mut self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.values
+            .borrow_mut()
+            .insert(key.to_owned(), value.to_vec());
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> anyhow::Result<()> {
+        self.values.borrow_mut().remove(key);
+        Ok(())
+    }
+}
+
+// the storages opened on the same namespace share their content
+pub struct InMemoryStorageProvider {
+    namespaces: HashMap<String, Namespace>,
+}
+
+impl InMemoryStorageProvider {
+    pub fn new() -> Self {
+        InMemoryStorageProvider {
+            namespaces: HashMap::new(),
+        }
+    }
+}
+
+impl StorageProvider for InMemoryStorageProvider {
+    fn open(&mut self, namespace: &str) -> anyhow::Result<Box<dyn KeyValueStorage>> {
+        let values = self.namespaces.entry(namespace.to_owned()).or_default();
+        Ok(Box::new(InMemoryStorage {
+            values: values.clone(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::storage::contract;
+
+    #[test]
+    fn reads_what_was_written() {
+        let mut storage = InMemoryStorageProvider::new().open("test").unwrap();
+        assert_eq!(storage.read("key").unwrap(), None);
+        storage.write("key", b"value").unwrap();
+        assert_eq!(storage.read("key").unwrap(), Some(b"value".to_vec()));
+        storage.write("key", b"other").unwrap();
+        assert_eq!(storage.read("key").unwrap(), Some(b"other".to_vec()));
+    }
+
+    #[test]
+    fn round_trips_values_of_any_length() {
+        let mut storage = InMemoryStorageProvider::new().open("test").unwrap();
+        contract::round_trips_values_of_any_length(storage.as_mut());
+    }
+
+    #[test]
+    fn remove_deletes_the_key() {
+        let mut storage = InMemoryStorageProvider::new().open("test").unwrap();
+        storage.write("key", b"value").unwrap();
+        storage.remove("key").unwrap();
+        assert_eq!(storage.read("key").unwrap(), None);
+        // removing a missing key is not an error, like on NVS
+        storage.remove("key").unwrap();
+    }
+
+    #[test]
+    fn namespaces_are_shared_by_name() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut first = provider.open("first").unwrap();
+        let mut again = provider.open("first").unwrap();
+        let mut second = provider.open("second").unwrap();
+        first.write("key", b"value").unwrap();
+        assert_eq!(again.read("key").unwrap(), Some(b"value".to_vec()));
+        assert_eq!(second.read("key").unwrap(), None);
+    }
+}
@@ -0,0 +1,21 @@
+use crate::hal::system::SystemControl;
+use log::warn;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+// a restart ends the process, the next run starts from scratch like a rebooted board
+pub struct HostSystemControl;
+
+impl SystemControl for HostSystemControl {
+    fn restart(&mut self) {
+        warn!("[system]: restart requested, exiting");
+        std::process::exit(0);
+    }
+
+    // the keys of RandomState come from the random source of the operating system
+    fn random(&mut self) -> u64 {
+        RandomState::new().build_hasher().finish()
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    config::config::{
+        ALLOW_PLAIN_HTTP, HTTP_KEEP_ALIVE_IDLE_SECONDS, HTTP_TIMEOUT_MILLIS,
+        MAX_RESPONSE_BODY_BYTES,
+    },
+    error::{BoxError, ClientError},
+    hal::tls::TlsSettings,
+};
+use std::time::Duration;
+
+const READ_CHUNK_BYTES: usize = 512;
+
+// the host transports have no TLS: there plain HTTP (and MQTT) is always allowed
+pub const PLAIN_HTTP_ALLOWED: bool = ALLOW_PLAIN_HTTP || cfg!(not(feature = "hal"));
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+    // version of the returned resource, if the server tells it
+    pub etag: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HttpSettings {
+    // timeout of the connection and of each read and write
+    pub timeout: Duration,
+    // a connection idle for longer is not reused, the server has probably closed it
+    pub keep_alive_idle: Duration,
+    // longer response bodies are rejected
+    pub max_body_size: usize,
+    // if false only https URLs are accepted
+    pub allow_plain_http: bool,
+    // used only by the ESP transport, the host one has no TLS
+    #[cfg_attr(not(feature = "hal"), allow(dead_code))]
+    pub tls: TlsSettings,
+}
+
+impl Default for HttpSettings {
+    fn default() -> Self {
+        HttpSettings {
+            timeout: Duration::from_millis(HTTP_TIMEOUT_MILLIS),
+            keep_alive_idle: Duration::from_secs(HTTP_KEEP_ALIVE_IDLE_SECONDS),
+            max_body_size: MAX_RESPONSE_BODY_BYTES,
+            allow_plain_http: PLAIN_HTTP_ALLOWED,
+            tls: TlsSettings::from_config(),
+        }
+    }
+}
+
+impl HttpSettings {
+    // fails if the URL would send the data in cleartext while it is not allowed
+    pub fn check_scheme(&self, url: &str) -> Result<(), ClientError> {
+        if url.starts_with("https://") || (self.allow_plain_http && url.starts_with("http://")) {
+            return Ok(());
+        }
+        let message = if url.starts_with("http://") {
+            format!(
+                "plain HTTP is disabled (ALLOW_PLAIN_HTTP in src/config/config.rs), an https:// URL is needed: {}",
+                url
+            )
+        } else {
+            format!(
+                "unsupported scheme, expected {}: {}",
+                if self.allow_plain_http {
+                    "https:// or http://"
+                } else {
+                    "https://"
+                },
+                url
+            )
+        };
+        Err(ClientError::UrlNotAllowed(message))
+    }
+}
+
+// transport used by the client service to talk with the server; the implementations
+// keep the connection open between the requests and open a new one when it is broken
+pub trait HttpTransport {
+    fn post(
+        &mut self,
+        url: &str,
+        headers: &[(&str, &str)],
+        payload: &[u8],
+    ) -> Result<HttpResponse, ClientError>;
+}
+
+// reads a body of content_length bytes, or until the end of the stream if the length is
+// unknown, failing instead of truncating it if it is longer than max_size.
+// read works like std::io::Read::read, 0 means the end of the stream.
+pub fn read_body(
+    content_length: Option<u64>,
+    max_size: usize,
+    mut read: impl FnMut(&mut [u8]) -> Result<usize, ClientError>,
+) -> Result<Vec<u8>, ClientError> {
+    if let Some(content_length) = content_length {
+        if content_length > max_size as u64 {
+            return Err(ClientError::ResponseTooLarge(max_size));
+        }
+    }
+    let expected = content_length.map(|content_length| content_length as usize);
+    let mut body = Vec::with_capacity(expected.unwrap_or(0));
+    let mut buf = [0u8; READ_CHUNK_BYTES];
+    loop {
+        // with a known length no more than the body is read: the connection may be reused
+        let wanted = match expected {
+            Some(expected) if body.len() >= expected => return Ok(body),
+            Some(expected) => (expected - body.len()).min(buf.len()),
+            None => buf.len(),
+        };
+        let bytes_read = read(&mut buf[..wanted])?;
+        if bytes_read == 0 {
+            break;
+        }
+        if body.len() + bytes_read > max_size {
+            return Err(ClientError::ResponseTooLarge(max_size));
+        }
+        body.extend_from_slice(&buf[..bytes_read]);
+    }
+    if let Some(expected) = expected {
+        let message: BoxError = format!(
+            "response truncated: {} of {} bytes received",
+            body.len(),
+            expected
+        )
+        .into();
+        return Err(ClientError::BodyDecode(message));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reads from the data, at most chunk bytes at a time like a socket
+    fn reader(
+        data: &[u8],
+        chunk: usize,
+    ) -> impl FnMut(&mut [u8]) -> Result<usize, ClientError> + '_ {
+        let mut position = 0;
+        move |buf| {
+            let size = buf.len().min(chunk).min(data.len() - position);
+            buf[..size].copy_from_slice(&data[position..position + size]);
+            position += size;
+            Ok(size)
+        }
+    }
+
+    #[test]
+    fn reads_the_content_length() {
+        let data = b"hello world, and the next response";
+        let body = read_body(Some(11), 100, reader(data, 4)).unwrap();
+        assert_eq!(body, b"hello world");
+    }
+
+    #[test]
+    fn does_not_read_beyond_the_content_length() {
+        let data = b"0123456789";
+        let mut position = 0;
+        let body = read_body(Some(4), 100, |buf| {
+            let size = buf.len().min(data.len() - position);
+            buf[..size].copy_from_slice(&data[position..position + size]);
+            position += size;
+            Ok(size)
+        })
+        .unwrap();
+        assert_eq!(body, b"0123");
+        // the rest belongs to the next response on the same connection
+        assert_eq!(position, 4);
+    }
+
+    #[test]
+    fn reads_until_the_end_without_length() {
+        let data = vec![7u8; 2000];
+        let body = read_body(None, 4096, reader(&data, 300)).unwrap();
+        assert_eq!(body, data);
+    }
+
+    #[test]
+    fn rejects_a_content_length_over_the_maximum() {
+        let mut reads = 0;
+        let result = read_body(Some(101), 100, |_| {
+            reads += 1;
+            Ok(0)
+        });
+        assert!(matches!(result, Err(ClientError::ResponseTooLarge(100))));
+        // rejected before reading it
+        assert_eq!(reads, 0);
+    }
+
+    #[test]
+    fn rejects_a_body_without_length_over_the_maximum() {
+        let data = vec![1u8; 101];
+        let result = read_body(None, 100, reader(&data, 64));
+        assert!(matches!(result, Err(ClientError::ResponseTooLarge(100))));
+        // exactly the maximum is accepted
+        assert_eq!(read_body(None, 101, reader(&data, 64)).unwrap(), data);
+    }
+
+    #[test]
+    fn fails_if_the_connection_ends_early() {
+        let result = read_body(Some(10), 100, reader(b"short", 3));
+        match result {
+            Err(ClientError::BodyDecode(e)) => {
+                assert_eq!(e.to_string(), "response truncated: 5 of 10 bytes received")
+            }
+            _ => panic!("expected a truncated body"),
+        }
+    }
+
+    #[test]
+    fn reports_the_read_errors() {
+        let result = read_body(Some(10), 100, |_| {
+            Err(ClientError::Timeout("read timed out".into()))
+        });
+        assert!(matches!(result, Err(ClientError::Timeout(_))));
+    }
+
+    #[test]
+    fn check_scheme_follows_the_settings() {
+        let mut settings = HttpSettings {
+            allow_plain_http: false,
+            ..Default::default()
+        };
+        let message = |settings: &HttpSettings, url: &str| match settings.check_scheme(url) {
+            Err(ClientError::UrlNotAllowed(message)) => message,
+            _ => panic!("{} should not be allowed", url),
+        };
+        assert!(settings.check_scheme("https://server/api").is_ok());
+        assert_eq!(
+            message(&settings, "http://server/api"),
+            "plain HTTP is disabled (ALLOW_PLAIN_HTTP in src/config/config.rs), an https:// URL is needed: http://server/api"
+        );
+        assert_eq!(
+            message(&settings, "ftp://server/api"),
+            "unsupported scheme, expected https://: ftp://server/api"
+        );
+        settings.allow_plain_http = true;
+        assert!(settings.check_scheme("http://server/api").is_ok());
+        assert_eq!(
+            message(&settings, "ftp://server/api"),
+            "unsupported scheme, expected https:// or http://: ftp://server/api"
+        );
+        // the URL will not become valid by retrying
+        assert!(!ClientError::UrlNotAllowed(String::new()).is_transient());
+    }
+}
@@ -0,0 +1,225 @@
+use embedded_hal_0_2::blocking::i2c::{Read, Write, WriteRead};
+use log::info;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// first and last 7-bit addresses that are not reserved by the I2C specification
+const FIRST_ADDRESS: u8 = 0x08;
+const LAST_ADDRESS: u8 = 0x77;
+
+// owns an I2C bus and hands out a proxy for each device connected to it
+pub struct I2cBusManager<BUS> {
+    bus: Arc<Mutex<BUS>>,
+}
+
+impl<BUS> I2cBusManager<BUS> {
+    pub fn new(bus: BUS) -> Self {
+        I2cBusManager {
+            bus: Arc::new(Mutex::new(bus)),
+        }
+    }
+
+    // handle that a single device driver owns, every transaction locks the bus
+    pub fn acquire(&self) -> I2cProxy<BUS> {
+        I2cProxy {
+            bus: self.bus.clone(),
+        }
+    }
+}
+
+impl<BUS: Read> I2cBusManager<BUS> {
+    // returns the addresses of the devices that answered to a one byte read
+    pub fn scan(&self) -> Vec<u8> {
+        let mut bus = lock(&self.bus);
+        let mut buffer = [0u8; 1];
+        let devices: Vec<u8> = (FIRST_ADDRESS..=LAST_ADDRESS)
+            .filter(|address| bus.read(*address, &mut buffer).is_ok())
+            .collect();
+        info!(
+            "[i2c scan]: {} device(s) found: {}",
+            devices.len(),
+            devices
+                .iter()
+                .map(|address| format!("0x{:02x}", address))
+                .collect::<Vec<String>>()
+                .join(", ")
+        );
+        devices
+    }
+}
+
+pub struct I2cProxy<BUS> {
+    bus: Arc<Mutex<BUS>>,
+}
+
+impl<BUS> Clone for I2cProxy<BUS> {
+    fn clone(&self) -> Self {
+        I2cProxy {
+            bus: self.bus.clone(),
+        }
+    }
+}
+
+impl<BUS: Read> Read for I2cProxy<BUS> {
+    type Error = BUS::Error;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        lock(&self.bus).read(address, buffer)
+    }
+}
+
+impl<BUS: Write> Write for I2cProxy<BUS> {
+    type Error = BUS::Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        lock(&self.bus).write(address, bytes)
+    }
+}
+
+impl<BUS: WriteRead> WriteRead for I2cProxy<BUS> {
+    type Error = BUS::Error;
+
+    fn write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Self::Error> {
+        lock(&self.bus).write_read(address, bytes, buffer)
+    }
+}
+
+// a panic while holding the bus does not leave the bus in an unusable state
+fn lock<BUS>(bus: &Mutex<BUS>) -> MutexGuard<'_, BUS> {
+    bus.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread, time::Duration};
+
+    // devices answer at their addresses, every transaction is recorded
+    #[derive(Default)]
+    struct FakeBus {
+        devices: Vec<u8>,
+        transactions: Vec<(&'static str, u8)>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Nack;
+
+    impl FakeBus {
+        fn transaction(&mut self, kind: &'static str, address: u8) -> Result<(), Nack> {
+            self.transactions.push((kind, address));
+            if self.devices.contains(&address) {
+                Ok(())
+            } else {
+                Err(Nack)
+            }
+        }
+    }
+
+    impl Read for FakeBus {
+        type Error = Nack;
+
+        fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Nack> {
+            buffer.fill(address);
+            self.transaction("read", address)
+        }
+    }
+
+    impl Write for FakeBus {
+        type Error = Nack;
+
+        fn write(&mut self, address: u8, _: &[u8]) -> Result<(), Nack> {
+            self.transaction("write", address)
+        }
+    }
+
+    impl WriteRead for FakeBus {
+        type Error = Nack;
+
+        fn write_read(&mut self, address: u8, _: &[u8], buffer: &mut [u8]) -> Result<(), Nack> {
+            buffer.fill(address);
+            self.transaction("write_read", address)
+        }
+    }
+
+    fn manager(devices: &[u8]) -> I2cBusManager<FakeBus> {
+        I2cBusManager::new(FakeBus {
+            devices: devices.to_vec(),
+            ..FakeBus::default()
+        })
+    }
+
+    #[test]
+    fn scan_reports_the_devices_that_acknowledge() {
+        // the reserved addresses are not probed
+        let manager = manager(&[0x03, 0x23, 0x76, 0x77, 0x78]);
+        assert_eq!(manager.scan(), [0x23, 0x76, 0x77]);
+        let probed: Vec<(&str, u8)> = (0x08..=0x77).map(|address| ("read", address)).collect();
+        assert_eq!(lock(&manager.bus).transactions, probed);
+    }
+
+    #[test]
+    fn scan_of_an_empty_bus() {
+        assert!(manager(&[]).scan().is_empty());
+    }
+
+    #[test]
+    fn proxies_share_the_bus() {
+        let manager = manager(&[0x23, 0x76]);
+        let mut light = manager.acquire();
+        let mut pressure = manager.acquire();
+        let mut buffer = [0u8; 2];
+        pressure.write_read(0x76, &[0xD0], &mut buffer).unwrap();
+        assert_eq!(buffer, [0x76, 0x76]);
+        light.write(0x23, &[0x10]).unwrap();
+        light.read(0x23, &mut buffer).unwrap();
+        assert_eq!(buffer, [0x23, 0x23]);
+        assert_eq!(pressure.write(0x77, &[0x00]), Err(Nack));
+        // the bus is free between the transactions
+        assert_eq!(manager.scan(), [0x23, 0x76]);
+        assert_eq!(
+            lock(&manager.bus).transactions[..4],
+            [
+                ("write_read", 0x76),
+                ("write", 0x23),
+                ("read", 0x23),
+                ("write", 0x77)
+            ]
+        );
+    }
+
+    #[test]
+    fn proxies_on_different_threads_do_not_deadlock() {
+        let manager = manager(&[0x23, 0x76]);
+        let (done, finished) = mpsc::channel();
+        for address in [0x23, 0x76] {
+            let mut proxy = manager.acquire();
+            let done = done.clone();
+            thread::spawn(move || {
+                let mut buffer = [0u8; 1];
+                for _ in 0..1000 {
+                    proxy.write_read(address, &[0x00], &mut buffer).unwrap();
+                    assert_eq!(buffer, [address]);
+                }
+                done.send(()).unwrap();
+            });
+        }
+        for _ in 0..2 {
+            finished
+                .recv_timeout(Duration::from_secs(10))
+                .expect("the proxies are deadlocked");
+        }
+        let bus = lock(&manager.bus);
+        for address in [0x23, 0x76] {
+            let count = bus
+                .transactions
+                .iter()
+                .filter(|transaction| transaction.1 == address)
+                .count();
+            assert_eq!(count, 1000);
+        }
+    }
+}
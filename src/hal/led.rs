@@ -0,0 +1,7 @@
+use crate::error::PeripheralError;
+
+// device status LED
+pub trait StatusLed {
+    fn set_high(&mut self) -> Result<(), PeripheralError>;
+    fn set_low(&mut self) -> Result<(), PeripheralError>;
+}
@@ -0,0 +1,19 @@
+#[cfg_attr(not(feature = "hal"), allow(dead_code))]
+pub mod bmp280;
+pub mod http;
+#[cfg_attr(not(feature = "hal"), allow(dead_code))]
+pub mod i2c_bus;
+pub mod led;
+pub mod mqtt;
+pub mod network;
+pub mod ota;
+pub mod sensor;
+pub mod storage;
+pub mod system;
+#[cfg_attr(not(feature = "hal"), allow(dead_code))]
+pub mod tls;
+
+#[cfg(feature = "hal")]
+pub mod esp;
+#[cfg(not(feature = "hal"))]
+pub mod host;
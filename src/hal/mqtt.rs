@@ -0,0 +1,94 @@
+use crate::{
+    config::config::{HTTP_TIMEOUT_MILLIS, MQTT_KEEP_ALIVE_SECONDS},
+    error::ClientError,
+    hal::http::PLAIN_HTTP_ALLOWED,
+};
+use std::time::Duration;
+
+// the names of the MQTT specification
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QoS {
+    AtMostOnce,
+    AtLeastOnce,
+    ExactlyOnce,
+}
+
+impl QoS {
+    pub fn from_level(level: u8) -> Option<QoS> {
+        match level {
+            0 => Some(QoS::AtMostOnce),
+            1 => Some(QoS::AtLeastOnce),
+            2 => Some(QoS::ExactlyOnce),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MqttMessage {
+    pub topic: String,
+    pub payload: Vec<u8>,
+    pub qos: QoS,
+    pub retain: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct MqttOptions {
+    // mqtt://host:port or mqtts://host:port
+    pub broker_url: String,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub keep_alive: Duration,
+    // timeout of the connection and of the acknowledgements
+    pub timeout: Duration,
+    // published by the broker when the connection is lost
+    pub last_will: Option<MqttMessage>,
+    // published after each (re)connection, e.g. to replace the last will
+    pub birth: Option<MqttMessage>,
+}
+
+impl MqttOptions {
+    pub fn new(broker_url: &str, client_id: &str) -> Self {
+        MqttOptions {
+            broker_url: broker_url.to_owned(),
+            client_id: client_id.to_owned(),
+            username: None,
+            password: None,
+            keep_alive: Duration::from_secs(MQTT_KEEP_ALIVE_SECONDS),
+            timeout: Duration::from_millis(HTTP_TIMEOUT_MILLIS),
+            last_will: None,
+            birth: None,
+        }
+    }
+
+    // fails if the messages would be sent in cleartext while it is not allowed
+    pub fn check_scheme(&self) -> Result<(), ClientError> {
+        let url = &self.broker_url;
+        if url.starts_with("mqtts://") || (PLAIN_HTTP_ALLOWED && url.starts_with("mqtt://")) {
+            return Ok(());
+        }
+        let message = if url.starts_with("mqtt://") {
+            format!(
+                "plain MQTT is disabled (ALLOW_PLAIN_HTTP in src/config/config.rs), an mqtts:// URL is needed: {}",
+                url
+            )
+        } else {
+            format!("unsupported broker URL scheme, expected mqtts://: {}", url)
+        };
+        Err(ClientError::UrlNotAllowed(message))
+    }
+}
+
+// connection to an MQTT broker; the implementations reconnect by themselves when the
+// connection is lost, and publish the birth message again once reconnected
+pub trait MqttConnection {
+    // fails if the broker is not reachable; with QoS 1 and 2 the implementations may
+    // wait for the acknowledgement of the broker
+    fn publish(&mut self, message: &MqttMessage) -> Result<(), ClientError>;
+}
+
+pub trait MqttConnector {
+    fn connect(&self, options: &MqttOptions) -> Result<Box<dyn MqttConnection>, ClientError>;
+}
@@ -0,0 +1,11 @@
+use crate::error::PeripheralError;
+
+// network link used to reach the server (Wi-Fi station on the board)
+pub trait NetworkLink {
+    fn connect(&mut self) -> Result<(), PeripheralError>;
+    fn is_connected(&self) -> Result<bool, PeripheralError>;
+    fn get_mac(&self) -> Result<[u8; 6], PeripheralError>;
+    // blocks until the system time is synchronized, fails if it takes longer than
+    // CLOCK_SYNC_TIMEOUT_SECONDS
+    fn synchronize_clock(&mut self) -> Result<(), PeripheralError>;
+}
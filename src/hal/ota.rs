@@ -0,0 +1,12 @@
+use crate::error::FirmwareUpdateError;
+
+// installs a new firmware over the air: the image is written to the partition that is
+// not running and booted from the next restart
+pub trait FirmwareUpdater {
+    // downloads the image with a GET and selects it for the next boot; nothing changes
+    // if the download or the image fail
+    fn update(&mut self, url: &str, headers: &[(&str, &str)]) -> Result<(), FirmwareUpdateError>;
+    // confirms that the running firmware works, so that the bootloader does not roll
+    // back to the previous one at the next restart
+    fn mark_running_valid(&mut self) -> Result<(), FirmwareUpdateError>;
+}
@@ -0,0 +1,16 @@
+use crate::error::PeripheralError;
+
+// ambient light sensor (BH1750 on the board)
+pub trait LightSensor {
+    fn read_lux(&mut self) -> Result<f32, PeripheralError>;
+}
+
+// temperature and humidity sensor (DHT11 on the board), returns (temperature, humidity)
+pub trait TemperatureHumiditySensor {
+    fn read_temperature_and_humidity(&mut self) -> Result<(f32, f32), PeripheralError>;
+}
+
+// barometric pressure sensor (BMP280/BME280 on the board), returns hPa
+pub trait PressureSensor {
+    fn read_pressure(&mut self) -> Result<f64, PeripheralError>;
+}
@@ -0,0 +1,27 @@
+// persistent key-value storage (an NVS namespace on the board)
+pub trait KeyValueStorage {
+    fn read(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+    fn write(&mut self, key: &str, value: &[u8]) -> anyhow::Result<()>;
+    fn remove(&mut self, key: &str) -> anyhow::Result<()>;
+}
+
+// opens the key-value storage of a namespace
+pub trait StorageProvider {
+    fn open(&mut self, namespace: &str) -> anyhow::Result<Box<dyn KeyValueStorage>>;
+}
+
+// the behaviour expected from every implementation, checked by their tests
+#[cfg(test)]
+pub(crate) mod contract {
+    use super::KeyValueStorage;
+
+    // the values shorter than a word included, NVS stores them differently
+    pub(crate) fn round_trips_values_of_any_length(storage: &mut dyn KeyValueStorage) {
+        for length in [0, 1, 7, 8, 100] {
+            let key = format!("length{}", length);
+            let value: Vec<u8> = (0..length).map(|i| i as u8 ^ 0xA5).collect();
+            storage.write(&key, &value).unwrap();
+            assert_eq!(storage.read(&key).unwrap(), Some(value), "{} bytes", length);
+        }
+    }
+}
@@ -0,0 +1,7 @@
+// control of the device itself
+pub trait SystemControl {
+    // restarts the device, on the board it does not return
+    fn restart(&mut self);
+    // random number that does not depend on the clock (the hardware RNG on the board)
+    fn random(&mut self) -> u64;
+}
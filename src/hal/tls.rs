@@ -0,0 +1,50 @@
+use crate::config::config::{SERVER_CA_CERTIFICATE_PEM, SERVER_CERTIFICATE_SHA256};
+use sha2::{Digest, Sha256};
+
+// how the certificate of the server is verified on https connections:
+// - with a pinned fingerprint only the certificate with that SHA-256 is accepted
+//   (the certification authority is not checked, self-signed certificates work);
+// - otherwise with the pinned CA certificate, if any;
+// - otherwise with the certificate bundle of ESP-IDF (the common public CAs).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TlsSettings {
+    pub ca_certificate_pem: Option<&'static str>,
+    pub server_certificate_sha256: Option<[u8; 32]>,
+}
+
+impl TlsSettings {
+    pub fn from_config() -> Self {
+        let server_certificate_sha256 = SERVER_CERTIFICATE_SHA256.map(|fingerprint| {
+            // an ignored fingerprint would silently disable the pinning
+            parse_sha256_fingerprint(fingerprint)
+                .expect("SERVER_CERTIFICATE_SHA256 is not a valid SHA-256 fingerprint")
+        });
+        TlsSettings {
+            ca_certificate_pem: SERVER_CA_CERTIFICATE_PEM,
+            server_certificate_sha256,
+        }
+    }
+}
+
+// parses a fingerprint as printed by openssl ("AB:CD:...") or as plain hex
+pub fn parse_sha256_fingerprint(fingerprint: &str) -> Option<[u8; 32]> {
+    let hex: Vec<u8> = fingerprint
+        .bytes()
+        .filter(|byte| !matches!(byte, b':' | b' '))
+        .collect();
+    if hex.len() != 64 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut result = [0u8; 32];
+    for (index, pair) in hex.chunks(2).enumerate() {
+        let pair = std::str::from_utf8(pair).ok()?;
+        result[index] = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(result)
+}
+
+// true if the certificate (DER) has the given SHA-256 fingerprint
+pub fn certificate_matches(certificate_der: &[u8], fingerprint: &[u8; 32]) -> bool {
+    let digest = Sha256::digest(certificate_der);
+    digest.as_slice() == fingerprint
+}
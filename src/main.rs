@@ -1,15 +1,73 @@
 use anyhow::Ok;
 mod config;
+#[cfg(feature = "hal")]
 use esp_idf_sys::{self as _};
-use service::orchestrator_service::orchestrate;
+use service::{orchestrator_service::orchestrate, settings_service::SettingsService};
+use std::rc::Rc;
 mod dto;
+mod error;
+mod hal;
 mod service;
 mod util;
+
+#[cfg(feature = "hal")]
 fn main() -> anyhow::Result<()> {
     esp_idf_svc::sys::link_patches();
     esp_idf_svc::log::EspLogger::initialize_default();
 
-    orchestrate();
+    let nvs = esp_idf_svc::nvs::EspDefaultNvsPartition::take().unwrap();
+    // the Wi-Fi is configured by the settings, read before everything else
+    let mut storage_provider = hal::esp::storage::EspNvsStorageProvider::new(nvs.clone());
+    let settings = SettingsService::open(&mut storage_provider).load();
+    let peripheral_service = hal::esp::board::build_peripheral_service(
+        nvs,
+        &settings.wifi_ssid,
+        &settings.wifi_password,
+    );
+    orchestrate(
+        peripheral_service,
+        Box::new(hal::esp::http::EspHttpTransport::new(
+            hal::http::HttpSettings::default(),
+        )),
+        Rc::new(hal::esp::mqtt::EspMqttConnector::new(
+            hal::tls::TlsSettings::from_config(),
+        )),
+        Box::new(hal::esp::ota::EspFirmwareUpdater::new(
+            hal::http::HttpSettings::default(),
+        )),
+        Box::new(storage_provider),
+        settings,
+    );
+
+    Ok(())
+}
+
+#[cfg(not(feature = "hal"))]
+fn main() -> anyhow::Result<()> {
+    util::host_logger::initialize_default();
+
+    let mut storage_provider = hal::host::storage::InMemoryStorageProvider::new();
+    let mut settings = SettingsService::open(&mut storage_provider).load();
+    // --server http://localhost:8080 talks to the mock server instead of the configured one
+    let arguments: Vec<String> = std::env::args().collect();
+    if let Some(index) = arguments.iter().position(|argument| argument == "--server") {
+        let server = arguments.get(index + 1).map_or("", String::as_str);
+        settings = settings
+            .with_server(server)
+            .map_err(|e| anyhow::anyhow!("invalid --server: {}", e))?;
+    }
+    let peripheral_service =
+        hal::host::board::build_peripheral_service(hal::host::board::DEFAULT_MAC_ADDRESS);
+    orchestrate(
+        peripheral_service,
+        Box::new(hal::host::http::HostHttpTransport::new(
+            hal::http::HttpSettings::default(),
+        )),
+        Rc::new(hal::host::mqtt::HostMqttConnector),
+        Box::new(hal::host::ota::HostFirmwareUpdater),
+        Box::new(storage_provider),
+        settings,
+    );
 
-    return Ok(());
+    Ok(())
 }
@@ -1,4 +1,6 @@
 pub mod config;
 pub mod dto;
+pub mod error;
+pub mod hal;
 pub mod service;
 pub mod util;
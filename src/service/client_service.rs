@@ -1,106 +1,299 @@
 use crate::{
     config::config::{
-        DEFAULT_ALERT_URL, DEFAULT_I_AM_ALIVE_URL, DEVICE_DESCRIPTION, DEVICE_NAME,
-        REGISTER_DEVICE_URL, TEMPERATURE_SENSOR_UNIT_OF_MEASURE,
-        WEATHER_SENSOR_SUPPLY_INTERVAL_SECONDS,
+        CONFIGURATION_REFRESH_INTERVAL_SECONDS, DEFAULT_I_AM_ALIVE_INTERVAL_SECONDS,
+        TEMPERATURE_SENSOR_UNIT_OF_MEASURE, WEATHER_SENSOR_SUPPLY_INTERVAL_SECONDS,
     },
     dto::{
-        config_request::ConfigRequest, config_response::Configuration,
-        register_device::RegisterDeviceDTO, request_i_am_alive::RequestIAmAlive,
+        config_request::ConfigRequest,
+        config_response::{Configuration, DownloadedConfiguration},
+        measurement::Measurement,
+        register_device::RegisterDeviceDTO,
+        register_device_response::RegisterDeviceResponse,
+        remote_command::{CommandAcknowledgement, IAmAliveResponse, RemoteCommand},
+        request_configuration_errors::{FieldError, RequestConfigurationErrors},
+        request_i_am_alive::RequestIAmAlive,
         request_submit::RequestSubmit,
+        retry_configuration::RetryConfiguration,
+    },
+    error::{ClientError, FirmwareUpdateError},
+    hal::{
+        http::{HttpResponse, HttpTransport},
+        ota::FirmwareUpdater,
+    },
+    service::{
+        configuration_merge_service::{merge, CONFIGURATION_SCHEMA_VERSION},
+        credential_service::CredentialService,
+        retry_service::{Jitter, RetryPolicies, RetryPolicy},
+        settings_service::Settings,
+        signature_service::content_version,
+        transport_service::{HttpMessageTransport, MessageKind, MessageTransport},
     },
 };
-use anyhow::{Error, Ok};
-use embedded_svc::{http::client::Client as HttpClient, io::Write, utils::io};
-use esp_idf_svc::http::client::EspHttpConnection;
-use esp_idf_sys as _;
-use log::{error, info};
+use log::{error, info, warn};
+use serde_json::{Map, Value};
 use std::result::Result::Ok as StandardOk;
 
 pub const DEVICE_TYPE: &str = "WeatherStation";
 
+// connection with the server and credentials of the device, used for the registration
+// and the configuration and, with the HTTP transport, for the messages
+pub struct HttpSession {
+    pub transport: Box<dyn HttpTransport>,
+    pub credentials: CredentialService,
+}
+
+impl HttpSession {
+    pub fn new(transport: Box<dyn HttpTransport>, credentials: CredentialService) -> HttpSession {
+        HttpSession {
+            transport,
+            credentials,
+        }
+    }
+}
+
+// sends the messages of the device with the transport chosen by the configuration,
+// retrying them according to the policies
 pub struct ClientService {
-    alert_url: String,
-    i_am_alive_url: String,
+    session: HttpSession,
+    transport: Box<dyn MessageTransport>,
+    retry_policies: RetryPolicies,
+    jitter: Jitter,
+    settings: Settings,
 }
 
 impl ClientService {
-    pub fn new(alert_url: &str, i_am_alive_url: &str) -> ClientService {
+    // until the configuration chooses the transport the messages are posted to the
+    // endpoints of the settings; the seed randomizes the delays of the retries
+    pub fn new(
+        session: HttpSession,
+        retry_policies: RetryPolicies,
+        jitter_seed: u64,
+        settings: Settings,
+    ) -> ClientService {
         ClientService {
-            alert_url: alert_url.to_owned(),
-            i_am_alive_url: i_am_alive_url.to_owned(),
+            session,
+            transport: Box::new(HttpMessageTransport::new(
+                &settings.register_device_url,
+                &settings.alert_url,
+                &settings.i_am_alive_url,
+            )),
+            retry_policies,
+            jitter: Jitter::new(jitter_seed),
+            settings,
         }
     }
 
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn set_transport(&mut self, transport: Box<dyn MessageTransport>) {
+        self.transport = transport;
+    }
+
+    pub fn set_retry_policies(&mut self, retry_policies: RetryPolicies) {
+        self.retry_policies = retry_policies;
+    }
+
+    // installs the firmware at the URL, downloaded with the credentials of the device;
+    // not retried, the server sends the command again if it is not acknowledged
+    pub fn download_firmware(
+        &mut self,
+        updater: &mut dyn FirmwareUpdater,
+        url: &str,
+    ) -> Result<(), FirmwareUpdateError> {
+        let credential_headers = self.session.credentials.headers(b"");
+        let headers: Vec<(&str, &str)> = credential_headers
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        updater.update(url, &headers)
+    }
+
+    // downloads the configuration, registering the device again if the server does
+    // not know it or its token
+    pub fn get_configuration(
+        &mut self,
+        mac_address: &str,
+    ) -> Result<DownloadedConfiguration, ClientError> {
+        let session = &mut self.session;
+        let defaults = default_configuration(&self.settings);
+        let configuration = get_configuration(
+            session.transport.as_mut(),
+            &self.retry_policies.configuration,
+            &mut self.jitter,
+            &self.settings.configuration_url,
+            mac_address,
+            &defaults,
+            &mut session.credentials,
+        );
+        if !configuration
+            .as_ref()
+            .is_err_and(|e| e.needs_registration())
+        {
+            return configuration;
+        }
+        info!("the server does not know the device or its token, registering it again...");
+        register_device(
+            session.transport.as_mut(),
+            &self.retry_policies.registration,
+            &mut self.jitter,
+            &self.settings.register_device_url,
+            &registration_payload(mac_address, &self.settings),
+            &mut session.credentials,
+        )?;
+        get_configuration(
+            session.transport.as_mut(),
+            &self.retry_policies.configuration,
+            &mut self.jitter,
+            &self.settings.configuration_url,
+            mac_address,
+            &defaults,
+            &mut session.credentials,
+        )
+    }
+
     pub fn send_alert(
-        &self,
+        &mut self,
         mac_address: &str,
-        temperature: Option<f32>,
-        humidity: Option<f32>,
-        pressure: Option<f64>,
-        lux: Option<f32>,
-        light: Option<bool>,
-    ) -> anyhow::Result<(), anyhow::Error> {
-        let client = HttpClient::wrap(EspHttpConnection::new(&Default::default())?);
-
-        let payload = serde_json::to_string(&RequestSubmit::new(
+        measurement: &Measurement,
+    ) -> Result<(), ClientError> {
+        let payload =
+            serde_json::to_string(&RequestSubmit::new(mac_address.to_owned(), measurement))
+                .unwrap();
+        let payload = payload.as_bytes();
+
+        info!("trying to send data...");
+        let transport = self.transport.as_mut();
+        let session = &mut self.session;
+        let result =
+            self.retry_policies
+                .submit
+                .execute("data submission", &mut self.jitter, || {
+                    transport.send(session, MessageKind::Measurement, payload)
+                });
+        info!("data sent? {}", result.is_ok());
+        result.map(|_| ())
+    }
+
+    // registers the device with the transport in use, e.g. again when the server no
+    // longer knows it
+    pub fn register_device(&mut self, mac_address: &str) -> Result<(), ClientError> {
+        let payload = registration_payload(mac_address, &self.settings);
+        let payload = payload.as_bytes();
+
+        info!("trying to send data...");
+        let transport = self.transport.as_mut();
+        let session = &mut self.session;
+        let result = self.retry_policies.registration.execute(
+            "device registration",
+            &mut self.jitter,
+            || transport.send(session, MessageKind::Registration, payload),
+        );
+        info!("data sent? {}", result.is_ok());
+        result.map(|_| ())
+    }
+
+    // tells the server which fields of the configuration the device did not accept
+    pub fn report_configuration_errors(
+        &mut self,
+        mac_address: &str,
+        configuration_version: &str,
+        errors: &[FieldError],
+    ) -> Result<(), ClientError> {
+        let payload = serde_json::to_string(&RequestConfigurationErrors::new(
             mac_address.to_owned(),
-            temperature,
-            humidity,
-            pressure,
-            lux,
-            light,
+            configuration_version.to_owned(),
+            errors.to_vec(),
         ))
         .unwrap();
         let payload = payload.as_bytes();
 
-        info!("trying to send data...");
-        let result = post_request(payload, client, &self.alert_url);
-        info!("data sent? {}", !result.is_err());
-        return match result {
-            Err(e) => Err(e.into()),
-            StandardOk(_) => Ok(()),
-        };
+        info!("trying to report the configuration errors...");
+        let session = &mut self.session;
+        let result = self.retry_policies.configuration.execute(
+            "configuration errors report",
+            &mut self.jitter,
+            || {
+                post_request(
+                    session.transport.as_mut(),
+                    payload,
+                    &self.settings.configuration_errors_url,
+                    &mut session.credentials,
+                )
+            },
+        );
+        info!("configuration errors reported? {}", result.is_ok());
+        result.map(|_| ())
     }
 
-    pub fn send_i_am_alive(&self, mac_address: &str) -> anyhow::Result<(), anyhow::Error> {
-        let client = HttpClient::wrap(EspHttpConnection::new(&Default::default())?);
-        let payload = serde_json::to_string(&RequestIAmAlive::new(mac_address.to_owned())).unwrap();
+    // sends the heartbeat with the outcome of the executed commands, returns the
+    // commands of the server (the MQTT transport has none)
+    pub fn send_i_am_alive(
+        &mut self,
+        mac_address: &str,
+        acknowledgements: &[CommandAcknowledgement],
+    ) -> Result<Vec<RemoteCommand>, ClientError> {
+        let payload = serde_json::to_string(&RequestIAmAlive::new(
+            mac_address.to_owned(),
+            acknowledgements.to_vec(),
+        ))
+        .unwrap();
         let payload = payload.as_bytes();
 
         info!("trying to send is alive ack...");
-        let result = post_request(payload, client, &self.i_am_alive_url);
-        info!("ack sent? {}", !result.is_err());
-        return match result {
-            Err(e) => Err(e.into()),
-            StandardOk(_) => Ok(()),
-        };
+        let transport = self.transport.as_mut();
+        let session = &mut self.session;
+        let result =
+            self.retry_policies
+                .heartbeat
+                .execute("is alive ack", &mut self.jitter, || {
+                    transport.send(session, MessageKind::Heartbeat, payload)
+                });
+        info!("ack sent? {}", result.is_ok());
+        let body = result?;
+
+        if body.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        // the heartbeat has been received anyway, the commands are sent again
+        let response = serde_json::from_str::<IAmAliveResponse>(&body).unwrap_or_else(|e| {
+            error!("unable to parse the is alive response: {}", e);
+            IAmAliveResponse::default()
+        });
+        Ok(response.commands)
     }
 }
 
 pub fn get_configuration(
+    transport: &mut dyn HttpTransport,
+    retry_policy: &RetryPolicy,
+    jitter: &mut Jitter,
     configuration_uri: &str,
     mac_address: &str,
-) -> anyhow::Result<Configuration, anyhow::Error> {
-    let client = HttpClient::wrap(EspHttpConnection::new(&Default::default())?);
+    defaults: &Configuration,
+    credentials: &mut CredentialService,
+) -> Result<DownloadedConfiguration, ClientError> {
     let payload = serde_json::to_string(&ConfigRequest::new(mac_address.to_owned())).unwrap();
     let payload = payload.as_bytes();
 
     info!("[config downloader]: trying to get remote configuration...");
-    let result = post_request(payload, client, configuration_uri);
+    let result = retry_policy.execute("configuration download", jitter, || {
+        send_request(transport, payload, configuration_uri, credentials)
+    });
     info!(
         "[config downloader]: configuration retrieved with success? {}",
-        !result.is_err()
+        result.is_ok()
     );
 
     match result {
-        StandardOk(body_string) => {
-            let configuration: Result<Configuration, serde_json::Error> =
-                serde_json::from_str(&body_string);
-            info!("{:?}", configuration);
+        StandardOk(response) => {
+            // any JSON object: the missing fields are taken from the defaults
+            let document: Result<Map<String, Value>, serde_json::Error> =
+                serde_json::from_slice(&response.body);
 
-            if configuration.is_err() {
-                let err = configuration.err().unwrap();
+            if document.is_err() {
+                let err = document.err().unwrap();
                 error!(
             "[config downloader]: error while trying to parse the configuration response: {}",
             &err
@@ -108,113 +301,153 @@ pub fn get_configuration(
                 return Err(err.into());
             }
 
-            let configuration = configuration.unwrap();
+            let merged = merge(&document.unwrap(), defaults);
+            if merged.schema_version > CONFIGURATION_SCHEMA_VERSION {
+                warn!(
+                    "[config downloader]: configuration schema {} is newer than the supported one ({}), the new fields are ignored",
+                    merged.schema_version, CONFIGURATION_SCHEMA_VERSION
+                );
+            }
+            if !merged.unknown_fields.is_empty() {
+                info!(
+                    "[config downloader]: unknown fields ignored: {}",
+                    merged.unknown_fields.join(", ")
+                );
+            }
+            let version = response
+                .etag
+                .unwrap_or_else(|| content_version(&response.body));
             info!(
-                "[config downloader]: Remote configuration loaded successfully: {:?}",
-                configuration
+                "[config downloader]: Remote configuration loaded successfully (version {}): {:?}",
+                version, merged.configuration
             );
-            return Ok(configuration);
+            Ok(DownloadedConfiguration {
+                configuration: merged.configuration,
+                version,
+                errors: merged.errors,
+            })
         }
         Err(e) => {
-            error!("[config downloader]: Error decoding response body: {}", e);
-            return Err(e.into());
+            error!("[config downloader]: {}", e);
+            Err(e)
         }
     }
 }
 
-fn post_request(
+pub fn post_request(
+    transport: &mut dyn HttpTransport,
     payload: &[u8],
-    mut client: HttpClient<EspHttpConnection>,
     url: &str,
-) -> Result<String, Error> {
+    credentials: &mut CredentialService,
+) -> Result<String, ClientError> {
+    let response = send_request(transport, payload, url, credentials)?;
+    match std::str::from_utf8(&response.body) {
+        Err(e) => Err(ClientError::BodyDecode(e.into())),
+        StandardOk(str) => Ok(str.to_owned()),
+    }
+}
+
+// posts the payload, authenticated and signed; fails if the status is not a success
+fn send_request(
+    transport: &mut dyn HttpTransport,
+    payload: &[u8],
+    url: &str,
+    credentials: &mut CredentialService,
+) -> Result<HttpResponse, ClientError> {
     let content_length_header = format!("{}", payload.len());
-    let headers = [
+    let mut headers = vec![
         ("content-type", "application/json"),
         ("content-length", &*content_length_header),
     ];
-
-    let request = client.post(url, &headers);
-
-    if request.is_err() {
-        let message = format!("connection error: {:?}", request.err());
-        error!("{}", message);
-        return Err(Error::msg(message));
-    }
-    let mut request = request.unwrap();
-
-    if request.write_all(payload).is_err() {
-        let message = format!("connection error while trying to write all");
-        error!("{}", message);
-        return Err(Error::msg(message));
+    // signed again at each attempt: a retry has a new nonce
+    let credential_headers = credentials.headers(payload);
+    for (name, value) in &credential_headers {
+        headers.push((name, value));
     }
-    if request.flush().is_err() {
-        let message = format!("connection error while trying to flush");
-        error!("{}", message);
-        return Err(Error::msg(message));
-    }
-    info!("-> POST {}", url);
-    let response = request.submit();
-    if response.is_err() {
-        let message = format!("connection error while trying to read response");
-        error!("{}", message);
-        return Err(Error::msg(message));
-    }
-    let mut response = response.unwrap();
 
-    let status = response.status();
-    info!("<- {}", status);
-    if !(status >= 200 && status <= 204) {
-        return Err(Error::msg(format!("Invalid response status: {}", status)));
-    }
-    let mut buf = [0u8; 4086];
-    let bytes_read = io::try_read_full(&mut response, &mut buf).map_err(|e| e.0);
+    let response = transport.post(url, &headers, payload)?;
 
-    if bytes_read.is_err() {
-        let message = format!(
-            "connection error while trying to read response: {:?}",
-            bytes_read.err()
-        );
-        error!("{}", message);
-        return Err(Error::msg(message));
-    } else {
-        let bytes_read = bytes_read.unwrap();
-        return match std::str::from_utf8(&buf[0..bytes_read]) {
-            Err(e) => Err(Error::msg(format!("{:?}", e))),
-            StandardOk(str) => Ok(str.to_owned()),
-        };
+    let status = response.status;
+    if !(200..=204).contains(&status) {
+        return Err(ClientError::HttpStatus(status));
     }
+    Ok(response)
 }
 
-pub fn get_default_configuration(e: Error) -> Configuration {
-    error!(
-        "Error while trying to load configuration from remote server: {:?}",
-        e
-    );
+// the configuration compiled in the firmware, with the endpoints of the settings
+pub fn default_configuration(settings: &Settings) -> Configuration {
     Configuration {
-        alert_endpoint: DEFAULT_ALERT_URL.to_owned(),
-        i_am_alive_endpoint: DEFAULT_I_AM_ALIVE_URL.to_owned(),
+        alert_endpoint: settings.alert_url.clone(),
+        i_am_alive_endpoint: settings.i_am_alive_url.clone(),
         temperature_sensor_unit_of_measure: TEMPERATURE_SENSOR_UNIT_OF_MEASURE.to_owned(),
         weather_sensor_supply_interval_seconds: WEATHER_SENSOR_SUPPLY_INTERVAL_SECONDS,
+        i_am_alive_interval_seconds: DEFAULT_I_AM_ALIVE_INTERVAL_SECONDS,
+        crontab: None,
+        transport: None,
+        mqtt: None,
+        configuration_refresh_interval_seconds: CONFIGURATION_REFRESH_INTERVAL_SECONDS,
+        retry_policies: RetryConfiguration::default(),
     }
 }
 
-pub fn register_device(mac_address: &str) -> anyhow::Result<(), anyhow::Error> {
-    let client = HttpClient::wrap(EspHttpConnection::new(&Default::default())?);
-
-    let payload = serde_json::to_string(&RegisterDeviceDTO::new(
+pub fn registration_payload(mac_address: &str, settings: &Settings) -> String {
+    serde_json::to_string(&RegisterDeviceDTO::new(
         mac_address.to_owned(),
         DEVICE_TYPE.into(),
-        DEVICE_NAME.into(),
-        DEVICE_DESCRIPTION.into(),
+        settings.device_name.clone(),
+        settings.device_description.clone(),
     ))
-    .unwrap();
+    .unwrap()
+}
+
+// registers the device and stores the token issued by the server
+pub fn register_device(
+    transport: &mut dyn HttpTransport,
+    retry_policy: &RetryPolicy,
+    jitter: &mut Jitter,
+    register_device_url: &str,
+    payload: &str,
+    credentials: &mut CredentialService,
+) -> Result<(), ClientError> {
     let payload = payload.as_bytes();
 
     info!("trying to send data...");
-    let result = post_request(payload, client, REGISTER_DEVICE_URL);
-    info!("data sent? {}", !result.is_err());
-    return match result {
-        Err(e) => Err(e.into()),
-        StandardOk(_) => Ok(()),
+    let result = retry_policy.execute("device registration", jitter, || {
+        post_registration(transport, payload, register_device_url, credentials)
+    });
+    info!("data sent? {}", result.is_ok());
+    result
+}
+
+// a single registration attempt. A device that has a token sends it, so that only the
+// device itself can register again with its MAC address; if the server does not
+// accept it any more, it registers without it.
+pub fn post_registration(
+    transport: &mut dyn HttpTransport,
+    payload: &[u8],
+    url: &str,
+    credentials: &mut CredentialService,
+) -> Result<(), ClientError> {
+    let mut result = post_request(transport, payload, url, credentials);
+    if matches!(result, Err(ClientError::HttpStatus(401))) && credentials.token().is_some() {
+        warn!("the server rejected the token of the device, registering without it...");
+        credentials.set_token(None);
+        result = post_request(transport, payload, url, credentials);
+    }
+    let body = result?;
+
+    let response = if body.trim().is_empty() {
+        RegisterDeviceResponse::default()
+    } else {
+        serde_json::from_str::<RegisterDeviceResponse>(&body).unwrap_or_else(|e| {
+            error!("unable to parse the registration response: {}", e);
+            RegisterDeviceResponse::default()
+        })
     };
+    // a server that does not issue a new token keeps the current one valid
+    if let Some(token) = response.token {
+        info!("new token received from the server");
+        credentials.set_token(Some(token));
+    }
+    Ok(())
 }
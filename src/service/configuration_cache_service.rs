@@ -0,0 +1,284 @@
+use super::configuration_validation_service::validate;
+use crate::{
+    dto::config_response::{Configuration, VersionedConfiguration},
+    hal::storage::KeyValueStorage,
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+// NVS namespace of the last configuration received from the server
+pub const CONFIGURATION_NAMESPACE: &str = "configuration";
+
+const KEY_CONFIGURATION: &str = "config";
+
+// the configuration and its version are written together, in a single blob
+#[derive(Serialize, Deserialize)]
+struct CachedConfiguration {
+    version: String,
+    configuration: Configuration,
+}
+
+// the configuration used while the server does not provide one
+pub enum FallbackConfiguration {
+    Cached(VersionedConfiguration),
+    Defaults(Configuration),
+}
+
+impl FallbackConfiguration {
+    pub fn configuration(&self) -> &Configuration {
+        match self {
+            FallbackConfiguration::Cached(cached) => &cached.configuration,
+            FallbackConfiguration::Defaults(configuration) => configuration,
+        }
+    }
+}
+
+// keeps the last valid configuration received from the server, used when the server
+// is not reachable at boot instead of the configuration compiled in the firmware
+pub struct ConfigurationCacheService {
+    storage: Option<Box<dyn KeyValueStorage>>,
+    // version of the stored configuration, to write the NVS only when it changes
+    version: Option<String>,
+}
+
+impl ConfigurationCacheService {
+    pub fn new(storage: Option<Box<dyn KeyValueStorage>>) -> Self {
+        ConfigurationCacheService {
+            storage,
+            version: None,
+        }
+    }
+
+    // the stored configuration, None if there is none or it cannot be read (e.g. it
+    // was written by a firmware with a different format)
+    pub fn load(&mut self) -> Option<VersionedConfiguration> {
+        let value = match self.storage.as_mut()?.read(KEY_CONFIGURATION) {
+            Ok(Some(value)) => value,
+            Ok(None) => return None,
+            Err(e) => {
+                error!(
+                    "[configuration cache]: unable to read the configuration: {}",
+                    e
+                );
+                return None;
+            }
+        };
+        match serde_json::from_slice::<CachedConfiguration>(&value) {
+            Ok(cached) => {
+                self.version = Some(cached.version.clone());
+                Some(VersionedConfiguration {
+                    configuration: cached.configuration,
+                    version: cached.version,
+                })
+            }
+            Err(e) => {
+                warn!(
+                    "[configuration cache]: the stored configuration is not valid: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    // the cached configuration, the compiled one if there is none or the cached one is
+    // not valid for this firmware
+    pub fn fallback(&mut self, defaults: impl FnOnce() -> Configuration) -> FallbackConfiguration {
+        match self
+            .load()
+            .filter(|cached| validate(&cached.configuration).is_empty())
+        {
+            Some(cached) => FallbackConfiguration::Cached(cached),
+            None => FallbackConfiguration::Defaults(defaults()),
+        }
+    }
+
+    pub fn store(&mut self, configuration: &VersionedConfiguration) {
+        // the version stored by the previous boot
+        if self.version.is_none() {
+            self.load();
+        }
+        if self.version.as_deref() == Some(configuration.version.as_str()) {
+            return;
+        }
+        let storage = match self.storage.as_mut() {
+            None => return,
+            Some(storage) => storage,
+        };
+        let cached = CachedConfiguration {
+            version: configuration.version.clone(),
+            configuration: configuration.configuration.clone(),
+        };
+        let value = serde_json::to_vec(&cached).unwrap();
+        match storage.write(KEY_CONFIGURATION, &value) {
+            Err(e) => error!(
+                "[configuration cache]: unable to store the configuration: {}",
+                e
+            ),
+            Ok(_) => {
+                info!(
+                    "[configuration cache]: configuration version {} stored",
+                    configuration.version
+                );
+                self.version = Some(configuration.version.clone());
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "hal")))]
+mod tests {
+    use super::*;
+    use crate::{
+        hal::{host::storage::InMemoryStorageProvider, storage::StorageProvider},
+        service::{client_service::default_configuration, settings_service::Settings},
+    };
+    use std::{cell::Cell, rc::Rc};
+
+    // counts the writes of the wrapped storage
+    struct CountingStorage {
+        storage: Box<dyn KeyValueStorage>,
+        writes: Rc<Cell<u32>>,
+    }
+
+    impl KeyValueStorage for CountingStorage {
+        fn read(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+            self.storage.read(key)
+        }
+
+        fn write(&mut self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+            self.writes.set(self.writes.get() + 1);
+            self.storage.write(key, value)
+        }
+
+        fn remove(&mut self, key: &str) -> anyhow::Result<()> {
+            self.storage.remove(key)
+        }
+    }
+
+    fn open(provider: &mut InMemoryStorageProvider) -> ConfigurationCacheService {
+        ConfigurationCacheService::new(Some(provider.open(CONFIGURATION_NAMESPACE).unwrap()))
+    }
+
+    fn open_counting(
+        provider: &mut InMemoryStorageProvider,
+        writes: &Rc<Cell<u32>>,
+    ) -> ConfigurationCacheService {
+        ConfigurationCacheService::new(Some(Box::new(CountingStorage {
+            storage: provider.open(CONFIGURATION_NAMESPACE).unwrap(),
+            writes: writes.clone(),
+        })))
+    }
+
+    fn defaults() -> Configuration {
+        default_configuration(&Settings::default())
+    }
+
+    fn versioned(version: &str, interval: u64) -> VersionedConfiguration {
+        VersionedConfiguration {
+            configuration: Configuration {
+                weather_sensor_supply_interval_seconds: interval,
+                ..defaults()
+            },
+            version: version.to_owned(),
+        }
+    }
+
+    #[test]
+    fn load_returns_what_was_stored() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut cache = open(&mut provider);
+        assert!(cache.load().is_none());
+        cache.store(&versioned("\"v1\"", 120));
+
+        // at the next boot
+        let loaded = open(&mut provider).load().unwrap();
+        assert_eq!(loaded.version, "\"v1\"");
+        assert_eq!(loaded.configuration, versioned("\"v1\"", 120).configuration);
+    }
+
+    #[test]
+    fn store_writes_only_a_new_version() {
+        let mut provider = InMemoryStorageProvider::new();
+        let writes = Rc::new(Cell::new(0));
+        let mut cache = open_counting(&mut provider, &writes);
+        cache.store(&versioned("v1", 120));
+        cache.store(&versioned("v1", 120));
+        assert_eq!(writes.get(), 1);
+
+        // the version stored by the previous boot is not written again
+        let mut cache = open_counting(&mut provider, &writes);
+        cache.store(&versioned("v1", 120));
+        assert_eq!(writes.get(), 1);
+        cache.store(&versioned("v2", 180));
+        assert_eq!(writes.get(), 2);
+        assert_eq!(open(&mut provider).load().unwrap().version, "v2");
+    }
+
+    #[test]
+    fn fallback_prefers_the_cached_configuration() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut cache = open(&mut provider);
+        assert!(matches!(
+            cache.fallback(defaults),
+            FallbackConfiguration::Defaults(configuration) if configuration == defaults()
+        ));
+
+        cache.store(&versioned("v1", 120));
+        match open(&mut provider).fallback(defaults) {
+            FallbackConfiguration::Cached(cached) => {
+                assert_eq!(cached.version, "v1");
+                assert_eq!(
+                    cached.configuration.weather_sensor_supply_interval_seconds,
+                    120
+                );
+            }
+            FallbackConfiguration::Defaults(_) => panic!("the cached configuration is not used"),
+        }
+    }
+
+    #[test]
+    fn fallback_ignores_a_cached_configuration_that_is_not_valid() {
+        let mut provider = InMemoryStorageProvider::new();
+        // e.g. stored by a firmware with looser bounds
+        open(&mut provider).store(&versioned("v1", 0));
+        assert!(matches!(
+            open(&mut provider).fallback(defaults),
+            FallbackConfiguration::Defaults(_)
+        ));
+    }
+
+    #[test]
+    fn corrupt_entry_falls_back_to_the_defaults() {
+        let blobs: [&[u8]; 3] = [
+            b"\xFF\x00garbage",
+            b"{\"version\":\"v1\"}",
+            b"{\"version\":\"v1\",\"configuration\":{\"alertEndpoint\":42}}",
+        ];
+        for blob in blobs {
+            let mut provider = InMemoryStorageProvider::new();
+            let mut storage = provider.open(CONFIGURATION_NAMESPACE).unwrap();
+            storage.write(KEY_CONFIGURATION, blob).unwrap();
+            let mut cache = open(&mut provider);
+            assert!(cache.load().is_none());
+            assert!(matches!(
+                cache.fallback(defaults),
+                FallbackConfiguration::Defaults(_)
+            ));
+            // and it is replaced by the next configuration
+            cache.store(&versioned("v1", 120));
+            assert_eq!(open(&mut provider).load().unwrap().version, "v1");
+        }
+    }
+
+    #[test]
+    fn without_the_storage_nothing_is_cached() {
+        let mut cache = ConfigurationCacheService::new(None);
+        cache.store(&versioned("v1", 120));
+        assert!(cache.load().is_none());
+        assert!(matches!(
+            cache.fallback(defaults),
+            FallbackConfiguration::Defaults(_)
+        ));
+    }
+}
@@ -0,0 +1,542 @@
+use crate::dto::{
+    config_response::Configuration,
+    mqtt_configuration::MqttConfiguration,
+    request_configuration_errors::FieldError,
+    retry_configuration::{RetryConfiguration, RetryPolicyConfiguration},
+};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+// version of the configuration schema known by this firmware, sent with the request of
+// the configuration; the fields added by a newer schema are ignored
+pub const CONFIGURATION_SCHEMA_VERSION: u64 = 1;
+
+const SCHEMA_VERSION_FIELD: &str = "schemaVersion";
+
+const CONFIGURATION_FIELDS: &[&str] = &[
+    SCHEMA_VERSION_FIELD,
+    "alertEndpoint",
+    "iAmAliveEndpoint",
+    "temperatureSensorUnitOfMeasure",
+    "weatherSensorSupplyIntervalSeconds",
+    "iAmAliveIntervalSeconds",
+    "crontab",
+    "transport",
+    "mqtt",
+    "configurationRefreshIntervalSeconds",
+    "retryPolicies",
+];
+
+const MQTT_FIELDS: &[&str] = &[
+    "brokerUrl",
+    "username",
+    "password",
+    "qos",
+    "measurementTopic",
+    "statusTopic",
+    "registrationTopic",
+    "homeAssistantDiscovery",
+    "discoveryPrefix",
+];
+
+const RETRY_CALLS: &[&str] = &["submit", "heartbeat", "configuration", "registration"];
+
+const RETRY_POLICY_FIELDS: &[&str] =
+    &["maxAttempts", "baseDelayMillis", "maxDelayMillis", "jitter"];
+
+#[derive(Debug)]
+pub struct MergedConfiguration {
+    pub configuration: Configuration,
+    // the schema of the document, 1 if it does not say
+    pub schema_version: u64,
+    // the fields with a value of the wrong type, they keep the value of the base
+    pub errors: Vec<FieldError>,
+    // the fields not known by this firmware, ignored
+    pub unknown_fields: Vec<String>,
+}
+
+// the configuration sent by the server applied field by field over the base one (the
+// compiled defaults): a missing or null field keeps the value of the base, so a server
+// may send only the fields it manages
+pub fn merge(document: &Map<String, Value>, base: &Configuration) -> MergedConfiguration {
+    let mut fields = Fields::new(document, String::new(), CONFIGURATION_FIELDS);
+    // a schema version that cannot be read is not a reason to drop the configuration
+    let schema_version = document
+        .get(SCHEMA_VERSION_FIELD)
+        .and_then(Value::as_u64)
+        .unwrap_or(1);
+
+    let configuration = Configuration {
+        alert_endpoint: fields
+            .get("alertEndpoint")
+            .unwrap_or_else(|| base.alert_endpoint.clone()),
+        i_am_alive_endpoint: fields
+            .get("iAmAliveEndpoint")
+            .unwrap_or_else(|| base.i_am_alive_endpoint.clone()),
+        temperature_sensor_unit_of_measure: fields
+            .get("temperatureSensorUnitOfMeasure")
+            .unwrap_or_else(|| base.temperature_sensor_unit_of_measure.clone()),
+        weather_sensor_supply_interval_seconds: fields
+            .get("weatherSensorSupplyIntervalSeconds")
+            .unwrap_or(base.weather_sensor_supply_interval_seconds),
+        i_am_alive_interval_seconds: fields
+            .get("iAmAliveIntervalSeconds")
+            .unwrap_or(base.i_am_alive_interval_seconds),
+        crontab: fields.get("crontab").or_else(|| base.crontab.clone()),
+        transport: fields.get("transport").or_else(|| base.transport.clone()),
+        mqtt: match document.get("mqtt") {
+            None | Some(Value::Null) => base.mqtt.clone(),
+            Some(Value::Object(mqtt)) => fields.merge_mqtt(mqtt, base.mqtt.as_ref()),
+            Some(_) => {
+                fields.error("mqtt", "expected an object".to_owned());
+                base.mqtt.clone()
+            }
+        },
+        configuration_refresh_interval_seconds: fields
+            .get("configurationRefreshIntervalSeconds")
+            .unwrap_or(base.configuration_refresh_interval_seconds),
+        retry_policies: match document.get("retryPolicies") {
+            None | Some(Value::Null) => base.retry_policies,
+            Some(Value::Object(retry_policies)) => {
+                fields.merge_retry_policies(retry_policies, &base.retry_policies)
+            }
+            Some(_) => {
+                fields.error("retryPolicies", "expected an object".to_owned());
+                base.retry_policies
+            }
+        },
+    };
+
+    MergedConfiguration {
+        configuration,
+        schema_version,
+        errors: fields.errors,
+        unknown_fields: fields.unknown_fields,
+    }
+}
+
+// reads the fields of a JSON object, collecting the errors and the unknown fields
+struct Fields<'a> {
+    object: &'a Map<String, Value>,
+    // prepended to the names of the fields, e.g. "mqtt."
+    prefix: String,
+    errors: Vec<FieldError>,
+    unknown_fields: Vec<String>,
+}
+
+impl<'a> Fields<'a> {
+    fn new(object: &'a Map<String, Value>, prefix: String, known_fields: &[&str]) -> Self {
+        let unknown_fields = object
+            .keys()
+            .filter(|field| !known_fields.contains(&field.as_str()))
+            .map(|field| format!("{}{}", prefix, field))
+            .collect();
+        Fields {
+            object,
+            prefix,
+            errors: Vec::new(),
+            unknown_fields,
+        }
+    }
+
+    // None if the field is missing, null or of the wrong type
+    fn get<T: DeserializeOwned>(&mut self, field: &str) -> Option<T> {
+        match self.object.get(field) {
+            None | Some(Value::Null) => None,
+            Some(value) => match serde_json::from_value(value.clone()) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    self.error(field, e.to_string());
+                    None
+                }
+            },
+        }
+    }
+
+    fn error(&mut self, field: &str, message: String) {
+        self.errors.push(FieldError::new(
+            &format!("{}{}", self.prefix, field),
+            message,
+        ));
+    }
+
+    // the mqtt section over the one of the base, None if neither has a broker
+    fn merge_mqtt(
+        &mut self,
+        object: &Map<String, Value>,
+        base: Option<&MqttConfiguration>,
+    ) -> Option<MqttConfiguration> {
+        let mut fields = Fields::new(object, "mqtt.".to_owned(), MQTT_FIELDS);
+        let broker_url = fields
+            .get("brokerUrl")
+            .or_else(|| base.map(|base| base.broker_url.clone()));
+        let mqtt = match broker_url {
+            None => {
+                if !fields.errors.iter().any(|e| e.field == "mqtt.brokerUrl") {
+                    fields.error("brokerUrl", "missing".to_owned());
+                }
+                None
+            }
+            Some(broker_url) => {
+                let base = base
+                    .cloned()
+                    .unwrap_or_else(|| MqttConfiguration::new(broker_url.clone()));
+                Some(MqttConfiguration {
+                    broker_url,
+                    username: fields.get("username").or(base.username),
+                    password: fields.get("password").or(base.password),
+                    qos: fields.get("qos").unwrap_or(base.qos),
+                    measurement_topic: fields
+                        .get("measurementTopic")
+                        .unwrap_or(base.measurement_topic),
+                    status_topic: fields.get("statusTopic").unwrap_or(base.status_topic),
+                    registration_topic: fields
+                        .get("registrationTopic")
+                        .unwrap_or(base.registration_topic),
+                    home_assistant_discovery: fields
+                        .get("homeAssistantDiscovery")
+                        .unwrap_or(base.home_assistant_discovery),
+                    discovery_prefix: fields
+                        .get("discoveryPrefix")
+                        .unwrap_or(base.discovery_prefix),
+                })
+            }
+        };
+        self.append(fields);
+        mqtt
+    }
+
+    // the policy of each call over the one of the base
+    fn merge_retry_policies(
+        &mut self,
+        object: &Map<String, Value>,
+        base: &RetryConfiguration,
+    ) -> RetryConfiguration {
+        let mut fields = Fields::new(object, "retryPolicies.".to_owned(), RETRY_CALLS);
+        let retry_policies = RetryConfiguration {
+            submit: fields.merge_retry_policy("submit", &base.submit),
+            heartbeat: fields.merge_retry_policy("heartbeat", &base.heartbeat),
+            configuration: fields.merge_retry_policy("configuration", &base.configuration),
+            registration: fields.merge_retry_policy("registration", &base.registration),
+        };
+        self.append(fields);
+        retry_policies
+    }
+
+    fn merge_retry_policy(
+        &mut self,
+        call: &str,
+        base: &RetryPolicyConfiguration,
+    ) -> RetryPolicyConfiguration {
+        let object = match self.object.get(call) {
+            None | Some(Value::Null) => return *base,
+            Some(Value::Object(object)) => object,
+            Some(_) => {
+                self.error(call, "expected an object".to_owned());
+                return *base;
+            }
+        };
+        let mut fields = Fields::new(
+            object,
+            format!("{}{}.", self.prefix, call),
+            RETRY_POLICY_FIELDS,
+        );
+        let policy = RetryPolicyConfiguration {
+            max_attempts: fields.get("maxAttempts").unwrap_or(base.max_attempts),
+            base_delay_millis: fields
+                .get("baseDelayMillis")
+                .unwrap_or(base.base_delay_millis),
+            max_delay_millis: fields
+                .get("maxDelayMillis")
+                .unwrap_or(base.max_delay_millis),
+            jitter: fields.get("jitter").unwrap_or(base.jitter),
+        };
+        self.append(fields);
+        policy
+    }
+
+    // adds the errors and the unknown fields of a nested object
+    fn append(&mut self, mut fields: Fields) {
+        self.errors.append(&mut fields.errors);
+        self.unknown_fields.append(&mut fields.unknown_fields);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn base() -> Configuration {
+        Configuration {
+            alert_endpoint: "https://server/api/v1/weather-sensor/submit".to_owned(),
+            i_am_alive_endpoint: "https://server/api/v1/i-am-alive/notify".to_owned(),
+            temperature_sensor_unit_of_measure: "C".to_owned(),
+            weather_sensor_supply_interval_seconds: 60,
+            i_am_alive_interval_seconds: 30,
+            crontab: Some("0 */5 * * * *".to_owned()),
+            transport: None,
+            mqtt: None,
+            configuration_refresh_interval_seconds: 3600,
+            retry_policies: RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(4, 500, 8000, 0.5),
+                heartbeat: RetryPolicyConfiguration::new(2, 500, 2000, 0.5),
+                configuration: RetryPolicyConfiguration::new(5, 1000, 16000, 0.5),
+                registration: RetryPolicyConfiguration::new(5, 1000, 16000, 0.5),
+            },
+        }
+    }
+
+    fn base_with_mqtt() -> Configuration {
+        let mut mqtt = MqttConfiguration::new("mqtts://broker:8883".to_owned());
+        mqtt.username = Some("station".to_owned());
+        mqtt.password = Some("secret".to_owned());
+        Configuration {
+            transport: Some("mqtt".to_owned()),
+            mqtt: Some(mqtt),
+            ..base()
+        }
+    }
+
+    fn merge_json(document: Value, base: &Configuration) -> MergedConfiguration {
+        merge(document.as_object().unwrap(), base)
+    }
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|error| error.field.as_str()).collect()
+    }
+
+    #[test]
+    fn empty_document_keeps_the_base() {
+        let merged = merge_json(json!({}), &base());
+        assert_eq!(merged.configuration, base());
+        assert_eq!(merged.schema_version, 1);
+        assert!(merged.errors.is_empty());
+        assert!(merged.unknown_fields.is_empty());
+    }
+
+    #[test]
+    fn absent_fields_keep_the_base() {
+        let merged = merge_json(
+            json!({ "iAmAliveIntervalSeconds": 10, "transport": "http" }),
+            &base(),
+        );
+        assert_eq!(
+            merged.configuration,
+            Configuration {
+                i_am_alive_interval_seconds: 10,
+                transport: Some("http".to_owned()),
+                ..base()
+            }
+        );
+        assert!(merged.errors.is_empty());
+    }
+
+    #[test]
+    fn every_field_is_applied() {
+        let document = json!({
+            "schemaVersion": 1,
+            "alertEndpoint": "https://other/submit",
+            "iAmAliveEndpoint": "https://other/alive",
+            "temperatureSensorUnitOfMeasure": "F",
+            "weatherSensorSupplyIntervalSeconds": 120,
+            "iAmAliveIntervalSeconds": 15,
+            "crontab": "0 0 * * * *",
+            "transport": "mqtt",
+            "mqtt": { "brokerUrl": "mqtt://broker:1883" },
+            "configurationRefreshIntervalSeconds": 0,
+            "retryPolicies": {}
+        });
+        let merged = merge_json(document, &base());
+        assert_eq!(
+            merged.configuration,
+            Configuration {
+                alert_endpoint: "https://other/submit".to_owned(),
+                i_am_alive_endpoint: "https://other/alive".to_owned(),
+                temperature_sensor_unit_of_measure: "F".to_owned(),
+                weather_sensor_supply_interval_seconds: 120,
+                i_am_alive_interval_seconds: 15,
+                crontab: Some("0 0 * * * *".to_owned()),
+                transport: Some("mqtt".to_owned()),
+                mqtt: Some(MqttConfiguration::new("mqtt://broker:1883".to_owned())),
+                configuration_refresh_interval_seconds: 0,
+                ..base()
+            }
+        );
+        assert!(merged.errors.is_empty());
+        assert!(merged.unknown_fields.is_empty());
+    }
+
+    #[test]
+    fn explicit_nulls_keep_the_base() {
+        let document = json!({
+            "schemaVersion": null,
+            "alertEndpoint": null,
+            "iAmAliveEndpoint": null,
+            "temperatureSensorUnitOfMeasure": null,
+            "weatherSensorSupplyIntervalSeconds": null,
+            "iAmAliveIntervalSeconds": null,
+            "crontab": null,
+            "transport": null,
+            "mqtt": null,
+            "configurationRefreshIntervalSeconds": null,
+            "retryPolicies": null
+        });
+        let merged = merge_json(document, &base_with_mqtt());
+        assert_eq!(merged.configuration, base_with_mqtt());
+        assert_eq!(merged.schema_version, 1);
+        assert!(merged.errors.is_empty());
+    }
+
+    #[test]
+    fn values_of_the_wrong_type_keep_the_base() {
+        let document = json!({
+            "alertEndpoint": 42,
+            "weatherSensorSupplyIntervalSeconds": "60",
+            "iAmAliveIntervalSeconds": -1,
+            "crontab": ["0 * * * * *"],
+            "mqtt": "mqtt://broker",
+            "retryPolicies": true,
+            "temperatureSensorUnitOfMeasure": "K"
+        });
+        let merged = merge_json(document, &base());
+        assert_eq!(
+            fields(&merged.errors),
+            [
+                "alertEndpoint",
+                "weatherSensorSupplyIntervalSeconds",
+                "iAmAliveIntervalSeconds",
+                "crontab",
+                "mqtt",
+                "retryPolicies",
+            ]
+        );
+        // the valid fields of the same document are applied
+        assert_eq!(
+            merged.configuration,
+            Configuration {
+                temperature_sensor_unit_of_measure: "K".to_owned(),
+                ..base()
+            }
+        );
+    }
+
+    #[test]
+    fn mqtt_fields_are_merged_over_the_base_section() {
+        let document = json!({
+            "mqtt": { "qos": 2, "measurementTopic": "garden/measurement", "password": null }
+        });
+        let merged = merge_json(document, &base_with_mqtt());
+        let mqtt = merged.configuration.mqtt.unwrap();
+        assert_eq!(mqtt.qos, 2);
+        assert_eq!(mqtt.measurement_topic, "garden/measurement");
+        // the other fields, also the null one, are the ones of the base
+        let base = base_with_mqtt().mqtt.unwrap();
+        assert_eq!(mqtt.broker_url, base.broker_url);
+        assert_eq!(mqtt.username, base.username);
+        assert_eq!(mqtt.password, base.password);
+        assert_eq!(mqtt.status_topic, base.status_topic);
+        assert!(merged.errors.is_empty());
+    }
+
+    #[test]
+    fn mqtt_section_needs_a_broker() {
+        let merged = merge_json(json!({ "mqtt": { "qos": 0 } }), &base());
+        assert_eq!(merged.configuration.mqtt, None);
+        assert_eq!(fields(&merged.errors), ["mqtt.brokerUrl"]);
+        assert_eq!(merged.errors[0].message, "missing");
+
+        // a broker of the wrong type is reported once
+        let merged = merge_json(json!({ "mqtt": { "brokerUrl": 1883 } }), &base());
+        assert_eq!(merged.configuration.mqtt, None);
+        assert_eq!(fields(&merged.errors), ["mqtt.brokerUrl"]);
+        assert_ne!(merged.errors[0].message, "missing");
+    }
+
+    #[test]
+    fn mqtt_errors_and_unknown_fields_have_the_prefix() {
+        let document = json!({
+            "mqtt": { "qos": "high", "retain": true, "homeAssistantDiscovery": false }
+        });
+        let merged = merge_json(document, &base_with_mqtt());
+        assert_eq!(fields(&merged.errors), ["mqtt.qos"]);
+        assert_eq!(merged.unknown_fields, ["mqtt.retain"]);
+        let mqtt = merged.configuration.mqtt.unwrap();
+        assert_eq!(mqtt.qos, base_with_mqtt().mqtt.unwrap().qos);
+        assert!(!mqtt.home_assistant_discovery);
+    }
+
+    #[test]
+    fn retry_policies_are_merged_field_by_field() {
+        let document = json!({
+            "retryPolicies": {
+                "submit": { "maxAttempts": 6, "jitter": 0 },
+                "heartbeat": null,
+                "registration": { "baseDelayMillis": 200, "maxDelayMillis": null }
+            }
+        });
+        let merged = merge_json(document, &base());
+        let base = base().retry_policies;
+        assert_eq!(
+            merged.configuration.retry_policies,
+            RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(6, 500, 8000, 0.0),
+                registration: RetryPolicyConfiguration::new(5, 200, 16000, 0.5),
+                ..base
+            }
+        );
+        assert!(merged.errors.is_empty());
+    }
+
+    #[test]
+    fn retry_policy_errors_and_unknown_fields_have_the_prefix() {
+        let document = json!({
+            "retryPolicies": {
+                "submit": { "maxAttempts": "many", "backoff": "exponential" },
+                "heartbeat": 3,
+                "alert": { "maxAttempts": 1 }
+            }
+        });
+        let merged = merge_json(document, &base());
+        assert_eq!(
+            fields(&merged.errors),
+            [
+                "retryPolicies.submit.maxAttempts",
+                "retryPolicies.heartbeat"
+            ]
+        );
+        assert_eq!(
+            merged.unknown_fields,
+            ["retryPolicies.alert", "retryPolicies.submit.backoff"]
+        );
+        assert_eq!(merged.configuration.retry_policies, base().retry_policies);
+    }
+
+    #[test]
+    fn newer_schema_is_read_ignoring_the_new_fields() {
+        let document = json!({
+            "schemaVersion": 2,
+            "iAmAliveIntervalSeconds": 10,
+            "displayBrightness": 80,
+            "mqtt": { "brokerUrl": "mqtt://broker", "keepAliveSeconds": 30 }
+        });
+        let merged = merge_json(document, &base());
+        assert_eq!(merged.schema_version, 2);
+        assert_eq!(
+            merged.unknown_fields,
+            ["displayBrightness", "mqtt.keepAliveSeconds"]
+        );
+        assert_eq!(merged.configuration.i_am_alive_interval_seconds, 10);
+        assert!(merged.configuration.mqtt.is_some());
+        assert!(merged.errors.is_empty());
+    }
+
+    #[test]
+    fn unreadable_schema_version_is_the_first_one() {
+        for schema_version in [json!("2"), json!(-1), json!(1.5)] {
+            let merged = merge_json(json!({ "schemaVersion": schema_version }), &base());
+            assert_eq!(merged.schema_version, 1);
+            assert!(merged.errors.is_empty());
+        }
+    }
+}
@@ -0,0 +1,635 @@
+use crate::{
+    dto::{
+        config_response::Configuration, mqtt_configuration::MqttConfiguration,
+        request_configuration_errors::FieldError, retry_configuration::RetryPolicyConfiguration,
+        temperature_unit::TemperatureUnit,
+    },
+    hal::http::PLAIN_HTTP_ALLOWED,
+};
+use cron::Schedule;
+use std::str::FromStr;
+
+// bounds of the intervals of the readings and of the heartbeat: below the minimum the
+// main loop would spin, above the maximum the station would look dead
+pub const MIN_INTERVAL_SECONDS: u64 = 1;
+pub const MAX_INTERVAL_SECONDS: u64 = 86400;
+// bounds of the refresh interval of the configuration, 0 disables the refresh
+pub const MIN_REFRESH_INTERVAL_SECONDS: u64 = 60;
+pub const MAX_REFRESH_INTERVAL_SECONDS: u64 = 604800;
+// bounds of the retry policies: a call retried longer would delay the other tasks too much
+pub const MAX_RETRY_ATTEMPTS: u32 = 10;
+pub const MAX_RETRY_DELAY_MILLIS: u64 = 300000;
+
+// the fields of the configuration that cannot be applied
+pub fn validate(configuration: &Configuration) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    check_endpoint(&mut errors, "alertEndpoint", &configuration.alert_endpoint);
+    check_endpoint(
+        &mut errors,
+        "iAmAliveEndpoint",
+        &configuration.i_am_alive_endpoint,
+    );
+    if let Err(e) = TemperatureUnit::parse(&configuration.temperature_sensor_unit_of_measure) {
+        errors.push(FieldError::new(
+            "temperatureSensorUnitOfMeasure",
+            format!("{}, expected C, F or K", e),
+        ));
+    }
+    check_interval(
+        &mut errors,
+        "weatherSensorSupplyIntervalSeconds",
+        configuration.weather_sensor_supply_interval_seconds,
+    );
+    check_interval(
+        &mut errors,
+        "iAmAliveIntervalSeconds",
+        configuration.i_am_alive_interval_seconds,
+    );
+    let refresh_interval = configuration.configuration_refresh_interval_seconds;
+    if refresh_interval != 0
+        && !(MIN_REFRESH_INTERVAL_SECONDS..=MAX_REFRESH_INTERVAL_SECONDS)
+            .contains(&refresh_interval)
+    {
+        errors.push(FieldError::new(
+            "configurationRefreshIntervalSeconds",
+            format!(
+                "{} is out of bounds, expected 0 or {}..={}",
+                refresh_interval, MIN_REFRESH_INTERVAL_SECONDS, MAX_REFRESH_INTERVAL_SECONDS
+            ),
+        ));
+    }
+    if let Some(crontab) = configuration.crontab.as_deref() {
+        if !crontab.trim().is_empty() {
+            if let Err(e) = Schedule::from_str(crontab) {
+                errors.push(FieldError::new(
+                    "crontab",
+                    format!("invalid expression {:?}: {}", crontab, e),
+                ));
+            }
+        }
+    }
+    check_transport(&mut errors, configuration);
+    let retry_policies = &configuration.retry_policies;
+    for (call, policy) in [
+        ("submit", &retry_policies.submit),
+        ("heartbeat", &retry_policies.heartbeat),
+        ("configuration", &retry_policies.configuration),
+        ("registration", &retry_policies.registration),
+    ] {
+        check_retry_policy(&mut errors, call, policy);
+    }
+    errors
+}
+
+// replaces the fields that cannot be applied, and the ones already rejected (e.g. while
+// reading the configuration), with the ones of the fallback (the active configuration);
+// returns the configuration to apply and all the errors
+pub fn sanitize(
+    configuration: &Configuration,
+    fallback: &Configuration,
+    mut errors: Vec<FieldError>,
+) -> (Configuration, Vec<FieldError>) {
+    errors.extend(validate(configuration));
+    let mut sanitized = configuration.clone();
+    for error in &errors {
+        match error.field.as_str() {
+            "alertEndpoint" => sanitized.alert_endpoint = fallback.alert_endpoint.clone(),
+            "iAmAliveEndpoint" => {
+                sanitized.i_am_alive_endpoint = fallback.i_am_alive_endpoint.clone()
+            }
+            "temperatureSensorUnitOfMeasure" => {
+                sanitized.temperature_sensor_unit_of_measure =
+                    fallback.temperature_sensor_unit_of_measure.clone()
+            }
+            "weatherSensorSupplyIntervalSeconds" => {
+                sanitized.weather_sensor_supply_interval_seconds =
+                    fallback.weather_sensor_supply_interval_seconds
+            }
+            "iAmAliveIntervalSeconds" => {
+                sanitized.i_am_alive_interval_seconds = fallback.i_am_alive_interval_seconds
+            }
+            "configurationRefreshIntervalSeconds" => {
+                sanitized.configuration_refresh_interval_seconds =
+                    fallback.configuration_refresh_interval_seconds
+            }
+            "crontab" => sanitized.crontab = fallback.crontab.clone(),
+            // a policy is replaced as a whole, its fields depend on each other
+            field if field.starts_with("retryPolicies") => {
+                let (sanitized, fallback) =
+                    (&mut sanitized.retry_policies, &fallback.retry_policies);
+                match field.split('.').nth(1) {
+                    Some("submit") => sanitized.submit = fallback.submit,
+                    Some("heartbeat") => sanitized.heartbeat = fallback.heartbeat,
+                    Some("configuration") => sanitized.configuration = fallback.configuration,
+                    Some("registration") => sanitized.registration = fallback.registration,
+                    _ => *sanitized = *fallback,
+                }
+            }
+            // the transport and its broker go together
+            _ => {
+                sanitized.transport = fallback.transport.clone();
+                sanitized.mqtt = fallback.mqtt.clone();
+            }
+        }
+    }
+    (sanitized, errors)
+}
+
+// the URL of an endpoint of the server: https, or also http if allowed, and a host
+pub fn check_server_url(url: &str) -> Result<(), String> {
+    let schemes: &[&str] = if PLAIN_HTTP_ALLOWED {
+        &["https://", "http://"]
+    } else {
+        &["https://"]
+    };
+    check_url(url, schemes)
+}
+
+fn check_endpoint(errors: &mut Vec<FieldError>, field: &str, url: &str) {
+    if let Err(message) = check_server_url(url) {
+        errors.push(FieldError::new(field, message));
+    }
+}
+
+fn check_interval(errors: &mut Vec<FieldError>, field: &str, interval: u64) {
+    if !(MIN_INTERVAL_SECONDS..=MAX_INTERVAL_SECONDS).contains(&interval) {
+        errors.push(FieldError::new(
+            field,
+            format!(
+                "{} is out of bounds, expected {}..={}",
+                interval, MIN_INTERVAL_SECONDS, MAX_INTERVAL_SECONDS
+            ),
+        ));
+    }
+}
+
+fn check_transport(errors: &mut Vec<FieldError>, configuration: &Configuration) {
+    let transport = configuration.transport.as_deref().unwrap_or("http");
+    match transport.trim().to_ascii_lowercase().as_str() {
+        "http" => {}
+        "mqtt" => match &configuration.mqtt {
+            None => errors.push(FieldError::new(
+                "mqtt",
+                "the MQTT transport needs the mqtt section".to_owned(),
+            )),
+            Some(mqtt) => check_mqtt(errors, mqtt),
+        },
+        _ => errors.push(FieldError::new(
+            "transport",
+            format!("invalid transport {:?}, expected http or mqtt", transport),
+        )),
+    }
+}
+
+fn check_mqtt(errors: &mut Vec<FieldError>, mqtt: &MqttConfiguration) {
+    let schemes: &[&str] = if PLAIN_HTTP_ALLOWED {
+        &["mqtts://", "mqtt://"]
+    } else {
+        &["mqtts://"]
+    };
+    if let Err(message) = check_url(&mqtt.broker_url, schemes) {
+        errors.push(FieldError::new("mqtt.brokerUrl", message));
+    }
+    if mqtt.qos > 2 {
+        errors.push(FieldError::new(
+            "mqtt.qos",
+            format!("{} is not a QoS level, expected 0, 1 or 2", mqtt.qos),
+        ));
+    }
+    for (field, topic) in [
+        ("mqtt.measurementTopic", &mqtt.measurement_topic),
+        ("mqtt.statusTopic", &mqtt.status_topic),
+        ("mqtt.registrationTopic", &mqtt.registration_topic),
+        ("mqtt.discoveryPrefix", &mqtt.discovery_prefix),
+    ] {
+        if topic.is_empty() || topic.contains(['+', '#']) {
+            errors.push(FieldError::new(
+                field,
+                format!(
+                    "invalid topic {:?}, it must be non empty, without wildcards",
+                    topic
+                ),
+            ));
+        }
+    }
+}
+
+fn check_retry_policy(errors: &mut Vec<FieldError>, call: &str, policy: &RetryPolicyConfiguration) {
+    let field = |name: &str| format!("retryPolicies.{}.{}", call, name);
+    if !(1..=MAX_RETRY_ATTEMPTS).contains(&policy.max_attempts) {
+        errors.push(FieldError::new(
+            &field("maxAttempts"),
+            format!(
+                "{} is out of bounds, expected 1..={}",
+                policy.max_attempts, MAX_RETRY_ATTEMPTS
+            ),
+        ));
+    }
+    if policy.max_delay_millis > MAX_RETRY_DELAY_MILLIS {
+        errors.push(FieldError::new(
+            &field("maxDelayMillis"),
+            format!(
+                "{} is out of bounds, expected at most {}",
+                policy.max_delay_millis, MAX_RETRY_DELAY_MILLIS
+            ),
+        ));
+    }
+    if policy.base_delay_millis > policy.max_delay_millis {
+        errors.push(FieldError::new(
+            &field("baseDelayMillis"),
+            format!(
+                "{} is greater than maxDelayMillis ({})",
+                policy.base_delay_millis, policy.max_delay_millis
+            ),
+        ));
+    }
+    if !(0.0..=1.0).contains(&policy.jitter) {
+        errors.push(FieldError::new(
+            &field("jitter"),
+            format!("{} is out of bounds, expected 0..=1", policy.jitter),
+        ));
+    }
+}
+
+// the URL must have one of the schemes and a host
+fn check_url(url: &str, schemes: &[&str]) -> Result<(), String> {
+    let rest = schemes
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+        .ok_or_else(|| format!("{:?} must start with {}", url, schemes.join(" or ")))?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = match authority.rsplit_once(':') {
+        // an IPv6 address without port, e.g. [fd00::1]
+        Some(_) if authority.ends_with(']') => authority,
+        Some((host, port)) => {
+            if port.parse::<u16>().is_err() {
+                return Err(format!("{:?} has an invalid port", url));
+            }
+            host
+        }
+        None => authority,
+    };
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || c == '@') {
+        return Err(format!("{:?} has no valid host", url));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::retry_configuration::RetryConfiguration;
+
+    fn http_configuration() -> Configuration {
+        Configuration {
+            alert_endpoint: "https://server:8443/api/v1/weather-sensor/submit".to_owned(),
+            i_am_alive_endpoint: "https://server:8443/api/v1/i-am-alive/notify".to_owned(),
+            temperature_sensor_unit_of_measure: "C".to_owned(),
+            weather_sensor_supply_interval_seconds: 60,
+            i_am_alive_interval_seconds: 30,
+            crontab: None,
+            transport: None,
+            mqtt: None,
+            configuration_refresh_interval_seconds: 3600,
+            retry_policies: RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(4, 500, 8000, 0.5),
+                heartbeat: RetryPolicyConfiguration::new(2, 500, 2000, 0.5),
+                configuration: RetryPolicyConfiguration::new(5, 1000, 16000, 0.5),
+                registration: RetryPolicyConfiguration::new(5, 1000, 16000, 0.5),
+            },
+        }
+    }
+
+    fn mqtt_configuration() -> Configuration {
+        Configuration {
+            transport: Some("mqtt".to_owned()),
+            mqtt: Some(MqttConfiguration::new("mqtts://broker:8883".to_owned())),
+            ..http_configuration()
+        }
+    }
+
+    // the fields rejected by the validation, in order
+    fn rejected(configuration: &Configuration) -> Vec<String> {
+        validate(configuration)
+            .into_iter()
+            .map(|error| error.field)
+            .collect()
+    }
+
+    #[test]
+    fn valid_configurations() {
+        assert!(rejected(&http_configuration()).is_empty());
+        assert!(rejected(&mqtt_configuration()).is_empty());
+        let configuration = Configuration {
+            crontab: Some("0 */5 * * * *".to_owned()),
+            configuration_refresh_interval_seconds: 0,
+            transport: Some(" HTTP ".to_owned()),
+            ..http_configuration()
+        };
+        assert!(rejected(&configuration).is_empty());
+    }
+
+    #[test]
+    fn bounds_are_inclusive() {
+        let configuration = Configuration {
+            weather_sensor_supply_interval_seconds: MIN_INTERVAL_SECONDS,
+            i_am_alive_interval_seconds: MAX_INTERVAL_SECONDS,
+            configuration_refresh_interval_seconds: MIN_REFRESH_INTERVAL_SECONDS,
+            retry_policies: RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(1, 0, 0, 0.0),
+                heartbeat: RetryPolicyConfiguration::new(
+                    MAX_RETRY_ATTEMPTS,
+                    MAX_RETRY_DELAY_MILLIS,
+                    MAX_RETRY_DELAY_MILLIS,
+                    1.0,
+                ),
+                ..http_configuration().retry_policies
+            },
+            ..http_configuration()
+        };
+        assert!(rejected(&configuration).is_empty());
+    }
+
+    #[test]
+    fn endpoints() {
+        for url in [
+            "ftp://server/submit",
+            "https://",
+            "https://server:port/submit",
+            "https://user@server/submit",
+            "server/submit",
+        ] {
+            let configuration = Configuration {
+                alert_endpoint: url.to_owned(),
+                i_am_alive_endpoint: url.to_owned(),
+                ..http_configuration()
+            };
+            assert_eq!(
+                rejected(&configuration),
+                ["alertEndpoint", "iAmAliveEndpoint"],
+                "{}",
+                url
+            );
+        }
+        assert!(check_server_url("https://[fd00::1]/submit").is_ok());
+        assert!(check_server_url("https://[fd00::1]:8443/submit").is_ok());
+    }
+
+    #[test]
+    fn temperature_unit() {
+        let configuration = Configuration {
+            temperature_sensor_unit_of_measure: "X".to_owned(),
+            ..http_configuration()
+        };
+        assert_eq!(rejected(&configuration), ["temperatureSensorUnitOfMeasure"]);
+    }
+
+    #[test]
+    fn intervals() {
+        for interval in [0, MAX_INTERVAL_SECONDS + 1] {
+            let configuration = Configuration {
+                weather_sensor_supply_interval_seconds: interval,
+                i_am_alive_interval_seconds: interval,
+                ..http_configuration()
+            };
+            assert_eq!(
+                rejected(&configuration),
+                [
+                    "weatherSensorSupplyIntervalSeconds",
+                    "iAmAliveIntervalSeconds"
+                ]
+            );
+        }
+        for interval in [
+            MIN_REFRESH_INTERVAL_SECONDS - 1,
+            MAX_REFRESH_INTERVAL_SECONDS + 1,
+        ] {
+            let configuration = Configuration {
+                configuration_refresh_interval_seconds: interval,
+                ..http_configuration()
+            };
+            assert_eq!(
+                rejected(&configuration),
+                ["configurationRefreshIntervalSeconds"]
+            );
+        }
+    }
+
+    #[test]
+    fn crontab() {
+        let configuration = Configuration {
+            crontab: Some("every minute".to_owned()),
+            ..http_configuration()
+        };
+        assert_eq!(rejected(&configuration), ["crontab"]);
+        // a blank expression means the interval
+        let configuration = Configuration {
+            crontab: Some("  ".to_owned()),
+            ..configuration
+        };
+        assert!(rejected(&configuration).is_empty());
+    }
+
+    #[test]
+    fn transport() {
+        let configuration = Configuration {
+            transport: Some("udp".to_owned()),
+            ..http_configuration()
+        };
+        assert_eq!(rejected(&configuration), ["transport"]);
+        let configuration = Configuration {
+            mqtt: None,
+            ..mqtt_configuration()
+        };
+        assert_eq!(rejected(&configuration), ["mqtt"]);
+    }
+
+    #[test]
+    fn mqtt_section() {
+        let mut configuration = mqtt_configuration();
+        let mqtt = configuration.mqtt.as_mut().unwrap();
+        mqtt.broker_url = "https://broker".to_owned();
+        mqtt.qos = 3;
+        mqtt.measurement_topic = "elisys/#".to_owned();
+        mqtt.status_topic = "elisys/+/status".to_owned();
+        mqtt.registration_topic = String::new();
+        mqtt.discovery_prefix = "homeassistant".to_owned();
+        assert_eq!(
+            rejected(&configuration),
+            [
+                "mqtt.brokerUrl",
+                "mqtt.qos",
+                "mqtt.measurementTopic",
+                "mqtt.statusTopic",
+                "mqtt.registrationTopic",
+            ]
+        );
+        // the section is not checked with the HTTP transport
+        configuration.transport = Some("http".to_owned());
+        assert!(rejected(&configuration).is_empty());
+    }
+
+    #[test]
+    fn retry_policies() {
+        let configuration = Configuration {
+            retry_policies: RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(0, 500, 8000, 0.5),
+                heartbeat: RetryPolicyConfiguration::new(
+                    MAX_RETRY_ATTEMPTS + 1,
+                    500,
+                    MAX_RETRY_DELAY_MILLIS + 1,
+                    0.5,
+                ),
+                configuration: RetryPolicyConfiguration::new(5, 20000, 16000, 1.5),
+                registration: RetryPolicyConfiguration::new(5, 1000, 16000, -0.1),
+            },
+            ..http_configuration()
+        };
+        assert_eq!(
+            rejected(&configuration),
+            [
+                "retryPolicies.submit.maxAttempts",
+                "retryPolicies.heartbeat.maxAttempts",
+                "retryPolicies.heartbeat.maxDelayMillis",
+                "retryPolicies.configuration.baseDelayMillis",
+                "retryPolicies.configuration.jitter",
+                "retryPolicies.registration.jitter",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_error_of_a_document_is_reported() {
+        let configuration = Configuration {
+            alert_endpoint: "ftp://server".to_owned(),
+            temperature_sensor_unit_of_measure: "X".to_owned(),
+            weather_sensor_supply_interval_seconds: 0,
+            crontab: Some("every minute".to_owned()),
+            transport: Some("udp".to_owned()),
+            retry_policies: RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(0, 500, 8000, 0.5),
+                ..http_configuration().retry_policies
+            },
+            ..http_configuration()
+        };
+        let errors = validate(&configuration);
+        let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "alertEndpoint",
+                "temperatureSensorUnitOfMeasure",
+                "weatherSensorSupplyIntervalSeconds",
+                "crontab",
+                "transport",
+                "retryPolicies.submit.maxAttempts",
+            ]
+        );
+        assert!(errors.iter().all(|error| !error.message.is_empty()));
+    }
+
+    #[test]
+    fn sanitize_replaces_only_the_rejected_fields() {
+        let fallback = http_configuration();
+        let configuration = Configuration {
+            alert_endpoint: "ftp://server".to_owned(),
+            i_am_alive_endpoint: "https://other/api/v1/i-am-alive/notify".to_owned(),
+            temperature_sensor_unit_of_measure: "X".to_owned(),
+            weather_sensor_supply_interval_seconds: 0,
+            i_am_alive_interval_seconds: 10,
+            crontab: Some("every minute".to_owned()),
+            configuration_refresh_interval_seconds: 1,
+            ..fallback.clone()
+        };
+        let (sanitized, errors) = sanitize(&configuration, &fallback, Vec::new());
+        assert_eq!(errors.len(), 5);
+        assert_eq!(sanitized.alert_endpoint, fallback.alert_endpoint);
+        assert_eq!(
+            sanitized.temperature_sensor_unit_of_measure,
+            fallback.temperature_sensor_unit_of_measure
+        );
+        assert_eq!(
+            sanitized.weather_sensor_supply_interval_seconds,
+            fallback.weather_sensor_supply_interval_seconds
+        );
+        assert_eq!(sanitized.crontab, fallback.crontab);
+        assert_eq!(
+            sanitized.configuration_refresh_interval_seconds,
+            fallback.configuration_refresh_interval_seconds
+        );
+        // the valid fields are applied
+        assert_eq!(
+            sanitized.i_am_alive_endpoint,
+            "https://other/api/v1/i-am-alive/notify"
+        );
+        assert_eq!(sanitized.i_am_alive_interval_seconds, 10);
+        assert!(validate(&sanitized).is_empty());
+    }
+
+    #[test]
+    fn sanitize_replaces_the_errors_already_found() {
+        let fallback = http_configuration();
+        let configuration = Configuration {
+            i_am_alive_interval_seconds: 10,
+            ..fallback.clone()
+        };
+        // e.g. a value of the wrong type, found while reading the document
+        let errors = vec![FieldError::new(
+            "iAmAliveIntervalSeconds",
+            "invalid type".to_owned(),
+        )];
+        let (sanitized, errors) = sanitize(&configuration, &fallback, errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(sanitized.i_am_alive_interval_seconds, 30);
+    }
+
+    #[test]
+    fn sanitize_replaces_the_transport_with_its_broker() {
+        let fallback = mqtt_configuration();
+        let mut configuration = mqtt_configuration();
+        configuration.mqtt.as_mut().unwrap().broker_url = "https://broker".to_owned();
+        let (sanitized, errors) = sanitize(&configuration, &fallback, Vec::new());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(sanitized.mqtt, fallback.mqtt);
+
+        let configuration = Configuration {
+            transport: Some("udp".to_owned()),
+            ..mqtt_configuration()
+        };
+        let (sanitized, _) = sanitize(&configuration, &http_configuration(), Vec::new());
+        assert_eq!(sanitized.transport, None);
+        assert_eq!(sanitized.mqtt, None);
+    }
+
+    #[test]
+    fn sanitize_replaces_a_retry_policy_as_a_whole() {
+        let fallback = http_configuration();
+        let configuration = Configuration {
+            retry_policies: RetryConfiguration {
+                submit: RetryPolicyConfiguration::new(8, 100, 1000, 2.0),
+                heartbeat: RetryPolicyConfiguration::new(3, 100, 1000, 0.1),
+                ..fallback.retry_policies
+            },
+            ..fallback.clone()
+        };
+        let (sanitized, errors) = sanitize(&configuration, &fallback, Vec::new());
+        assert_eq!(errors.len(), 1);
+        // the valid maxAttempts of the rejected policy is not kept
+        assert_eq!(
+            sanitized.retry_policies.submit,
+            fallback.retry_policies.submit
+        );
+        assert_eq!(
+            sanitized.retry_policies.heartbeat,
+            RetryPolicyConfiguration::new(3, 100, 1000, 0.1)
+        );
+
+        // an error on the whole section replaces all the policies
+        let errors = vec![FieldError::new(
+            "retryPolicies",
+            "expected an object".to_owned(),
+        )];
+        let (sanitized, _) = sanitize(&configuration, &fallback, errors);
+        assert_eq!(sanitized.retry_policies, fallback.retry_policies);
+    }
+}
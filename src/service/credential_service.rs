@@ -0,0 +1,176 @@
+use super::signature_service::{sign, NONCE_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+use crate::{config::config::SIGNING_KEY, hal::storage::KeyValueStorage};
+use log::{error, info, warn};
+
+// NVS namespace of the credentials of the device
+pub const CREDENTIALS_NAMESPACE: &str = "credentials";
+
+const KEY_TOKEN: &str = "token";
+const KEY_NONCE: &str = "nonce";
+// the nonces are reserved in blocks, to write the NVS once every NONCE_BLOCK requests
+const NONCE_BLOCK: u64 = 1000;
+// without the stored nonce the nonces start at a random point above this one, beyond
+// the ones of the counter
+const RANDOM_NONCE_BASE: u64 = 1 << 62;
+
+// token issued by the server at the registration, sent with every call; it is kept in
+// NVS so that the device can authenticate also when the registration fails at boot.
+// The bodies are also signed, with SIGNING_KEY or else with the token.
+pub struct CredentialService {
+    storage: Option<Box<dyn KeyValueStorage>>,
+    token: Option<String>,
+    next_nonce: u64,
+    // first nonce not reserved in NVS, it is where the next boot starts
+    reserved_nonce: u64,
+}
+
+impl CredentialService {
+    // random is used only if the stored nonce is not available
+    pub fn new(mut storage: Option<Box<dyn KeyValueStorage>>, random: u64) -> Self {
+        let token = match storage.as_mut().map(|storage| storage.read(KEY_TOKEN)) {
+            Some(Ok(Some(token))) => String::from_utf8(token).ok(),
+            Some(Err(e)) => {
+                error!("[credentials]: unable to read the token: {}", e);
+                None
+            }
+            _ => None,
+        };
+        info!("[credentials]: token available? {}", token.is_some());
+        let next_nonce = match storage.as_mut().map(|storage| storage.read(KEY_NONCE)) {
+            Some(Ok(Some(nonce))) if nonce.len() == 8 => {
+                u64::from_le_bytes(nonce.try_into().unwrap())
+            }
+            Some(Ok(None)) => 1,
+            _ => {
+                // the clock may not be synchronized, a random start is not likely to reuse
+                // the nonces of another boot
+                warn!("[credentials]: stored nonce not available, starting from a random one");
+                RANDOM_NONCE_BASE | (random >> 2)
+            }
+        };
+        CredentialService {
+            storage,
+            token,
+            next_nonce,
+            reserved_nonce: next_nonce,
+        }
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    // stores the token, or forgets it if None
+    pub fn set_token(&mut self, token: Option<String>) {
+        if let Some(storage) = self.storage.as_mut() {
+            let result = match &token {
+                Some(token) => storage.write(KEY_TOKEN, token.as_bytes()),
+                None => storage.remove(KEY_TOKEN),
+            };
+            if let Err(e) = result {
+                error!("[credentials]: unable to store the token: {}", e);
+            }
+        }
+        self.token = token;
+    }
+
+    // authorization and signature headers of a request with the given body
+    pub fn headers(&mut self, body: &[u8]) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(token) = &self.token {
+            headers.push(("authorization", format!("Bearer {}", token)));
+        }
+        let key = match SIGNING_KEY.or(self.token.as_deref()) {
+            None => return headers,
+            Some(key) => key.as_bytes().to_vec(),
+        };
+        let timestamp_millis = chrono::Utc::now().timestamp_millis();
+        let nonce = self.take_nonce();
+        headers.push((TIMESTAMP_HEADER, timestamp_millis.to_string()));
+        headers.push((NONCE_HEADER, nonce.to_string()));
+        headers.push((SIGNATURE_HEADER, sign(&key, timestamp_millis, nonce, body)));
+        headers
+    }
+
+    fn take_nonce(&mut self) -> u64 {
+        let nonce = self.next_nonce;
+        self.next_nonce += 1;
+        if nonce >= self.reserved_nonce {
+            self.reserved_nonce = nonce + NONCE_BLOCK;
+            if let Some(storage) = self.storage.as_mut() {
+                if let Err(e) = storage.write(KEY_NONCE, &self.reserved_nonce.to_le_bytes()) {
+                    error!("[credentials]: unable to store the nonce: {}", e);
+                }
+            }
+        }
+        nonce
+    }
+}
+
+#[cfg(all(test, not(feature = "hal")))]
+mod tests {
+    use super::*;
+    use crate::hal::{host::storage::InMemoryStorageProvider, storage::StorageProvider};
+
+    fn nonce(headers: &[(&'static str, String)]) -> u64 {
+        headers
+            .iter()
+            .find(|(name, _)| *name == NONCE_HEADER)
+            .map(|(_, value)| value.parse().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn nonces_grow_across_boots() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut credentials =
+            CredentialService::new(Some(provider.open(CREDENTIALS_NAMESPACE).unwrap()), 7);
+        credentials.set_token(Some("token".to_owned()));
+        let first = nonce(&credentials.headers(b"{}"));
+        let second = nonce(&credentials.headers(b"{}"));
+        assert_eq!((first, second), (1, 2));
+
+        // the next boot starts after the reserved block
+        let mut rebooted =
+            CredentialService::new(Some(provider.open(CREDENTIALS_NAMESPACE).unwrap()), 7);
+        assert_eq!(rebooted.token(), Some("token"));
+        assert_eq!(nonce(&rebooted.headers(b"{}")), 1 + NONCE_BLOCK);
+    }
+
+    #[test]
+    fn random_start_without_the_stored_nonce() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut storage = provider.open(CREDENTIALS_NAMESPACE).unwrap();
+        storage.write(KEY_TOKEN, b"token").unwrap();
+        storage.write(KEY_NONCE, b"bad").unwrap();
+        let mut credentials = CredentialService::new(Some(storage), u64::MAX);
+        let nonce = nonce(&credentials.headers(b"{}"));
+        assert_eq!(nonce, RANDOM_NONCE_BASE | (u64::MAX >> 2));
+        // above any nonce of the counter, and far from the overflow
+        assert!((RANDOM_NONCE_BASE..=u64::MAX / 2).contains(&nonce));
+    }
+
+    #[test]
+    fn headers_are_signed_with_the_token() {
+        let mut credentials = CredentialService::new(None, 0);
+        if SIGNING_KEY.is_none() {
+            assert!(credentials.headers(b"{}").is_empty());
+        }
+        credentials.set_token(Some("token".to_owned()));
+        let headers = credentials.headers(b"{}");
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(header, _)| *header == name)
+                .map(|(_, value)| value.clone())
+                .unwrap()
+        };
+        assert_eq!(header("authorization"), "Bearer token");
+        let timestamp: i64 = header(TIMESTAMP_HEADER).parse().unwrap();
+        let key = SIGNING_KEY.unwrap_or("token");
+        assert_eq!(
+            header(SIGNATURE_HEADER),
+            sign(key.as_bytes(), timestamp, nonce(&headers), b"{}")
+        );
+    }
+}
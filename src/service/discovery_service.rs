@@ -0,0 +1,246 @@
+use super::mqtt_service::{STATUS_OFFLINE, STATUS_ONLINE};
+use crate::dto::temperature_unit::TemperatureUnit;
+use serde_json::{json, Value};
+
+const MANUFACTURER: &str = "Elisys";
+
+// the sensors of the station, only the ones it has are announced
+#[derive(Debug, Clone, Copy)]
+pub struct StationSensors {
+    pub temperature_unit: TemperatureUnit,
+    pub pressure: bool,
+}
+
+// how the station is named in Home Assistant
+pub struct DeviceDescription<'a> {
+    pub name: &'a str,
+    pub model: &'a str,
+}
+
+// where the station publishes, the values are read from the measurement messages
+pub struct DiscoveryTopics<'a> {
+    pub discovery_prefix: &'a str,
+    pub measurement_topic: &'a str,
+    pub status_topic: &'a str,
+}
+
+struct Entity {
+    // key of the value in the measurement JSON, also the object id of the entity
+    key: &'static str,
+    name: &'static str,
+    component: &'static str,
+    device_class: &'static str,
+    unit_of_measurement: Option<&'static str>,
+    value_template: &'static str,
+}
+
+// topic and payload of the Home Assistant MQTT discovery configuration of each sensor,
+// see https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery
+pub fn discovery_messages(
+    mac_address: &str,
+    device: &DeviceDescription,
+    sensors: &StationSensors,
+    topics: &DiscoveryTopics,
+) -> Vec<(String, Value)> {
+    let mut entities = vec![
+        Entity {
+            key: "temperature",
+            name: "Temperature",
+            component: "sensor",
+            device_class: "temperature",
+            unit_of_measurement: Some(sensors.temperature_unit.symbol()),
+            value_template: "{{ value_json.temperature }}",
+        },
+        Entity {
+            key: "humidity",
+            name: "Humidity",
+            component: "sensor",
+            device_class: "humidity",
+            unit_of_measurement: Some("%"),
+            value_template: "{{ value_json.humidity }}",
+        },
+        Entity {
+            key: "lux",
+            name: "Illuminance",
+            component: "sensor",
+            device_class: "illuminance",
+            unit_of_measurement: Some("lx"),
+            value_template: "{{ value_json.lux }}",
+        },
+        Entity {
+            key: "light",
+            name: "Light",
+            component: "binary_sensor",
+            device_class: "light",
+            unit_of_measurement: None,
+            value_template: "{{ 'ON' if value_json.light else 'OFF' }}",
+        },
+    ];
+    if sensors.pressure {
+        entities.push(Entity {
+            key: "pressure",
+            name: "Pressure",
+            component: "sensor",
+            device_class: "atmospheric_pressure",
+            unit_of_measurement: Some("hPa"),
+            value_template: "{{ value_json.pressure }}",
+        });
+    }
+
+    let node_id = format!(
+        "weather_station_{}",
+        mac_address.replace(':', "").to_ascii_lowercase()
+    );
+    let device = json!({
+        "identifiers": [node_id],
+        "connections": [["mac", mac_address.to_ascii_lowercase()]],
+        "name": device.name,
+        "model": device.model,
+        "manufacturer": MANUFACTURER,
+    });
+    entities
+        .iter()
+        .map(|entity| {
+            let topic = format!(
+                "{}/{}/{}/{}/config",
+                topics.discovery_prefix, entity.component, node_id, entity.key
+            );
+            let mut payload = json!({
+                "name": entity.name,
+                "unique_id": format!("{}_{}", node_id, entity.key),
+                "device_class": entity.device_class,
+                "state_topic": topics.measurement_topic,
+                "value_template": entity.value_template,
+                "availability_topic": topics.status_topic,
+                "payload_available": STATUS_ONLINE,
+                "payload_not_available": STATUS_OFFLINE,
+                "device": device,
+            });
+            // the binary sensors have no unit and no state class
+            if let Some(unit_of_measurement) = entity.unit_of_measurement {
+                payload["unit_of_measurement"] = json!(unit_of_measurement);
+                payload["state_class"] = json!("measurement");
+            }
+            (topic, payload)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC_ADDRESS: &str = "AA:BB:CC:DD:EE:0F";
+
+    fn messages(temperature_unit: TemperatureUnit, pressure: bool) -> Vec<(String, Value)> {
+        discovery_messages(
+            MAC_ADDRESS,
+            &DeviceDescription {
+                name: "Garden",
+                model: "ESP32 weather station",
+            },
+            &StationSensors {
+                temperature_unit,
+                pressure,
+            },
+            &DiscoveryTopics {
+                discovery_prefix: "homeassistant",
+                measurement_topic: "elisys/aabbccddee0f/measurement",
+                status_topic: "elisys/aabbccddee0f/status",
+            },
+        )
+    }
+
+    fn topics(messages: &[(String, Value)]) -> Vec<&str> {
+        messages.iter().map(|(topic, _)| topic.as_str()).collect()
+    }
+
+    #[test]
+    fn announces_each_sensor_of_the_station() {
+        let messages = messages(TemperatureUnit::Celsius, true);
+        assert_eq!(
+            topics(&messages),
+            [
+                "homeassistant/sensor/weather_station_aabbccddee0f/temperature/config",
+                "homeassistant/sensor/weather_station_aabbccddee0f/humidity/config",
+                "homeassistant/sensor/weather_station_aabbccddee0f/lux/config",
+                "homeassistant/binary_sensor/weather_station_aabbccddee0f/light/config",
+                "homeassistant/sensor/weather_station_aabbccddee0f/pressure/config",
+            ]
+        );
+    }
+
+    #[test]
+    fn pressure_is_announced_only_with_the_sensor() {
+        let messages = messages(TemperatureUnit::Celsius, false);
+        assert_eq!(messages.len(), 4);
+        assert!(!topics(&messages)
+            .iter()
+            .any(|topic| topic.contains("pressure")));
+    }
+
+    #[test]
+    fn sensor_payload() {
+        let messages = messages(TemperatureUnit::Celsius, true);
+        assert_eq!(
+            messages[0].1,
+            json!({
+                "name": "Temperature",
+                "unique_id": "weather_station_aabbccddee0f_temperature",
+                "device_class": "temperature",
+                "state_topic": "elisys/aabbccddee0f/measurement",
+                "value_template": "{{ value_json.temperature }}",
+                "availability_topic": "elisys/aabbccddee0f/status",
+                "payload_available": "online",
+                "payload_not_available": "offline",
+                "unit_of_measurement": "°C",
+                "state_class": "measurement",
+                "device": {
+                    "identifiers": ["weather_station_aabbccddee0f"],
+                    "connections": [["mac", "aa:bb:cc:dd:ee:0f"]],
+                    "name": "Garden",
+                    "model": "ESP32 weather station",
+                    "manufacturer": "Elisys",
+                },
+            })
+        );
+        assert_eq!(messages[4].1["device_class"], "atmospheric_pressure");
+        assert_eq!(messages[4].1["unit_of_measurement"], "hPa");
+    }
+
+    #[test]
+    fn binary_sensor_payload_has_no_unit() {
+        let messages = messages(TemperatureUnit::Celsius, false);
+        assert_eq!(
+            messages[3].1,
+            json!({
+                "name": "Light",
+                "unique_id": "weather_station_aabbccddee0f_light",
+                "device_class": "light",
+                "state_topic": "elisys/aabbccddee0f/measurement",
+                "value_template": "{{ 'ON' if value_json.light else 'OFF' }}",
+                "availability_topic": "elisys/aabbccddee0f/status",
+                "payload_available": "online",
+                "payload_not_available": "offline",
+                "device": messages[0].1["device"],
+            })
+        );
+    }
+
+    #[test]
+    fn temperature_unit_follows_the_configuration() {
+        let unit = |temperature_unit| {
+            messages(temperature_unit, false)[0].1["unit_of_measurement"].clone()
+        };
+        assert_eq!(unit(TemperatureUnit::Fahrenheit), "°F");
+        assert_eq!(unit(TemperatureUnit::Kelvin), "K");
+    }
+
+    #[test]
+    fn every_entity_belongs_to_the_same_device() {
+        let messages = messages(TemperatureUnit::Celsius, true);
+        for (_, payload) in &messages {
+            assert_eq!(payload["device"], messages[0].1["device"]);
+        }
+    }
+}
@@ -1,3 +1,15 @@
 pub mod client_service;
+pub mod configuration_cache_service;
+pub mod configuration_merge_service;
+pub mod configuration_validation_service;
+pub mod credential_service;
+pub mod discovery_service;
+pub mod mqtt_service;
+pub mod offline_buffer_service;
 pub mod orchestrator_service;
 pub mod peripheral_service;
+pub mod retry_service;
+pub mod schedule_service;
+pub mod settings_service;
+pub mod signature_service;
+pub mod transport_service;
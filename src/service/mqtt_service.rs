@@ -0,0 +1,255 @@
+use super::{
+    client_service::HttpSession,
+    discovery_service::{discovery_messages, DeviceDescription, DiscoveryTopics, StationSensors},
+    transport_service::{MessageKind, MessageTransport},
+};
+use crate::{
+    config::config::MQTT_QOS,
+    dto::mqtt_configuration::MqttConfiguration,
+    error::ClientError,
+    hal::mqtt::{MqttConnection, MqttConnector, MqttMessage, MqttOptions, QoS},
+};
+use log::{error, info};
+use std::rc::Rc;
+
+pub const STATUS_ONLINE: &str = "online";
+pub const STATUS_OFFLINE: &str = "offline";
+
+// publishes the messages to the broker: the measurements, the registration (retained,
+// followed by the Home Assistant discovery) and, as heartbeat, the retained "online"
+// status; the broker publishes the "offline" last will when the device disappears
+pub struct MqttMessageTransport {
+    connector: Rc<dyn MqttConnector>,
+    connection: Option<Box<dyn MqttConnection>>,
+    options: MqttOptions,
+    qos: QoS,
+    measurement_topic: String,
+    status_topic: String,
+    registration_topic: String,
+    discovery: Vec<MqttMessage>,
+}
+
+impl MqttMessageTransport {
+    pub fn new(
+        connector: Rc<dyn MqttConnector>,
+        configuration: &MqttConfiguration,
+        mac_address: &str,
+        device: &DeviceDescription,
+        sensors: &StationSensors,
+    ) -> MqttMessageTransport {
+        let qos = QoS::from_level(configuration.qos).unwrap_or_else(|| {
+            error!(
+                "[mqtt]: invalid QoS {}, using {}",
+                configuration.qos, MQTT_QOS
+            );
+            QoS::from_level(MQTT_QOS).unwrap_or(QoS::AtLeastOnce)
+        });
+        let topic = |template: &str| template.replace("{mac}", mac_address);
+        let measurement_topic = topic(&configuration.measurement_topic);
+        let status_topic = topic(&configuration.status_topic);
+
+        let client_id = format!("weather-station-{}", mac_address.replace(':', ""));
+        let mut options = MqttOptions::new(&configuration.broker_url, &client_id);
+        options.username = configuration.username.clone();
+        options.password = configuration.password.clone();
+        options.last_will = Some(status_message(&status_topic, STATUS_OFFLINE, qos));
+        options.birth = Some(status_message(&status_topic, STATUS_ONLINE, qos));
+
+        let mut discovery = Vec::new();
+        if configuration.home_assistant_discovery {
+            let topics = DiscoveryTopics {
+                discovery_prefix: &configuration.discovery_prefix,
+                measurement_topic: &measurement_topic,
+                status_topic: &status_topic,
+            };
+            for (topic, payload) in discovery_messages(mac_address, device, sensors, &topics) {
+                discovery.push(MqttMessage {
+                    topic,
+                    payload: payload.to_string().into_bytes(),
+                    qos,
+                    retain: true,
+                });
+            }
+        }
+
+        MqttMessageTransport {
+            connector,
+            connection: None,
+            options,
+            qos,
+            measurement_topic,
+            status_topic,
+            registration_topic: topic(&configuration.registration_topic),
+            discovery,
+        }
+    }
+
+    // the connection is opened at the first message; afterwards it reconnects by itself
+    fn connection(&mut self) -> Result<&mut Box<dyn MqttConnection>, ClientError> {
+        if self.connection.is_none() {
+            info!("[mqtt]: connecting to {}...", self.options.broker_url);
+            self.connection = Some(self.connector.connect(&self.options)?);
+        }
+        Ok(self.connection.as_mut().unwrap())
+    }
+}
+
+impl MessageTransport for MqttMessageTransport {
+    // the HTTP session is not used, the server has no answers over MQTT
+    fn send(
+        &mut self,
+        _session: &mut HttpSession,
+        kind: MessageKind,
+        payload: &[u8],
+    ) -> Result<String, ClientError> {
+        let message = match kind {
+            MessageKind::Measurement => MqttMessage {
+                topic: self.measurement_topic.clone(),
+                payload: payload.to_vec(),
+                qos: self.qos,
+                retain: false,
+            },
+            MessageKind::Registration => MqttMessage {
+                topic: self.registration_topic.clone(),
+                payload: payload.to_vec(),
+                qos: self.qos,
+                retain: true,
+            },
+            MessageKind::Heartbeat => status_message(&self.status_topic, STATUS_ONLINE, self.qos),
+        };
+        let connection = self.connection()?;
+        connection.publish(&message)?;
+        // Home Assistant keeps the retained discovery, it is published again with the
+        // registration to follow a change of the sensors or of the unit
+        if kind == MessageKind::Registration {
+            for message in &self.discovery {
+                self.connection.as_mut().unwrap().publish(message)?;
+            }
+        }
+        Ok(String::new())
+    }
+}
+
+fn status_message(topic: &str, status: &str, qos: QoS) -> MqttMessage {
+    MqttMessage {
+        topic: topic.to_owned(),
+        payload: status.as_bytes().to_vec(),
+        qos,
+        retain: true,
+    }
+}
+
+#[cfg(all(test, not(feature = "hal")))]
+mod tests {
+    use super::*;
+    use crate::{
+        dto::temperature_unit::TemperatureUnit,
+        hal::{host::http::HostHttpTransport, http::HttpSettings},
+        service::credential_service::CredentialService,
+    };
+    use serde_json::Value;
+    use std::cell::RefCell;
+
+    const MAC_ADDRESS: &str = "AA:BB:CC:DD:EE:0F";
+
+    // keeps the published messages instead of sending them
+    #[derive(Default)]
+    struct RecordingConnector {
+        published: Rc<RefCell<Vec<MqttMessage>>>,
+    }
+
+    struct RecordingConnection {
+        published: Rc<RefCell<Vec<MqttMessage>>>,
+    }
+
+    impl MqttConnector for RecordingConnector {
+        fn connect(&self, _: &MqttOptions) -> Result<Box<dyn MqttConnection>, ClientError> {
+            Ok(Box::new(RecordingConnection {
+                published: self.published.clone(),
+            }))
+        }
+    }
+
+    impl MqttConnection for RecordingConnection {
+        fn publish(&mut self, message: &MqttMessage) -> Result<(), ClientError> {
+            self.published.borrow_mut().push(message.clone());
+            Ok(())
+        }
+    }
+
+    fn register(home_assistant_discovery: bool) -> Vec<MqttMessage> {
+        let connector = RecordingConnector::default();
+        let published = connector.published.clone();
+        let configuration = MqttConfiguration {
+            home_assistant_discovery,
+            ..MqttConfiguration::new("mqtt://broker:1883".to_owned())
+        };
+        let mut transport = MqttMessageTransport::new(
+            Rc::new(connector),
+            &configuration,
+            MAC_ADDRESS,
+            &DeviceDescription {
+                name: "Garden",
+                model: "ESP32 weather station",
+            },
+            &StationSensors {
+                temperature_unit: TemperatureUnit::Celsius,
+                pressure: false,
+            },
+        );
+        let mut session = HttpSession::new(
+            Box::new(HostHttpTransport::new(HttpSettings::default())),
+            CredentialService::new(None, 0),
+        );
+        transport
+            .send(&mut session, MessageKind::Registration, b"{}")
+            .unwrap();
+        let published = published.borrow().clone();
+        published
+    }
+
+    #[test]
+    fn discovery_follows_the_registration() {
+        let published = register(true);
+        let topics: Vec<&str> = published
+            .iter()
+            .map(|message| message.topic.as_str())
+            .collect();
+        assert_eq!(
+            topics,
+            [
+                "elisys/weather-station/AA:BB:CC:DD:EE:0F/registration",
+                "homeassistant/sensor/weather_station_aabbccddee0f/temperature/config",
+                "homeassistant/sensor/weather_station_aabbccddee0f/humidity/config",
+                "homeassistant/sensor/weather_station_aabbccddee0f/lux/config",
+                "homeassistant/binary_sensor/weather_station_aabbccddee0f/light/config",
+            ]
+        );
+        // Home Assistant must find the discovery after a restart
+        assert!(published.iter().all(|message| message.retain));
+    }
+
+    #[test]
+    fn discovery_uses_the_topics_of_the_configuration() {
+        let published = register(true);
+        let payload: Value = serde_json::from_slice(&published[1].payload).unwrap();
+        assert_eq!(
+            payload["state_topic"],
+            "elisys/weather-station/AA:BB:CC:DD:EE:0F/measurement"
+        );
+        assert_eq!(
+            payload["availability_topic"],
+            "elisys/weather-station/AA:BB:CC:DD:EE:0F/status"
+        );
+    }
+
+    #[test]
+    fn discovery_can_be_disabled() {
+        let published = register(false);
+        assert_eq!(published.len(), 1);
+        assert_eq!(
+            published[0].topic,
+            "elisys/weather-station/AA:BB:CC:DD:EE:0F/registration"
+        );
+    }
+}
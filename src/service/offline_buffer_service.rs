@@ -0,0 +1,272 @@
+use crate::{dto::measurement::Measurement, hal::storage::KeyValueStorage};
+use anyhow::Error;
+use log::{error, info, warn};
+
+// NVS namespace of the offline buffer
+pub const OFFLINE_BUFFER_NAMESPACE: &str = "offline_buffer";
+
+const KEY_META: &str = "meta";
+
+// bounded ring buffer of the measurements that could not be sent to the server.
+// Each measurement is stored in its own slot, the "meta" key holds the index of the
+// oldest slot and the number of stored measurements. When the buffer is full the
+// oldest measurement is overwritten.
+pub struct OfflineBufferService {
+    storage: Box<dyn KeyValueStorage>,
+    capacity: u32,
+    head: u32,
+    len: u32,
+}
+
+impl OfflineBufferService {
+    pub fn new(mut storage: Box<dyn KeyValueStorage>, capacity: u32) -> Self {
+        let (head, len) = match storage.read(KEY_META) {
+            Ok(Some(meta)) => decode_meta(&meta, capacity).unwrap_or_else(|| {
+                warn!("[offline buffer]: invalid metadata, the buffer will be reset");
+                (0, 0)
+            }),
+            Ok(None) => (0, 0),
+            Err(e) => {
+                error!("[offline buffer]: unable to read the metadata: {}", e);
+                (0, 0)
+            }
+        };
+        info!(
+            "[offline buffer]: {} measurement(s) waiting to be sent",
+            len
+        );
+        OfflineBufferService {
+            storage,
+            capacity,
+            head,
+            len,
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // appends a measurement, dropping the oldest one if the buffer is full
+    pub fn push(&mut self, measurement: &Measurement) -> anyhow::Result<()> {
+        if self.capacity == 0 {
+            return Err(Error::msg("the offline buffer is disabled"));
+        }
+        let value = serde_json::to_vec(measurement)?;
+        let (head, len) = if self.len == self.capacity {
+            warn!("[offline buffer]: buffer full, dropping the oldest measurement");
+            ((self.head + 1) % self.capacity, self.len)
+        } else {
+            (self.head, self.len + 1)
+        };
+        let slot = (self.head + self.len) % self.capacity;
+        self.storage.write(&slot_key(slot), &value)?;
+        self.write_meta(head, len)
+    }
+
+    // returns the oldest measurement, skipping the slots that cannot be decoded
+    pub fn peek(&mut self) -> anyhow::Result<Option<Measurement>> {
+        while !self.is_empty() {
+            let value = self.storage.read(&slot_key(self.head))?;
+            let measurement =
+                value.and_then(|value| serde_json::from_slice::<Measurement>(&value).ok());
+            if measurement.is_some() {
+                return Ok(measurement);
+            }
+            error!(
+                "[offline buffer]: slot {} is missing or corrupted, skipping it",
+                self.head
+            );
+            self.pop()?;
+        }
+        Ok(None)
+    }
+
+    // removes the oldest measurement
+    pub fn pop(&mut self) -> anyhow::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let slot = self.head;
+        self.write_meta((self.head + 1) % self.capacity, self.len - 1)?;
+        self.storage.remove(&slot_key(slot))
+    }
+
+    // drops all the measurements, returns how many were waiting
+    pub fn clear(&mut self) -> anyhow::Result<u32> {
+        let (head, len) = (self.head, self.len);
+        self.write_meta(0, 0)?;
+        for index in 0..len {
+            let slot = (head + index) % self.capacity;
+            if let Err(e) = self.storage.remove(&slot_key(slot)) {
+                warn!("[offline buffer]: unable to remove slot {}: {}", slot, e);
+            }
+        }
+        Ok(len)
+    }
+
+    fn write_meta(&mut self, head: u32, len: u32) -> anyhow::Result<()> {
+        let mut meta = head.to_le_bytes().to_vec();
+        meta.extend_from_slice(&len.to_le_bytes());
+        self.storage.write(KEY_META, &meta)?;
+        self.head = head;
+        self.len = len;
+        Ok(())
+    }
+}
+
+fn slot_key(slot: u32) -> String {
+    format!("m{}", slot)
+}
+
+fn decode_meta(meta: &[u8], capacity: u32) -> Option<(u32, u32)> {
+    if meta.len() != 8 {
+        return None;
+    }
+    let head = u32::from_le_bytes([meta[0], meta[1], meta[2], meta[3]]);
+    let len = u32::from_le_bytes([meta[4], meta[5], meta[6], meta[7]]);
+    if len > capacity || (capacity > 0 && head >= capacity) {
+        return None;
+    }
+    Some((head, len))
+}
+
+#[cfg(all(test, not(feature = "hal")))]
+mod tests {
+    use super::*;
+    use crate::{
+        dto::temperature_unit::TemperatureUnit,
+        hal::{host::storage::InMemoryStorageProvider, storage::StorageProvider},
+    };
+
+    fn measurement(captured_at_millis: i64) -> Measurement {
+        Measurement {
+            temperature: Some(21.5),
+            temperature_unit: TemperatureUnit::default(),
+            humidity: Some(40.0),
+            pressure: None,
+            lux: Some(100.0),
+            light: Some(true),
+            captured_at_millis,
+            clock_synchronized: true,
+        }
+    }
+
+    fn open(provider: &mut InMemoryStorageProvider, capacity: u32) -> OfflineBufferService {
+        OfflineBufferService::new(provider.open(OFFLINE_BUFFER_NAMESPACE).unwrap(), capacity)
+    }
+
+    // empties the buffer, returning the capture times in the order of the replay
+    fn drain(buffer: &mut OfflineBufferService) -> Vec<i64> {
+        let mut replayed = Vec::new();
+        while let Some(measurement) = buffer.peek().unwrap() {
+            replayed.push(measurement.captured_at_millis);
+            buffer.pop().unwrap();
+        }
+        replayed
+    }
+
+    #[test]
+    fn replays_in_fifo_order() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut buffer = open(&mut provider, 5);
+        assert!(buffer.is_empty());
+        for millis in 1..=3 {
+            buffer.push(&measurement(millis)).unwrap();
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(drain(&mut buffer), vec![1, 2, 3]);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.peek().unwrap(), None);
+    }
+
+    #[test]
+    fn wraps_around_the_slots() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut buffer = open(&mut provider, 3);
+        buffer.push(&measurement(1)).unwrap();
+        buffer.push(&measurement(2)).unwrap();
+        buffer.pop().unwrap();
+        buffer.pop().unwrap();
+        // the head is now on the last slot, the next ones go back to the first
+        for millis in 3..=5 {
+            buffer.push(&measurement(millis)).unwrap();
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(drain(&mut buffer), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn drops_the_oldest_when_full() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut buffer = open(&mut provider, 3);
+        for millis in 1..=5 {
+            buffer.push(&measurement(millis)).unwrap();
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(drain(&mut buffer), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn skips_a_corrupted_slot() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut buffer = open(&mut provider, 5);
+        for millis in 1..=3 {
+            buffer.push(&measurement(millis)).unwrap();
+        }
+        let mut storage = provider.open(OFFLINE_BUFFER_NAMESPACE).unwrap();
+        storage.write(&slot_key(0), b"not json").unwrap();
+        storage.remove(&slot_key(1)).unwrap();
+        assert_eq!(drain(&mut buffer), vec![3]);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn clear_drops_everything() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut buffer = open(&mut provider, 3);
+        for millis in 1..=4 {
+            buffer.push(&measurement(millis)).unwrap();
+        }
+        assert_eq!(buffer.clear().unwrap(), 3);
+        assert!(buffer.is_empty());
+        let mut storage = provider.open(OFFLINE_BUFFER_NAMESPACE).unwrap();
+        for slot in 0..3 {
+            assert_eq!(storage.read(&slot_key(slot)).unwrap(), None);
+        }
+        // still usable after the clear
+        buffer.push(&measurement(5)).unwrap();
+        assert_eq!(drain(&mut buffer), vec![5]);
+    }
+
+    #[test]
+    fn reloads_the_state_from_the_metadata() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut buffer = open(&mut provider, 3);
+        for millis in 1..=4 {
+            buffer.push(&measurement(millis)).unwrap();
+        }
+        buffer.pop().unwrap();
+
+        // a reboot: the buffer is opened again on the same storage
+        let mut reloaded = open(&mut provider, 3);
+        assert_eq!((reloaded.head, reloaded.len), (buffer.head, buffer.len));
+        reloaded.push(&measurement(5)).unwrap();
+        assert_eq!(drain(&mut reloaded), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn resets_invalid_metadata() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut storage = provider.open(OFFLINE_BUFFER_NAMESPACE).unwrap();
+        // head and len beyond the capacity
+        storage.write(KEY_META, &[9, 0, 0, 0, 9, 0, 0, 0]).unwrap();
+        let buffer = open(&mut provider, 3);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.head, 0);
+    }
+}
@@ -1,21 +1,112 @@
 use super::{
-    client_service::{self, get_configuration},
+    client_service::{ClientService, HttpSession},
+    configuration_cache_service::{
+        ConfigurationCacheService, FallbackConfiguration, CONFIGURATION_NAMESPACE,
+    },
+    configuration_validation_service::sanitize,
+    credential_service::{CredentialService, CREDENTIALS_NAMESPACE},
+    discovery_service::{DeviceDescription, StationSensors},
+    mqtt_service::MqttMessageTransport,
+    offline_buffer_service::{OfflineBufferService, OFFLINE_BUFFER_NAMESPACE},
     peripheral_service::PeripheralService,
+    retry_service::RetryPolicies,
+    schedule_service::{MeasurementSchedule, Task, TaskScheduler},
+    settings_service::{Settings, SettingsService},
+    transport_service::{HttpMessageTransport, MessageTransport},
 };
 use crate::{
-    config::config::{self, CONFIGURATION_URL},
-    dto::config_response::Configuration,
-    service::client_service::{get_default_configuration, register_device},
+    config::config::{self, OFFLINE_BUFFER_CAPACITY},
+    dto::{
+        config_response::{
+            Configuration, ConfigurationChanges, DownloadedConfiguration, TransportKind,
+            VersionedConfiguration,
+        },
+        measurement::Measurement,
+        remote_command::{CommandAcknowledgement, CommandKind, CommandStatus, RemoteCommand},
+        temperature_unit::TemperatureUnit,
+    },
+    error::ClientError,
+    hal::{
+        http::HttpTransport, mqtt::MqttConnector, ota::FirmwareUpdater, storage::StorageProvider,
+    },
+    service::client_service::default_configuration,
     util::thread_util,
 };
 use core::result::Result::Ok as StandardOk;
-use esp_idf_svc::sntp::{self, SyncStatus};
-use log::{error, info};
-pub fn orchestrate() {
-    let mut peripheral_service = PeripheralService::new(config::WIFI_SSID, config::WIFI_PASS);
+use log::{error, info, warn};
+use std::{collections::VecDeque, fmt, rc::Rc};
+
+// how many executed commands are remembered, so that a command sent again by the
+// server (e.g. its acknowledgement got lost) is not executed twice
+const RECENT_COMMANDS: usize = 16;
+
+pub fn orchestrate(
+    peripheral_service: PeripheralService,
+    transport: Box<dyn HttpTransport>,
+    mqtt_connector: Rc<dyn MqttConnector>,
+    firmware_updater: Box<dyn FirmwareUpdater>,
+    storage_provider: Box<dyn StorageProvider>,
+    settings: Settings,
+) {
+    orchestrate_cycles(
+        peripheral_service,
+        transport,
+        mqtt_connector,
+        firmware_updater,
+        storage_provider,
+        settings,
+        None,
+    );
+}
+
+// as orchestrate, but it returns after the given number of cycles (a cycle runs the due
+// tasks), forever if None; used on the host to run a station for a bounded time
+pub fn orchestrate_cycles(
+    mut peripheral_service: PeripheralService,
+    transport: Box<dyn HttpTransport>,
+    mqtt_connector: Rc<dyn MqttConnector>,
+    mut firmware_updater: Box<dyn FirmwareUpdater>,
+    mut storage_provider: Box<dyn StorageProvider>,
+    settings: Settings,
+    cycles: Option<u32>,
+) {
     let mac_address = peripheral_service.get_mac_address();
 
-    let register_device_result = register_device(&mac_address);
+    // the requests are signed with the time, the server rejects the ones too far from its own
+    let clock_synchronized = synchronize_clock(&mut peripheral_service);
+
+    let credentials = CredentialService::new(
+        match storage_provider.open(CREDENTIALS_NAMESPACE) {
+            Err(e) => {
+                error!("unable to open the credentials storage: {}", e);
+                None
+            }
+            StandardOk(storage) => Some(storage),
+        },
+        peripheral_service.random(),
+    );
+    let mut configuration_cache =
+        ConfigurationCacheService::new(match storage_provider.open(CONFIGURATION_NAMESPACE) {
+            Err(e) => {
+                error!("unable to open the configuration storage: {}", e);
+                None
+            }
+            StandardOk(storage) => Some(storage),
+        });
+
+    // until the configuration is downloaded the calls are retried as the cached one says
+    let fallback = configuration_cache.fallback(|| default_configuration(&settings));
+    let retry_policies = fallback.configuration().retry_policies;
+    let mut client_service = ClientService::new(
+        HttpSession::new(transport, credentials),
+        RetryPolicies::from(&retry_policies),
+        peripheral_service.random(),
+        settings,
+    );
+
+    // until the configuration is known the registration goes over HTTP, that issues
+    // the token of the device
+    let register_device_result = client_service.register_device(&mac_address);
     if register_device_result.is_err() {
         error!(
             "failed to register the device: {:?}",
@@ -25,99 +116,1008 @@ pub fn orchestrate() {
         info!("device registered with success!");
     }
 
-    let configuration: Result<Configuration, anyhow::Error> =
-        get_configuration(CONFIGURATION_URL, &mac_address);
+    let (configuration, source) = match client_service.get_configuration(&mac_address) {
+        StandardOk(downloaded) => {
+            // the fields that are not valid keep the cached or the compiled value
+            let (accepted, _) = accept_configuration(
+                &mut client_service,
+                &mut configuration_cache,
+                &mac_address,
+                downloaded,
+                fallback.configuration(),
+            );
+            (
+                accepted.configuration,
+                ConfigurationSource::Remote(accepted.version),
+            )
+        }
+        Err(e) => {
+            error!(
+                "Error while trying to load configuration from remote server: {:?}",
+                e
+            );
+            peripheral_service.led_blink_3_time_short();
+            // the last configuration received from the server is better than the
+            // compiled one
+            match fallback {
+                FallbackConfiguration::Cached(cached) => (
+                    cached.configuration,
+                    ConfigurationSource::Cached(cached.version),
+                ),
+                FallbackConfiguration::Defaults(configuration) => {
+                    if config::IS_REMOTE_CONFIGURATION_MANDATORY {
+                        error!("Could not download the remote configuration and none is cached. REMOTE CONFIGURATION DOWNLOAD IS MANDATORY. Terminating the application...");
+                        return;
+                    }
+                    (configuration, ConfigurationSource::Defaults)
+                }
+            }
+        }
+    };
+    info!("configuration source: {}", source);
+    // the firmware reached the server, so it can receive another update: a new firmware
+    // that does not get here is rolled back at the next restart
+    if let ConfigurationSource::Remote(_) = source {
+        if let Err(e) = firmware_updater.mark_running_valid() {
+            error!("unable to confirm the running firmware: {}", e);
+        }
+    }
 
-    let configuration = match configuration {
-        Err(e) => Some({
-            if config::IS_REMOTE_CONFIGURATION_MANDATORY {
-                error!("Could not download the remote configuration. REMOTE CONFIGURATION DOWNLOAD IS MANDATORY. Terminating the application...");
+    let offline_buffer = match storage_provider.open(OFFLINE_BUFFER_NAMESPACE) {
+        Err(e) => {
+            error!("unable to open the offline buffer storage: {}", e);
+            None
+        }
+        StandardOk(storage) => Some(OfflineBufferService::new(storage, OFFLINE_BUFFER_CAPACITY)),
+    };
+    let settings_service = SettingsService::open(storage_provider.as_mut());
+
+    let mut station = Station {
+        mac_address,
+        peripheral_service,
+        client_service,
+        mqtt_connector,
+        firmware_updater,
+        offline_buffer,
+        configuration_cache,
+        settings_service,
+        configuration: None,
+        temperature_unit: TemperatureUnit::default(),
+        scheduler: None,
+        clock_synchronized,
+        pending_acknowledgements: Vec::new(),
+        recent_commands: VecDeque::new(),
+    };
+    station.apply_configuration(configuration);
+
+    station.peripheral_service.led_blink_1_time_long();
+
+    station.run(cycles);
+}
+
+// where the active configuration comes from, with its version
+enum ConfigurationSource {
+    Remote(String),
+    Cached(String),
+    Defaults,
+}
+
+impl fmt::Display for ConfigurationSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigurationSource::Remote(version) => write!(f, "server (version {})", version),
+            ConfigurationSource::Cached(version) => {
+                write!(
+                    f,
+                    "cached in NVS (version {}), the server is not reachable",
+                    version
+                )
+            }
+            ConfigurationSource::Defaults => write!(f, "compiled defaults"),
+        }
+    }
+}
+
+// the running station; the configuration can be applied again while it runs
+struct Station {
+    mac_address: String,
+    peripheral_service: PeripheralService,
+    client_service: ClientService,
+    mqtt_connector: Rc<dyn MqttConnector>,
+    firmware_updater: Box<dyn FirmwareUpdater>,
+    offline_buffer: Option<OfflineBufferService>,
+    configuration_cache: ConfigurationCacheService,
+    // changed by the server, the settings in use are the ones read at startup
+    settings_service: SettingsService,
+    // the configuration in use, set at startup
+    configuration: Option<Configuration>,
+    temperature_unit: TemperatureUnit,
+    // created by the first configuration
+    scheduler: Option<TaskScheduler>,
+    clock_synchronized: bool,
+    // outcome of the executed commands, sent with the next heartbeat
+    pending_acknowledgements: Vec<CommandAcknowledgement>,
+    recent_commands: VecDeque<CommandAcknowledgement>,
+}
+
+impl Station {
+    // runs the due tasks and waits for the next ones, forever if cycles is None
+    fn run(&mut self, cycles: Option<u32>) {
+        let mut completed = 0;
+        loop {
+            while !self
+                .peripheral_service
+                .retry_wifi_connection_if_necessary_and_return_status()
+            {
+                self.peripheral_service.led_blink_3_time_long();
+                thread_util::sleep_short();
+            }
+            let scheduler = self.scheduler.as_mut().unwrap();
+            for task in scheduler.due_tasks(chrono::Utc::now()) {
+                match task {
+                    Task::Heartbeat => {
+                        info!("sending I AM ALIVE message...");
+                        self.send_i_am_alive();
+                    }
+                    Task::Measurement => self.submit_measurements(),
+                    Task::ConfigurationRefresh => {
+                        info!("refreshing the configuration...");
+                        if let Err(e) = self.refresh_configuration() {
+                            error!("the active configuration is kept: {}", e);
+                        }
+                    }
+                }
+            }
+            completed += 1;
+            if cycles.is_some_and(|cycles| completed >= cycles) {
+                info!("{} cycle(s) completed, the station stops", completed);
                 return;
             }
-            peripheral_service.led_blink_3_time_short();
-            get_default_configuration(e)
-        }),
-        StandardOk(config) => Some(config),
-    };
 
-    let configuration = configuration.unwrap();
-    info!("{}", format!("configuration: {:?}", &configuration));
-    let client_service = client_service::ClientService::new(
-        &configuration.alert_endpoint,
-        &configuration.i_am_alive_endpoint,
-    );
+            let delay = self
+                .scheduler
+                .as_ref()
+                .unwrap()
+                .delay_from(chrono::Utc::now());
+            info!("next task in {} seconds", delay.as_secs());
+            // rounded up, waking before the task is due would run an empty cycle
+            let partial_milli = delay.subsec_nanos() % 1_000_000 != 0;
+            thread_util::sleep_time(delay.as_millis() as u64 + u64::from(partial_milli));
+        }
+    }
 
-    peripheral_service.led_blink_1_time_long();
+    // applies the parts of the configuration (unit, schedules, transport) that differ
+    // from the active one
+    fn apply_configuration(&mut self, configuration: Configuration) {
+        let changes = match &self.configuration {
+            None => ConfigurationChanges {
+                schedule: true,
+                temperature_unit: true,
+                transport: true,
+                retry_policies: true,
+            },
+            Some(active) => configuration.changes_from(active),
+        };
+        if changes.is_empty() {
+            info!("configuration unchanged");
+            return;
+        }
+        info!("configuration: {:?}", configuration);
+        info!("changes: {:?}", changes);
 
-    synchronize_clock();
+        if changes.temperature_unit {
+            self.temperature_unit = configuration.temperature_unit();
+            info!("temperature unit of measure: {:?}", self.temperature_unit);
+        }
 
-    loop {
-        while !peripheral_service.retry_wifi_connection_if_necessary_and_return_status() {
-            peripheral_service.led_blink_3_time_long();
-            thread_util::sleep_short();
+        if changes.retry_policies {
+            self.client_service
+                .set_retry_policies(RetryPolicies::from(&configuration.retry_policies));
+            info!("retry policies: {:?}", configuration.retry_policies);
         }
-        info!("sending I AM ALIVE message...");
-        send_i_am_alive(&client_service, &mac_address, &mut peripheral_service);
 
-        info!("---<< Gathering information from sensors >>---");
-        let lux = peripheral_service.get_lux_measure() as f32;
+        if changes.schedule {
+            let measurement_schedule = MeasurementSchedule::new(
+                configuration.crontab.as_deref(),
+                configuration.weather_sensor_supply_interval_seconds,
+            );
+            let now = chrono::Utc::now();
+            let refresh_interval_changed = match &self.configuration {
+                None => true,
+                Some(active) => {
+                    active.configuration_refresh_interval_seconds
+                        != configuration.configuration_refresh_interval_seconds
+                }
+            };
+            let scheduler = match self.scheduler.as_mut() {
+                None => self.scheduler.insert(TaskScheduler::new(
+                    measurement_schedule,
+                    configuration.i_am_alive_interval_seconds,
+                    now,
+                )),
+                Some(scheduler) => {
+                    scheduler.reconfigure(
+                        measurement_schedule,
+                        configuration.i_am_alive_interval_seconds,
+                        now,
+                    );
+                    scheduler
+                }
+            };
+            if refresh_interval_changed {
+                scheduler.set_refresh_interval(
+                    configuration.configuration_refresh_interval_seconds,
+                    now,
+                );
+            }
+        }
 
-        let (temperature, humidity) =
-            match peripheral_service.get_temperature_and_humidity_insistently(100) {
-                Err(e) => {
-                    error!("e: {:?}", e);
-                    (None, None)
+        let transport_kind = configuration.transport_kind();
+        // the Home Assistant discovery, published with the registration, has the unit
+        if changes.transport || (changes.temperature_unit && transport_kind == TransportKind::Mqtt)
+        {
+            info!("transport: {:?}", transport_kind);
+            let message_transport: Box<dyn MessageTransport> = match &configuration.mqtt {
+                Some(mqtt) if transport_kind == TransportKind::Mqtt => {
+                    let sensors = StationSensors {
+                        temperature_unit: self.temperature_unit,
+                        pressure: self.peripheral_service.has_pressure_sensor(),
+                    };
+                    let settings = self.client_service.settings();
+                    let device = DeviceDescription {
+                        name: &settings.device_name,
+                        model: &settings.device_description,
+                    };
+                    Box::new(MqttMessageTransport::new(
+                        self.mqtt_connector.clone(),
+                        mqtt,
+                        &self.mac_address,
+                        &device,
+                        &sensors,
+                    ))
                 }
-                Ok(data) => (Some(data.0), Some(data.1)),
+                _ => Box::new(HttpMessageTransport::new(
+                    &self.client_service.settings().register_device_url,
+                    &configuration.alert_endpoint,
+                    &configuration.i_am_alive_endpoint,
+                )),
             };
-        let pressure = None;
+            self.client_service.set_transport(message_transport);
+            // the subscribers of the broker (and Home Assistant) learn about the device
+            // from its registration
+            if transport_kind == TransportKind::Mqtt {
+                if let Err(e) = self.client_service.register_device(&self.mac_address) {
+                    error!("failed to publish the registration of the device: {}", e);
+                }
+            }
+        }
+        self.configuration = Some(configuration);
+    }
+
+    // downloads the configuration and applies it, the fields that are not valid keep
+    // their active value; returns the number of rejected fields
+    fn refresh_configuration(&mut self) -> Result<usize, String> {
+        let downloaded = self
+            .client_service
+            .get_configuration(&self.mac_address)
+            .map_err(|e| format!("failed to download the configuration: {}", e))?;
+        let active = self
+            .configuration
+            .clone()
+            .unwrap_or_else(|| default_configuration(self.client_service.settings()));
+        let (accepted, errors) = accept_configuration(
+            &mut self.client_service,
+            &mut self.configuration_cache,
+            &self.mac_address,
+            downloaded,
+            &active,
+        );
         info!(
-            "lux: {:?}, temperature: {:?}, humidity: {:?}, pressure: {:?}",
-            lux, temperature, humidity, pressure
+            "configuration source: {}",
+            ConfigurationSource::Remote(accepted.version)
         );
-        info!("submiting data...");
-        if client_service
-            .send_alert(
-                &mac_address,
-                temperature,
-                humidity,
-                pressure,
-                Some(lux),
-                Some(lux > 3.0),
+        self.apply_configuration(accepted.configuration);
+        Ok(errors)
+    }
+
+    fn submit_measurements(&mut self) {
+        submit_measurements(
+            &mut self.client_service,
+            self.offline_buffer.as_mut(),
+            &self.mac_address,
+            &mut self.peripheral_service,
+            self.temperature_unit,
+            self.clock_synchronized,
+        );
+    }
+
+    // sends the heartbeat with the pending acknowledgements, then executes the
+    // commands of the server
+    fn send_i_am_alive(&mut self) {
+        let commands = match send_i_am_alive(
+            &mut self.client_service,
+            &self.mac_address,
+            &mut self.peripheral_service,
+            &self.pending_acknowledgements,
+        ) {
+            None => return,
+            Some(commands) => commands,
+        };
+        self.pending_acknowledgements.clear();
+
+        // the reboot waits for the other commands of the same heartbeat
+        let mut reboot = false;
+        for command in commands {
+            if let Some(acknowledgement) = self
+                .recent_commands
+                .iter()
+                .find(|acknowledgement| acknowledgement.id == command.id)
+            {
+                info!(
+                    "command {} already executed, acknowledging it again",
+                    command.id
+                );
+                self.pending_acknowledgements.push(acknowledgement.clone());
+                continue;
+            }
+            info!(
+                "executing command {} ({})",
+                command.id, command.command_type
+            );
+            let (status, message) = match command.kind() {
+                Some(CommandKind::Reboot) => {
+                    reboot = true;
+                    (CommandStatus::Done, None)
+                }
+                Some(kind) => {
+                    let (status, message) = self.execute(kind, &command);
+                    // the new firmware runs from the next restart
+                    if kind == CommandKind::StartOta && status == CommandStatus::Done {
+                        reboot = true;
+                    }
+                    (status, message)
+                }
+                None => {
+                    warn!("unknown command type {:?}", command.command_type);
+                    (
+                        CommandStatus::Unsupported,
+                        Some(format!("unknown command type {}", command.command_type)),
+                    )
+                }
+            };
+            let acknowledgement = CommandAcknowledgement {
+                id: command.id,
+                status,
+                message,
+            };
+            if self.recent_commands.len() == RECENT_COMMANDS {
+                self.recent_commands.pop_front();
+            }
+            self.recent_commands.push_back(acknowledgement.clone());
+            self.pending_acknowledgements.push(acknowledgement);
+        }
+
+        if reboot {
+            // the acknowledgements would be lost with the restart
+            info!("rebooting...");
+            if send_i_am_alive(
+                &mut self.client_service,
+                &self.mac_address,
+                &mut self.peripheral_service,
+                &self.pending_acknowledgements,
             )
-            .is_err()
-        {
-            error!("cannot send data to server");
-            peripheral_service.led_blink_2_time_long();
-        } else {
-            info!("data sent to server successfully!");
-            peripheral_service.led_blink_1_time_short();
+            .is_some()
+            {
+                self.pending_acknowledgements.clear();
+            }
+            self.peripheral_service.restart();
         }
+    }
 
-        thread_util::sleep_time(configuration.weather_sensor_supply_interval_seconds * 1000);
+    // executes a command, except the reboot
+    fn execute(
+        &mut self,
+        kind: CommandKind,
+        command: &RemoteCommand,
+    ) -> (CommandStatus, Option<String>) {
+        match kind {
+            CommandKind::Reboot => (CommandStatus::Done, None),
+            CommandKind::RefreshConfiguration => match self.refresh_configuration() {
+                Err(e) => {
+                    error!("the active configuration is kept: {}", e);
+                    (CommandStatus::Failed, Some(e))
+                }
+                StandardOk(0) => (CommandStatus::Done, None),
+                StandardOk(errors) => (
+                    CommandStatus::Done,
+                    Some(format!(
+                        "{} field(s) not valid, their value is kept",
+                        errors
+                    )),
+                ),
+            },
+            CommandKind::TakeReading => {
+                // a failed measurement waits in the offline buffer, as the scheduled ones
+                self.submit_measurements();
+                (CommandStatus::Done, None)
+            }
+            CommandKind::Identify => {
+                self.peripheral_service.identify();
+                (CommandStatus::Done, None)
+            }
+            CommandKind::ClearOfflineBuffer => match self.offline_buffer.as_mut() {
+                None => (
+                    CommandStatus::Failed,
+                    Some("the offline buffer is not available".to_owned()),
+                ),
+                Some(offline_buffer) => match offline_buffer.clear() {
+                    Err(e) => {
+                        error!("unable to clear the offline buffer: {}", e);
+                        (CommandStatus::Failed, Some(e.to_string()))
+                    }
+                    StandardOk(dropped) => {
+                        info!("offline buffer cleared, {} measurement(s) dropped", dropped);
+                        (CommandStatus::Done, None)
+                    }
+                },
+            },
+            CommandKind::UpdateSettings => {
+                let result = match &command.settings {
+                    None => Err("the command has no settings".to_owned()),
+                    Some(values) => self.settings_service.update(values),
+                };
+                match result {
+                    Err(e) => {
+                        error!("the settings are not updated: {}", e);
+                        (CommandStatus::Failed, Some(e))
+                    }
+                    StandardOk(updated) => {
+                        info!(
+                            "settings updated, in use from the next restart: {}",
+                            updated.join(", ")
+                        );
+                        (
+                            CommandStatus::Done,
+                            Some(format!(
+                                "{} setting(s) stored, in use from the next restart",
+                                updated.len()
+                            )),
+                        )
+                    }
+                }
+            }
+            CommandKind::StartOta => {
+                let url = match &command.url {
+                    None => {
+                        return (
+                            CommandStatus::Failed,
+                            Some("the command has no url".to_owned()),
+                        )
+                    }
+                    Some(url) => url,
+                };
+                info!("updating the firmware from {}...", url);
+                match self
+                    .client_service
+                    .download_firmware(self.firmware_updater.as_mut(), url)
+                {
+                    Err(e) => {
+                        error!("the firmware is not updated: {}", e);
+                        (CommandStatus::Failed, Some(e.to_string()))
+                    }
+                    StandardOk(()) => (
+                        CommandStatus::Done,
+                        Some("firmware installed, restarting".to_owned()),
+                    ),
+                }
+            }
+        }
     }
 }
 
-fn send_i_am_alive(
-    client_service: &client_service::ClientService,
-    mac_address: &String,
+pub fn submit_measurements(
+    client_service: &mut ClientService,
+    offline_buffer: Option<&mut OfflineBufferService>,
+    mac_address: &str,
     peripheral_service: &mut PeripheralService,
+    temperature_unit: TemperatureUnit,
+    clock_synchronized: bool,
 ) {
-    if client_service.send_i_am_alive(mac_address).is_err() {
-        log::error!("failed to send is alive ack");
-        peripheral_service.led_blink_2_time_short();
+    info!("---<< Gathering information from sensors >>---");
+    let lux = match peripheral_service.get_lux_measure() {
+        Err(e) => {
+            error!("e: {:?}", e);
+            None
+        }
+        Ok(lux) => Some(lux),
+    };
+
+    let (temperature, humidity) =
+        match peripheral_service.get_temperature_and_humidity_insistently(100) {
+            Err(e) => {
+                error!("e: {:?}", e);
+                (None, None)
+            }
+            Ok(data) => (Some(temperature_unit.convert_celsius(data.0)), Some(data.1)),
+        };
+    let pressure = match peripheral_service.get_pressure_measure() {
+        Some(Err(e)) => {
+            error!("e: {:?}", e);
+            None
+        }
+        Some(Ok(pressure)) => Some(pressure),
+        None => None,
+    };
+    info!(
+        "lux: {:?}, temperature: {:?}, humidity: {:?}, pressure: {:?}",
+        lux, temperature, humidity, pressure
+    );
+    let measurement = Measurement {
+        temperature,
+        temperature_unit,
+        humidity,
+        pressure,
+        lux,
+        light: lux.map(|lux| lux > 3.0),
+        captured_at_millis: chrono::Utc::now().timestamp_millis(),
+        clock_synchronized,
+    };
+
+    let offline_buffer = match offline_buffer {
+        None => {
+            // without the offline buffer a failed measurement is lost, the error is logged
+            let _ = submit_measurement(
+                client_service,
+                mac_address,
+                peripheral_service,
+                &measurement,
+            );
+            return;
+        }
+        Some(offline_buffer) => offline_buffer,
+    };
+
+    // older measurements are sent first, so that the server receives them in order
+    if replay_offline_measurements(client_service, offline_buffer, mac_address) {
+        match submit_measurement(
+            client_service,
+            mac_address,
+            peripheral_service,
+            &measurement,
+        ) {
+            StandardOk(_) => return,
+            Err(e) if !e.is_transient() && !e.needs_registration() => {
+                error!("the server rejected the measurement, it will not be sent again");
+                return;
+            }
+            Err(_) => {}
+        }
+    }
+    match offline_buffer.push(&measurement) {
+        Err(e) => error!(
+            "unable to store the measurement in the offline buffer: {}",
+            e
+        ),
+        StandardOk(_) => info!(
+            "measurement stored in the offline buffer ({} waiting)",
+            offline_buffer.len()
+        ),
+    }
+}
+
+fn submit_measurement(
+    client_service: &mut ClientService,
+    mac_address: &str,
+    peripheral_service: &mut PeripheralService,
+    measurement: &Measurement,
+) -> Result<(), ClientError> {
+    info!("submiting data...");
+    if let Err(e) = client_service.send_alert(mac_address, measurement) {
+        error!("cannot send data to server: {}", e);
+        peripheral_service.led_blink_2_time_long();
+        register_again_if_needed(client_service, mac_address, &e);
+        return Err(e);
     }
+    info!("data sent to server successfully!");
+    peripheral_service.led_blink_1_time_short();
+    Ok(())
 }
 
-fn synchronize_clock() {
-    let sntp = sntp::EspSntp::new_default();
-    if sntp.is_err() {
-        error!("unable to set system time");
+// sends the buffered measurements, returns true if the buffer has been emptied
+fn replay_offline_measurements(
+    client_service: &mut ClientService,
+    offline_buffer: &mut OfflineBufferService,
+    mac_address: &str,
+) -> bool {
+    if offline_buffer.is_empty() {
+        return true;
+    }
+    info!(
+        "replaying {} measurement(s) from the offline buffer...",
+        offline_buffer.len()
+    );
+    loop {
+        let measurement = match offline_buffer.peek() {
+            Err(e) => {
+                error!("unable to read the offline buffer: {}", e);
+                return false;
+            }
+            StandardOk(None) => return true,
+            StandardOk(Some(measurement)) => measurement,
+        };
+        if let Err(e) = client_service.send_alert(mac_address, &measurement) {
+            if e.is_transient() || e.needs_registration() {
+                error!(
+                    "cannot replay the offline measurements ({}), {} still waiting",
+                    e,
+                    offline_buffer.len()
+                );
+                register_again_if_needed(client_service, mac_address, &e);
+                return false;
+            }
+            // sending it again would fail again: the measurement is dropped
+            error!("the server rejected an offline measurement: {}", e);
+        }
+        if let Err(e) = offline_buffer.pop() {
+            error!(
+                "unable to remove the measurement from the offline buffer: {}",
+                e
+            );
+            return false;
+        }
+    }
+}
+
+// the server lost the registration of the device (e.g. after a database reset) or
+// does not accept its token any more
+fn register_again_if_needed(
+    client_service: &mut ClientService,
+    mac_address: &str,
+    error: &ClientError,
+) {
+    if !error.needs_registration() {
         return;
     }
-    let sntp = sntp.unwrap();
-    info!("SNTP initialized, waiting for status!");
-    while sntp.get_sync_status() != SyncStatus::Completed {
-        thread_util::sleep_short();
+    info!("the server does not know the device or its token, registering it again...");
+    match client_service.register_device(mac_address) {
+        Err(e) => error!("failed to register the device: {}", e),
+        StandardOk(_) => info!("device registered with success!"),
+    }
+}
+
+// returns the commands of the server, None if the heartbeat could not be sent
+fn send_i_am_alive(
+    client_service: &mut ClientService,
+    mac_address: &str,
+    peripheral_service: &mut PeripheralService,
+    acknowledgements: &[CommandAcknowledgement],
+) -> Option<Vec<RemoteCommand>> {
+    match client_service.send_i_am_alive(mac_address, acknowledgements) {
+        Err(e) => {
+            log::error!("failed to send is alive ack: {}", e);
+            peripheral_service.led_blink_2_time_short();
+            register_again_if_needed(client_service, mac_address, &e);
+            None
+        }
+        StandardOk(commands) => Some(commands),
+    }
+}
+
+// replaces the fields that are not valid with the ones of the fallback, reports them
+// to the server and caches the result; returns also the number of rejected fields
+fn accept_configuration(
+    client_service: &mut ClientService,
+    configuration_cache: &mut ConfigurationCacheService,
+    mac_address: &str,
+    downloaded: DownloadedConfiguration,
+    fallback: &Configuration,
+) -> (VersionedConfiguration, usize) {
+    let (configuration, errors) = sanitize(&downloaded.configuration, fallback, downloaded.errors);
+    if !errors.is_empty() {
+        for error in &errors {
+            error!(
+                "configuration field {} not valid, its value is kept: {}",
+                error.field, error.message
+            );
+        }
+        if let Err(e) =
+            client_service.report_configuration_errors(mac_address, &downloaded.version, &errors)
+        {
+            error!("failed to report the configuration errors: {}", e);
+        }
+    }
+    let accepted = VersionedConfiguration {
+        configuration,
+        version: downloaded.version,
+    };
+    configuration_cache.store(&accepted);
+    (accepted, errors.len())
+}
+
+// returns false if the clock could not be synchronized
+fn synchronize_clock(peripheral_service: &mut PeripheralService) -> bool {
+    if let Err(e) = peripheral_service.synchronize_clock() {
+        error!("{}", e);
+        error!("the measurements will be sent with an unsynchronized capture time");
+        return false;
+    }
+    true
+}
+
+#[cfg(all(test, not(feature = "hal")))]
+mod tests {
+    use super::*;
+    use crate::{
+        dto::{mqtt_configuration::MqttConfiguration, retry_configuration::RetryConfiguration},
+        hal::{
+            host::{
+                board::{build_peripheral_service, DEFAULT_MAC_ADDRESS},
+                ota::HostFirmwareUpdater,
+            },
+            http::HttpResponse,
+            mqtt::{MqttConnection, MqttMessage, MqttOptions},
+        },
+    };
+    use serde_json::Value;
+    use std::cell::RefCell;
+
+    const MAC_ADDRESS: &str = "02:00:00:00:00:01";
+
+    // answers every request with an empty success, keeping the URL and the body
+    struct RecordingTransport {
+        posted: Rc<RefCell<Vec<(String, Value)>>>,
+    }
+
+    impl HttpTransport for RecordingTransport {
+        fn post(
+            &mut self,
+            url: &str,
+            _: &[(&str, &str)],
+            payload: &[u8],
+        ) -> Result<HttpResponse, ClientError> {
+            let body = serde_json::from_slice(payload).unwrap();
+            self.posted.borrow_mut().push((url.to_owned(), body));
+            StandardOk(HttpResponse {
+                status: 200,
+                body: b"{}".to_vec(),
+                etag: None,
+            })
+        }
+    }
+
+    // counts the connections to the broker and keeps the published messages
+    #[derive(Default)]
+    struct RecordingConnector {
+        connections: Rc<RefCell<Vec<String>>>,
+        published: Rc<RefCell<Vec<MqttMessage>>>,
+    }
+
+    struct RecordingConnection {
+        published: Rc<RefCell<Vec<MqttMessage>>>,
+    }
+
+    impl MqttConnector for RecordingConnector {
+        fn connect(&self, options: &MqttOptions) -> Result<Box<dyn MqttConnection>, ClientError> {
+            self.connections
+                .borrow_mut()
+                .push(options.broker_url.clone());
+            StandardOk(Box::new(RecordingConnection {
+                published: self.published.clone(),
+            }))
+        }
+    }
+
+    impl MqttConnection for RecordingConnection {
+        fn publish(&mut self, message: &MqttMessage) -> Result<(), ClientError> {
+            self.published.borrow_mut().push(message.clone());
+            StandardOk(())
+        }
+    }
+
+    struct TestStation {
+        station: Station,
+        posted: Rc<RefCell<Vec<(String, Value)>>>,
+        connector: Rc<RecordingConnector>,
+    }
+
+    impl TestStation {
+        fn new() -> TestStation {
+            let posted = Rc::new(RefCell::new(Vec::new()));
+            let connector = Rc::new(RecordingConnector::default());
+            let session = HttpSession::new(
+                Box::new(RecordingTransport {
+                    posted: posted.clone(),
+                }),
+                CredentialService::new(None, 0),
+            );
+            let station = Station {
+                mac_address: MAC_ADDRESS.to_owned(),
+                peripheral_service: build_peripheral_service(DEFAULT_MAC_ADDRESS),
+                client_service: ClientService::new(
+                    session,
+                    RetryPolicies::from(&RetryConfiguration::default()),
+                    0,
+                    Settings::default(),
+                ),
+                mqtt_connector: connector.clone(),
+                firmware_updater: Box::new(HostFirmwareUpdater),
+                offline_buffer: None,
+                configuration_cache: ConfigurationCacheService::new(None),
+                settings_service: SettingsService::new(None),
+                configuration: None,
+                temperature_unit: TemperatureUnit::default(),
+                scheduler: None,
+                clock_synchronized: true,
+                pending_acknowledgements: Vec::new(),
+                recent_commands: VecDeque::new(),
+            };
+            TestStation {
+                station,
+                posted,
+                connector,
+            }
+        }
+
+        // applies the first configuration, the tasks due at once are taken as the first
+        // cycle of the station would
+        fn start(configuration: Configuration) -> TestStation {
+            let mut test = TestStation::new();
+            test.station.apply_configuration(configuration);
+            test.scheduler().due_tasks(chrono::Utc::now());
+            test
+        }
+
+        fn scheduler(&mut self) -> &mut TaskScheduler {
+            self.station.scheduler.as_mut().unwrap()
+        }
+
+        fn delay(&mut self) -> std::time::Duration {
+            self.scheduler().delay_from(chrono::Utc::now())
+        }
+
+        // the measurement posted by a reading
+        fn submit(&mut self) -> (String, Value) {
+            self.posted.borrow_mut().clear();
+            self.station.submit_measurements();
+            let posted = self.posted.borrow();
+            assert_eq!(posted.len(), 1, "{:?}", posted);
+            posted[0].clone()
+        }
+    }
+
+    fn configuration() -> Configuration {
+        Configuration {
+            weather_sensor_supply_interval_seconds: 3600,
+            i_am_alive_interval_seconds: 3600,
+            configuration_refresh_interval_seconds: 0,
+            ..default_configuration(&Settings::default())
+        }
+    }
+
+    fn mqtt_configuration() -> Configuration {
+        Configuration {
+            transport: Some("mqtt".to_owned()),
+            mqtt: Some(MqttConfiguration::new("mqtt://broker:1883".to_owned())),
+            ..configuration()
+        }
+    }
+
+    #[test]
+    fn changed_interval_rearms_the_scheduler() {
+        let mut test = TestStation::start(mqtt_configuration());
+        assert!(test.delay().as_secs() > 3500);
+        assert_eq!(test.connector.connections.borrow().len(), 1);
+
+        test.station.apply_configuration(Configuration {
+            weather_sensor_supply_interval_seconds: 60,
+            ..mqtt_configuration()
+        });
+        let delay = test.delay();
+        assert!(delay.as_secs() <= 60 && delay.as_secs() > 50, "{:?}", delay);
+        // the transport is kept
+        assert_eq!(test.connector.connections.borrow().len(), 1);
+        assert_eq!(test.station.temperature_unit, TemperatureUnit::Celsius);
+    }
+
+    #[test]
+    fn changed_refresh_interval_schedules_the_refresh() {
+        let mut test = TestStation::start(configuration());
+        test.station.apply_configuration(Configuration {
+            configuration_refresh_interval_seconds: 120,
+            ..configuration()
+        });
+        let delay = test.delay();
+        assert!(
+            delay.as_secs() <= 120 && delay.as_secs() > 110,
+            "{:?}",
+            delay
+        );
+    }
+
+    #[test]
+    fn changed_unit_converts_the_next_measurements() {
+        let mut test = TestStation::start(configuration());
+        let (_, body) = test.submit();
+        assert_eq!(body["temperatureUnitOfMeasure"], "C");
+        assert_eq!(body["temperature"].as_f64().unwrap() as f32, 21.0);
+
+        test.station.apply_configuration(Configuration {
+            temperature_sensor_unit_of_measure: "F".to_owned(),
+            ..configuration()
+        });
+        assert_eq!(test.station.temperature_unit, TemperatureUnit::Fahrenheit);
+        let (_, body) = test.submit();
+        assert_eq!(body["temperatureUnitOfMeasure"], "F");
+        assert_eq!(body["temperature"].as_f64().unwrap() as f32, 69.8);
+    }
+
+    #[test]
+    fn changed_unit_publishes_the_discovery_again_over_mqtt() {
+        let mut test = TestStation::start(mqtt_configuration());
+        test.connector.published.borrow_mut().clear();
+
+        test.station.apply_configuration(Configuration {
+            temperature_sensor_unit_of_measure: "F".to_owned(),
+            ..mqtt_configuration()
+        });
+        // a new transport, with the discovery of the new unit
+        assert_eq!(test.connector.connections.borrow().len(), 2);
+        let published = test.connector.published.borrow();
+        let temperature = published
+            .iter()
+            .find(|message| message.topic.ends_with("/temperature/config"))
+            .unwrap();
+        let payload: Value = serde_json::from_slice(&temperature.payload).unwrap();
+        assert_eq!(payload["unit_of_measurement"], "°F");
+    }
+
+    #[test]
+    fn changed_transport_sends_the_measurements_over_mqtt() {
+        let mut test = TestStation::start(configuration());
+        let (url, _) = test.submit();
+        assert_eq!(url, configuration().alert_endpoint);
+        assert!(test.connector.connections.borrow().is_empty());
+
+        test.station.apply_configuration(mqtt_configuration());
+        // the registration is published at the connection
+        assert_eq!(*test.connector.connections.borrow(), ["mqtt://broker:1883"]);
+        let registration = "elisys/weather-station/02:00:00:00:00:01/registration";
+        assert_eq!(test.connector.published.borrow()[0].topic, registration);
+
+        test.posted.borrow_mut().clear();
+        test.connector.published.borrow_mut().clear();
+        test.station.submit_measurements();
+        assert!(test.posted.borrow().is_empty());
+        let published = test.connector.published.borrow();
+        assert_eq!(published.len(), 1);
+        assert_eq!(
+            published[0].topic,
+            "elisys/weather-station/02:00:00:00:00:01/measurement"
+        );
+    }
+
+    #[test]
+    fn changed_endpoint_is_used_by_the_next_measurement() {
+        let mut test = TestStation::start(configuration());
+        let alert_endpoint = "https://other:8443/api/v1/weather-sensor/submit";
+        test.station.apply_configuration(Configuration {
+            alert_endpoint: alert_endpoint.to_owned(),
+            ..configuration()
+        });
+        let (url, _) = test.submit();
+        assert_eq!(url, alert_endpoint);
+    }
+
+    #[test]
+    fn unchanged_configuration_is_not_applied_again() {
+        let mut test = TestStation::start(mqtt_configuration());
+        let delay = test.delay();
+        let published = test.connector.published.borrow().len();
+
+        test.station.apply_configuration(mqtt_configuration());
+        assert_eq!(test.connector.connections.borrow().len(), 1);
+        assert_eq!(test.connector.published.borrow().len(), published);
+        assert!(test.delay() <= delay);
+        assert!(test.delay().as_secs() > 3500);
     }
 }
@@ -1,108 +1,74 @@
-use ::dht11::Dht11;
-use bh1750_ehal::BH1750;
-use dht11::Error as dhterr;
-use embedded_svc::wifi::{AuthMethod, ClientConfiguration, Configuration};
-use esp_idf_hal::delay::Delay;
-use esp_idf_hal::{
-    delay::{self, Ets},
-    gpio::{Gpio15, Gpio5, InputOutput, Output, PinDriver},
-    i2c::{I2cConfig, I2cDriver},
-    peripherals::Peripherals,
+use crate::{
+    error::PeripheralError,
+    hal::{
+        led::StatusLed,
+        network::NetworkLink,
+        sensor::{LightSensor, PressureSensor, TemperatureHumiditySensor},
+        system::SystemControl,
+    },
+    util::thread_util,
 };
-use esp_idf_svc::{
-    eventloop::EspSystemEventLoop,
-    nvs::EspDefaultNvsPartition,
-    wifi::{BlockingWifi, EspWifi, WifiDeviceId},
-};
-use esp_idf_sys::EspError;
-use log::{info, warn};
-
-use crate::util::thread_util;
+use log::info;
 
 const TIME_SHORT: u64 = 20;
 const TIME_LONG: u64 = 1000;
+// blinks of the LED that identify the device
+const IDENTIFY_BLINKS: u32 = 10;
+const IDENTIFY_BLINK_MILLIS: u64 = 250;
 
 pub struct PeripheralService {
-    led: PinDriver<'static, Gpio5, Output>,
-    bh1750: BH1750<I2cDriver<'static>, Ets>,
-    temperature_and_humidity_sensor: Dht11<PinDriver<'static, Gpio15, InputOutput>>,
-    wifi: BlockingWifi<EspWifi<'static>>,
-    wifi_ssid: String,
-    wifi_password: String,
+    led: Box<dyn StatusLed>,
+    light_sensor: Box<dyn LightSensor>,
+    temperature_and_humidity_sensor: Box<dyn TemperatureHumiditySensor>,
+    pressure_sensor: Option<Box<dyn PressureSensor>>,
+    network: Box<dyn NetworkLink>,
+    system: Box<dyn SystemControl>,
 }
 
 impl PeripheralService {
-    pub fn new(wifi_ssid: &str, wifi_password: &str) -> Self {
-        let peripherals = Peripherals::take().unwrap();
-        let led = PinDriver::output(peripherals.pins.gpio5).unwrap();
-
-        let sys_loop = EspSystemEventLoop::take().unwrap();
-        let nvs = EspDefaultNvsPartition::take().unwrap();
-
-        let mut wifi = BlockingWifi::wrap(
-            EspWifi::new(peripherals.modem, sys_loop.clone(), Some(nvs)).unwrap(),
-            sys_loop,
-        )
-        .unwrap();
-        let mut wifi_connection = connect_wifi(&mut wifi, wifi_ssid, wifi_password);
-        while wifi_connection.is_err() {
-            thread_util::sleep_time(TIME_LONG);
-            wifi_connection = connect_wifi(&mut wifi, wifi_ssid, wifi_password);
-        }
-
-        info!("configuring light sensor...");
-        let sda = peripherals.pins.gpio21;
-        let scl = peripherals.pins.gpio22;
-        let config = I2cConfig::new().baudrate(400000.into());
-        let i2c_instance = I2cDriver::new(peripherals.i2c0, sda, scl, &config).unwrap();
-        let bh1750 =
-            bh1750_ehal::BH1750::new(i2c_instance, delay::Ets, bh1750_ehal::Address::ADDR_L)
-                .unwrap();
-        info!("configuration of light sensor completed");
-
-        let pin = PinDriver::input_output_od(peripherals.pins.gpio15);
-
-        let mut temperature_and_humidity_sensor = Dht11::new(pin.unwrap());
-        warn!(
-            "{:?}",
-            temperature_and_humidity_sensor.perform_measurement(&mut Delay::new(80))
-        );
-
-        let peripheral_service = PeripheralService {
+    pub fn new(
+        led: Box<dyn StatusLed>,
+        light_sensor: Box<dyn LightSensor>,
+        temperature_and_humidity_sensor: Box<dyn TemperatureHumiditySensor>,
+        pressure_sensor: Option<Box<dyn PressureSensor>>,
+        network: Box<dyn NetworkLink>,
+        system: Box<dyn SystemControl>,
+    ) -> Self {
+        let mut peripheral_service = PeripheralService {
             led,
+            light_sensor,
             temperature_and_humidity_sensor,
-            wifi,
-            wifi_ssid: wifi_ssid.to_owned(),
-            wifi_password: wifi_password.to_owned(),
-            bh1750,
+            pressure_sensor,
+            network,
+            system,
         };
-        return peripheral_service;
+        while peripheral_service.network.connect().is_err() {
+            thread_util::sleep_time(TIME_LONG);
+        }
+        peripheral_service
     }
 
     pub fn retry_wifi_connection_if_necessary_and_return_status(&mut self) -> bool {
-        if !self.wifi.is_connected().unwrap() {
-            if connect_wifi(&mut self.wifi, &self.wifi_ssid, &self.wifi_password).is_err() {
-                self.led_blink_3_time_long();
-                return false;
-            }
+        if !self.network.is_connected().unwrap_or(false) && self.network.connect().is_err() {
+            self.led_blink_3_time_long();
+            return false;
         }
-        return true;
+        true
     }
 
-    pub fn get_temperature_and_humidity(&mut self) -> Result<(f32, f32), dhterr<EspError>> {
-        match self
-            .temperature_and_humidity_sensor
-            .perform_measurement(&mut Delay::new(80))
-        {
-            Ok(r) => return Ok(((r.temperature / 10) as f32, (r.humidity / 10) as f32)),
-            Err(e) => return Err(e),
-        }
+    pub fn synchronize_clock(&mut self) -> Result<(), PeripheralError> {
+        self.network.synchronize_clock()
+    }
+
+    pub fn get_temperature_and_humidity(&mut self) -> Result<(f32, f32), PeripheralError> {
+        self.temperature_and_humidity_sensor
+            .read_temperature_and_humidity()
     }
 
     pub fn get_temperature_and_humidity_insistently(
         &mut self,
         times: u16,
-    ) -> Result<(f32, f32), dhterr<EspError>> {
+    ) -> Result<(f32, f32), PeripheralError> {
         info!("insisting: {}", times);
         let result = self.get_temperature_and_humidity();
         if times == 0 {
@@ -111,17 +77,22 @@ impl PeripheralService {
         if result.is_err() {
             return self.get_temperature_and_humidity_insistently(times - 1);
         }
-        return result;
+        result
+    }
+
+    pub fn get_lux_measure(&mut self) -> Result<f32, PeripheralError> {
+        self.light_sensor.read_lux()
     }
 
-    pub fn get_lux_measure(&mut self) -> u32 {
-        self.bh1750
-            .start_measurement(bh1750_ehal::ContinuesMeasurement::HIHGT_RES2);
+    // returns None when the board has no pressure sensor
+    pub fn get_pressure_measure(&mut self) -> Option<Result<f64, PeripheralError>> {
+        self.pressure_sensor
+            .as_mut()
+            .map(|pressure_sensor| pressure_sensor.read_pressure())
+    }
 
-        let value = self
-            .bh1750
-            .get_measurement(bh1750_ehal::ContinuesMeasurement::HIHGT_RES2);
-        value
+    pub fn has_pressure_sensor(&self) -> bool {
+        self.pressure_sensor.is_some()
     }
 
     pub fn led_blink_3_time_short(&mut self) {
@@ -154,13 +125,23 @@ impl PeripheralService {
         self.led_blink_1_time(TIME_LONG);
     }
 
+    // blinks the LED for a while, to find the device among the others
+    pub fn identify(&mut self) {
+        for _ in 0..IDENTIFY_BLINKS {
+            self.led_blink_1_time(IDENTIFY_BLINK_MILLIS);
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.system.restart();
+    }
+
+    pub fn random(&mut self) -> u64 {
+        self.system.random()
+    }
+
     pub fn get_mac_address(&self) -> String {
-        let mav = &self
-            .wifi
-            .wifi()
-            .driver()
-            .get_mac(WifiDeviceId::Sta)
-            .unwrap();
+        let mav = self.network.get_mac().unwrap();
         let mac_address_obj =
             macaddr::MacAddr6::new(mav[0], mav[1], mav[2], mav[3], mav[4], mav[5]);
         let mac_address_value = mac_address_obj.to_string();
@@ -175,30 +156,3 @@ impl PeripheralService {
         thread_util::sleep_time(time);
     }
 }
-
-fn connect_wifi(
-    wifi: &mut BlockingWifi<EspWifi<'static>>,
-    ssid: &str,
-    password: &str,
-) -> anyhow::Result<()> {
-    let wifi_configuration: Configuration = Configuration::Client(ClientConfiguration {
-        ssid: ssid.into(),
-        bssid: None,
-        auth_method: AuthMethod::WPA2Personal,
-        password: password.into(),
-        channel: None,
-    });
-    info!("Connecting to SSID: {}", ssid);
-    wifi.set_configuration(&wifi_configuration)?;
-
-    wifi.start()?;
-    info!("Wifi started");
-
-    wifi.connect()?;
-    info!("Wifi connected: {}", ssid);
-
-    wifi.wait_netif_up()?;
-    info!("Wifi netif up");
-
-    Ok(())
-}
@@ -0,0 +1,289 @@
+use crate::{
+    dto::retry_configuration::{RetryConfiguration, RetryPolicyConfiguration},
+    error::ClientError,
+};
+use log::warn;
+use std::time::Duration;
+
+// exponential backoff: the n-th retry waits base_delay * 2^(n-1), capped at max_delay;
+// a fraction (jitter, between 0 and 1) of the delay is randomized so that the stations
+// that lost the connection together do not retry together
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+}
+
+impl RetryPolicy {
+    pub const fn new(
+        max_attempts: u32,
+        base_delay_millis: u64,
+        max_delay_millis: u64,
+        jitter: f64,
+    ) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(base_delay_millis),
+            max_delay: Duration::from_millis(max_delay_millis),
+            jitter,
+        }
+    }
+
+    // delay before the given retry (1 for the first retry), random must be in [0, 1)
+    pub fn delay_before_retry(&self, retry: u32, random: f64) -> Duration {
+        let exponent = retry.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay
+            .saturating_mul(1u32 << exponent)
+            .min(self.max_delay);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        delay.mul_f64(1.0 - jitter + jitter * random.clamp(0.0, 1.0))
+    }
+
+    pub fn execute<T>(
+        &self,
+        operation_name: &str,
+        jitter: &mut Jitter,
+        operation: impl FnMut() -> Result<T, ClientError>,
+    ) -> Result<T, ClientError> {
+        self.execute_with(
+            operation_name,
+            operation,
+            &mut std::thread::sleep,
+            &mut || jitter.next(),
+        )
+    }
+
+    // runs the operation until it succeeds, fails with a permanent error or the
+    // attempts are exhausted; sleep and random are injected to make it testable
+    pub fn execute_with<T>(
+        &self,
+        operation_name: &str,
+        mut operation: impl FnMut() -> Result<T, ClientError>,
+        sleep: &mut dyn FnMut(Duration),
+        random: &mut dyn FnMut() -> f64,
+    ) -> Result<T, ClientError> {
+        let mut attempt = 1;
+        loop {
+            let error = match operation() {
+                Ok(result) => return Ok(result),
+                Err(error) => error,
+            };
+            if attempt >= self.max_attempts || !error.is_transient() {
+                return Err(error);
+            }
+            let delay = self.delay_before_retry(attempt, random());
+            warn!(
+                "[retry]: {} failed (attempt {}/{}): {}, retrying in {} ms",
+                operation_name,
+                attempt,
+                self.max_attempts,
+                error,
+                delay.as_millis()
+            );
+            sleep(delay);
+            attempt += 1;
+        }
+    }
+}
+
+// retry policy of each call made to the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicies {
+    pub submit: RetryPolicy,
+    pub heartbeat: RetryPolicy,
+    pub configuration: RetryPolicy,
+    pub registration: RetryPolicy,
+}
+
+impl From<&RetryPolicyConfiguration> for RetryPolicy {
+    fn from(configuration: &RetryPolicyConfiguration) -> Self {
+        RetryPolicy::new(
+            configuration.max_attempts,
+            configuration.base_delay_millis,
+            configuration.max_delay_millis,
+            configuration.jitter,
+        )
+    }
+}
+
+impl From<&RetryConfiguration> for RetryPolicies {
+    fn from(configuration: &RetryConfiguration) -> Self {
+        RetryPolicies {
+            submit: (&configuration.submit).into(),
+            heartbeat: (&configuration.heartbeat).into(),
+            configuration: (&configuration.configuration).into(),
+            registration: (&configuration.registration).into(),
+        }
+    }
+}
+
+// xorshift64*, good enough to spread the retries. The seed must differ between the
+// stations, e.g. from the hardware RNG: the clocks of the stations that boot together
+// (after a power cut) are the same until SNTP synchronizes them.
+pub struct Jitter {
+    state: u64,
+}
+
+impl Jitter {
+    pub fn new(seed: u64) -> Jitter {
+        // a zero state would stay zero
+        let state = if seed == 0 { 0x9E3779B97F4A7C15 } else { seed };
+        Jitter { state }
+    }
+
+    // returns a value in [0, 1)
+    pub fn next(&mut self) -> f64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        (x.wrapping_mul(0x2545F4914F6CDD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // runs the operation with the policy, returning the result, the number of
+    // attempts and the delays slept
+    fn run(
+        policy: &RetryPolicy,
+        mut outcome: impl FnMut(u32) -> Result<u32, ClientError>,
+        random: f64,
+    ) -> (Result<u32, ClientError>, u32, Vec<Duration>) {
+        let mut attempts = 0;
+        let mut delays = Vec::new();
+        let result = policy.execute_with(
+            "test",
+            || {
+                attempts += 1;
+                outcome(attempts)
+            },
+            &mut |delay| delays.push(delay),
+            &mut || random,
+        );
+        (result, attempts, delays)
+    }
+
+    fn millis(delays: &[u64]) -> Vec<Duration> {
+        delays
+            .iter()
+            .map(|delay| Duration::from_millis(*delay))
+            .collect()
+    }
+
+    #[test]
+    fn gives_up_after_the_attempts() {
+        let policy = RetryPolicy::new(4, 100, 10000, 0.0);
+        let (result, attempts, delays) = run(&policy, |_| Err(ClientError::HttpStatus(503)), 0.5);
+        assert!(matches!(result, Err(ClientError::HttpStatus(503))));
+        assert_eq!(attempts, 4);
+        assert_eq!(delays, millis(&[100, 200, 400]));
+    }
+
+    #[test]
+    fn stops_at_the_first_success() {
+        let policy = RetryPolicy::new(5, 100, 10000, 0.0);
+        let (result, attempts, delays) = run(
+            &policy,
+            |attempt| {
+                if attempt < 3 {
+                    Err(ClientError::Timeout("timeout".into()))
+                } else {
+                    Ok(attempt)
+                }
+            },
+            0.5,
+        );
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(attempts, 3);
+        assert_eq!(delays, millis(&[100, 200]));
+    }
+
+    #[test]
+    fn does_not_retry_permanent_errors() {
+        let policy = RetryPolicy::new(5, 100, 10000, 0.0);
+        let (result, attempts, delays) = run(&policy, |_| Err(ClientError::HttpStatus(400)), 0.5);
+        assert!(matches!(result, Err(ClientError::HttpStatus(400))));
+        assert_eq!(attempts, 1);
+        assert!(delays.is_empty());
+    }
+
+    #[test]
+    fn single_attempt_never_sleeps() {
+        let policy = RetryPolicy::new(1, 100, 10000, 0.5);
+        let (_, attempts, delays) = run(&policy, |_| Err(ClientError::HttpStatus(503)), 0.5);
+        assert_eq!(attempts, 1);
+        assert!(delays.is_empty());
+    }
+
+    #[test]
+    fn delays_double_up_to_the_maximum() {
+        let policy = RetryPolicy::new(10, 500, 3000, 0.0);
+        let delays: Vec<Duration> = (1..=6)
+            .map(|retry| policy.delay_before_retry(retry, 0.9))
+            .collect();
+        assert_eq!(delays, millis(&[500, 1000, 2000, 3000, 3000, 3000]));
+        // no overflow with many retries
+        assert_eq!(
+            policy.delay_before_retry(u32::MAX, 0.9),
+            Duration::from_millis(3000)
+        );
+    }
+
+    #[test]
+    fn jitter_randomizes_a_fraction_of_the_delay() {
+        let policy = RetryPolicy::new(10, 1000, 8000, 0.5);
+        assert_eq!(
+            policy.delay_before_retry(2, 0.0),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(
+            policy.delay_before_retry(2, 0.5),
+            Duration::from_millis(1500)
+        );
+        assert!(policy.delay_before_retry(2, 0.999) <= Duration::from_millis(2000));
+        // out of range values are clamped
+        assert_eq!(
+            RetryPolicy::new(10, 1000, 8000, 3.0).delay_before_retry(1, -1.0),
+            Duration::ZERO
+        );
+        let mut jitter = Jitter::new(0x5EED);
+        for _ in 0..1000 {
+            let value = jitter.next();
+            assert!((0.0..1.0).contains(&value));
+            let delay = policy.delay_before_retry(3, value);
+            assert!(delay >= Duration::from_millis(2000) && delay <= Duration::from_millis(4000));
+        }
+    }
+
+    #[test]
+    fn policies_from_the_configuration() {
+        let configuration = RetryConfiguration {
+            heartbeat: RetryPolicyConfiguration::new(1, 250, 1000, 0.0),
+            ..Default::default()
+        };
+        let policies = RetryPolicies::from(&configuration);
+        assert_eq!(policies.heartbeat, RetryPolicy::new(1, 250, 1000, 0.0));
+        assert_eq!(policies.submit, RetryPolicy::from(&configuration.submit));
+    }
+
+    #[test]
+    fn jitter_depends_only_on_the_seed() {
+        let sequence = |seed| {
+            let mut jitter = Jitter::new(seed);
+            (0..8).map(|_| jitter.next()).collect::<Vec<f64>>()
+        };
+        assert_eq!(sequence(42), sequence(42));
+        assert_ne!(sequence(42), sequence(43));
+        // also the zero seed gives random values
+        let zero = sequence(0);
+        assert!(zero.iter().all(|value| (0.0..1.0).contains(value)));
+        assert!(zero.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+}
@@ -0,0 +1,330 @@
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use log::{error, info};
+use std::str::FromStr;
+
+// when the readings are taken and submitted: a cron expression (evaluated in UTC)
+// or a fixed interval between two readings
+pub enum MeasurementSchedule {
+    Cron(Box<Schedule>),
+    Interval(Duration),
+}
+
+impl MeasurementSchedule {
+    // uses the cron expression if present and valid, otherwise the fixed interval
+    pub fn new(crontab: Option<&str>, interval_seconds: u64) -> MeasurementSchedule {
+        let interval = MeasurementSchedule::Interval(Duration::seconds(interval_seconds as i64));
+        let crontab = match crontab {
+            None => return interval,
+            Some(crontab) if crontab.trim().is_empty() => return interval,
+            Some(crontab) => crontab,
+        };
+        match Schedule::from_str(crontab) {
+            Ok(schedule) => {
+                info!("[schedule]: readings scheduled by crontab {:?}", crontab);
+                MeasurementSchedule::Cron(Box::new(schedule))
+            }
+            Err(e) => {
+                error!(
+                    "[schedule]: invalid crontab {:?} ({}), falling back to an interval of {} seconds",
+                    crontab, e, interval_seconds
+                );
+                interval
+            }
+        }
+    }
+
+    // the time of the next reading after the given instant
+    pub fn next_after(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            MeasurementSchedule::Interval(interval) => now + *interval,
+            MeasurementSchedule::Cron(schedule) => match schedule.after(&now).next() {
+                Some(next) => next,
+                // the expression has no future occurrence (e.g. past years only)
+                None => now + Duration::days(1),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Task {
+    Heartbeat,
+    Measurement,
+    ConfigurationRefresh,
+}
+
+// interleaves the I-am-alive heartbeat and the download of the configuration, done at
+// fixed intervals, with the readings
+pub struct TaskScheduler {
+    measurement_schedule: MeasurementSchedule,
+    heartbeat_interval: Duration,
+    next_heartbeat: DateTime<Utc>,
+    next_measurement: DateTime<Utc>,
+    // None if the configuration is downloaded only at startup
+    refresh_interval: Option<Duration>,
+    next_refresh: Option<DateTime<Utc>>,
+}
+
+impl TaskScheduler {
+    // both the tasks are due immediately
+    pub fn new(
+        measurement_schedule: MeasurementSchedule,
+        heartbeat_interval_seconds: u64,
+        now: DateTime<Utc>,
+    ) -> TaskScheduler {
+        TaskScheduler {
+            measurement_schedule,
+            heartbeat_interval: Duration::seconds(heartbeat_interval_seconds as i64),
+            next_heartbeat: now,
+            next_measurement: now,
+            refresh_interval: None,
+            next_refresh: None,
+        }
+    }
+
+    // the configuration has just been downloaded: the first refresh is due after the
+    // interval, 0 disables the refresh
+    pub fn set_refresh_interval(&mut self, refresh_interval_seconds: u64, now: DateTime<Utc>) {
+        if refresh_interval_seconds == 0 {
+            self.refresh_interval = None;
+            self.next_refresh = None;
+            return;
+        }
+        let refresh_interval = Duration::seconds(refresh_interval_seconds as i64);
+        self.refresh_interval = Some(refresh_interval);
+        self.next_refresh = Some(now + refresh_interval);
+    }
+
+    // returns the tasks that are due at the given instant (heartbeat first) and
+    // schedules their next execution
+    pub fn due_tasks(&mut self, now: DateTime<Utc>) -> Vec<Task> {
+        let mut tasks = Vec::new();
+        if self.next_heartbeat <= now {
+            tasks.push(Task::Heartbeat);
+            self.next_heartbeat = now + self.heartbeat_interval;
+        }
+        if self.next_measurement <= now {
+            tasks.push(Task::Measurement);
+            self.next_measurement = self.measurement_schedule.next_after(now);
+        }
+        if let (Some(next_refresh), Some(refresh_interval)) =
+            (self.next_refresh, self.refresh_interval)
+        {
+            if next_refresh <= now {
+                tasks.push(Task::ConfigurationRefresh);
+                self.next_refresh = Some(now + refresh_interval);
+            }
+        }
+        tasks
+    }
+
+    // replaces the schedules of a running station: the tasks already planned are kept
+    // unless the new schedules want them earlier
+    pub fn reconfigure(
+        &mut self,
+        measurement_schedule: MeasurementSchedule,
+        heartbeat_interval_seconds: u64,
+        now: DateTime<Utc>,
+    ) {
+        self.heartbeat_interval = Duration::seconds(heartbeat_interval_seconds as i64);
+        self.next_heartbeat = self.next_heartbeat.min(now + self.heartbeat_interval);
+        self.next_measurement = self
+            .next_measurement
+            .min(measurement_schedule.next_after(now));
+        self.measurement_schedule = measurement_schedule;
+    }
+
+    // how long to wait, from the given instant, before the next due task
+    pub fn delay_from(&self, now: DateTime<Utc>) -> std::time::Duration {
+        let next_task = self.next_heartbeat.min(self.next_measurement);
+        let next_task = self
+            .next_refresh
+            .map_or(next_task, |next| next.min(next_task));
+        (next_task - now)
+            .to_std()
+            .unwrap_or(std::time::Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32, second: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 10, hour, minute, second)
+            .unwrap()
+    }
+
+    #[test]
+    fn interval_adds_the_seconds() {
+        let schedule = MeasurementSchedule::new(None, 90);
+        assert_eq!(schedule.next_after(at(10, 0, 0)), at(10, 1, 30));
+        assert_eq!(
+            schedule.next_after(at(23, 59, 0)),
+            at(0, 0, 30) + Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn blank_crontab_uses_the_interval() {
+        let schedule = MeasurementSchedule::new(Some("  "), 60);
+        assert!(matches!(schedule, MeasurementSchedule::Interval(_)));
+        assert_eq!(schedule.next_after(at(10, 0, 0)), at(10, 1, 0));
+    }
+
+    #[test]
+    fn cron_gives_the_next_occurrence() {
+        // every 15 minutes
+        let schedule = MeasurementSchedule::new(Some("0 */15 * * * *"), 60);
+        assert!(matches!(schedule, MeasurementSchedule::Cron(_)));
+        assert_eq!(schedule.next_after(at(10, 7, 12)), at(10, 15, 0));
+        assert_eq!(schedule.next_after(at(10, 50, 0)), at(11, 0, 0));
+    }
+
+    #[test]
+    fn cron_on_a_boundary_gives_the_following_one() {
+        let schedule = MeasurementSchedule::new(Some("0 */15 * * * *"), 60);
+        assert_eq!(schedule.next_after(at(10, 15, 0)), at(10, 30, 0));
+        assert_eq!(schedule.next_after(at(10, 14, 59)), at(10, 15, 0));
+        // the last boundary of the day moves to the next day
+        assert_eq!(
+            schedule.next_after(at(23, 45, 0)),
+            at(0, 0, 0) + Duration::days(1)
+        );
+    }
+
+    #[test]
+    fn invalid_cron_falls_back_to_the_interval() {
+        let schedule = MeasurementSchedule::new(Some("every quarter of an hour"), 300);
+        assert!(matches!(schedule, MeasurementSchedule::Interval(_)));
+        assert_eq!(schedule.next_after(at(10, 7, 0)), at(10, 12, 0));
+    }
+
+    #[test]
+    fn cron_without_future_occurrences_waits_a_day() {
+        let schedule = MeasurementSchedule::new(Some("0 0 0 1 1 * 2020"), 60);
+        assert_eq!(
+            schedule.next_after(at(10, 0, 0)),
+            at(10, 0, 0) + Duration::days(1)
+        );
+    }
+
+    fn scheduler(now: DateTime<Utc>) -> TaskScheduler {
+        // readings every 5 minutes, heartbeat every 2, refresh every 7
+        let mut scheduler = TaskScheduler::new(MeasurementSchedule::new(None, 300), 120, now);
+        scheduler.set_refresh_interval(420, now);
+        scheduler
+    }
+
+    // runs the scheduler from the given instant, waking up when it says, and returns
+    // the tasks done with the minute they were done at
+    fn run(scheduler: &mut TaskScheduler, from: DateTime<Utc>, minutes: i64) -> Vec<(i64, Task)> {
+        let mut done = Vec::new();
+        let mut now = from;
+        while now < from + Duration::minutes(minutes) {
+            for task in scheduler.due_tasks(now) {
+                done.push(((now - from).num_minutes(), task));
+            }
+            now = now + Duration::from_std(scheduler.delay_from(now)).unwrap();
+        }
+        done
+    }
+
+    #[test]
+    fn tasks_are_due_immediately_except_the_refresh() {
+        let now = at(10, 0, 0);
+        let mut scheduler = scheduler(now);
+        assert_eq!(
+            scheduler.due_tasks(now),
+            vec![Task::Heartbeat, Task::Measurement]
+        );
+        assert!(scheduler.due_tasks(now).is_empty());
+        assert_eq!(
+            scheduler.delay_from(now),
+            std::time::Duration::from_secs(120)
+        );
+    }
+
+    #[test]
+    fn interleaves_the_tasks() {
+        let now = at(10, 0, 0);
+        let mut scheduler = scheduler(now);
+        assert_eq!(
+            run(&mut scheduler, now, 15),
+            vec![
+                (0, Task::Heartbeat),
+                (0, Task::Measurement),
+                (2, Task::Heartbeat),
+                (4, Task::Heartbeat),
+                (5, Task::Measurement),
+                (6, Task::Heartbeat),
+                (7, Task::ConfigurationRefresh),
+                (8, Task::Heartbeat),
+                (10, Task::Heartbeat),
+                (10, Task::Measurement),
+                (12, Task::Heartbeat),
+                (14, Task::Heartbeat),
+                (14, Task::ConfigurationRefresh),
+            ]
+        );
+    }
+
+    #[test]
+    fn catches_up_once_after_a_long_measurement() {
+        let now = at(10, 0, 0);
+        let mut scheduler = scheduler(now);
+        scheduler.due_tasks(now);
+        // the measurement took 20 minutes: every task is late, each one is done once
+        // and planned again from the current instant, not in a burst
+        let late = now + Duration::minutes(20);
+        assert_eq!(
+            scheduler.due_tasks(late),
+            vec![
+                Task::Heartbeat,
+                Task::Measurement,
+                Task::ConfigurationRefresh
+            ]
+        );
+        assert!(scheduler.due_tasks(late).is_empty());
+        assert_eq!(
+            scheduler.delay_from(late),
+            std::time::Duration::from_secs(120)
+        );
+        assert_eq!(
+            scheduler.due_tasks(late + Duration::minutes(5)),
+            vec![Task::Heartbeat, Task::Measurement]
+        );
+    }
+
+    #[test]
+    fn refresh_disabled_by_zero() {
+        let now = at(10, 0, 0);
+        let mut scheduler = scheduler(now);
+        scheduler.set_refresh_interval(0, now);
+        let tasks = run(&mut scheduler, now, 60);
+        assert!(!tasks
+            .iter()
+            .any(|(_, task)| *task == Task::ConfigurationRefresh));
+    }
+
+    #[test]
+    fn reconfigure_keeps_the_earlier_plan() {
+        let now = at(10, 0, 0);
+        let mut scheduler = scheduler(now);
+        scheduler.due_tasks(now);
+        // a shorter interval brings the reading forward, a longer one does not delay
+        // the heartbeat already planned
+        let later = now + Duration::minutes(1);
+        scheduler.reconfigure(MeasurementSchedule::new(None, 60), 600, later);
+        assert_eq!(
+            scheduler.delay_from(later),
+            std::time::Duration::from_secs(60)
+        );
+        assert_eq!(
+            scheduler.due_tasks(now + Duration::minutes(2)),
+            vec![Task::Heartbeat, Task::Measurement]
+        );
+    }
+}
@@ -0,0 +1,451 @@
+use super::configuration_validation_service::check_server_url;
+use crate::{
+    config::config::{
+        CONFIGURATION_ERRORS_URL, CONFIGURATION_URL, DEFAULT_ALERT_URL, DEFAULT_I_AM_ALIVE_URL,
+        DEVICE_DESCRIPTION, DEVICE_NAME, REGISTER_DEVICE_URL, WIFI_PASS, WIFI_SSID,
+    },
+    hal::storage::{KeyValueStorage, StorageProvider},
+};
+use log::{error, info, warn};
+use serde_json::{Map, Value};
+
+// NVS namespace of the settings of the station
+pub const SETTINGS_NAMESPACE: &str = "settings";
+
+// longest SSID and password accepted by the Wi-Fi driver, in bytes
+const MAX_WIFI_SSID_LENGTH: usize = 32;
+const MAX_WIFI_PASSWORD_LENGTH: usize = 64;
+
+// what makes a station different from the other ones running the same firmware; the
+// constants of config.rs are only the defaults
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub wifi_ssid: String,
+    pub wifi_password: String,
+    pub register_device_url: String,
+    pub configuration_url: String,
+    pub configuration_errors_url: String,
+    // the endpoints used until the configuration has its own
+    pub alert_url: String,
+    pub i_am_alive_url: String,
+    pub device_name: String,
+    pub device_description: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            wifi_ssid: WIFI_SSID.to_owned(),
+            wifi_password: WIFI_PASS.to_owned(),
+            register_device_url: REGISTER_DEVICE_URL.to_owned(),
+            configuration_url: CONFIGURATION_URL.to_owned(),
+            configuration_errors_url: CONFIGURATION_ERRORS_URL.to_owned(),
+            alert_url: DEFAULT_ALERT_URL.to_owned(),
+            i_am_alive_url: DEFAULT_I_AM_ALIVE_URL.to_owned(),
+            device_name: DEVICE_NAME.to_owned(),
+            device_description: DEVICE_DESCRIPTION.to_owned(),
+        }
+    }
+}
+
+impl Settings {
+    // the settings with the server URLs moved to another server (scheme, host and
+    // port), e.g. the mock server on a host; the paths are kept
+    pub fn with_server(mut self, server: &str) -> Result<Settings, String> {
+        let server = server.trim_end_matches('/');
+        check_server_url(server)?;
+        for setting in SETTINGS {
+            if let SettingKind::Url = setting.kind {
+                let url = (setting.value)(&mut self);
+                let path = url
+                    .split_once("://")
+                    .and_then(|(_, rest)| rest.find('/').map(|index| &rest[index..]))
+                    .unwrap_or_default();
+                *url = format!("{}{}", server, path);
+            }
+        }
+        Ok(self)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SettingKind {
+    WifiSsid,
+    WifiPassword,
+    Url,
+    Text,
+}
+
+struct Setting {
+    // name in the updateSettings command
+    name: &'static str,
+    // NVS key, at most 15 characters
+    key: &'static str,
+    kind: SettingKind,
+    value: fn(&mut Settings) -> &mut String,
+}
+
+const SETTINGS: &[Setting] = &[
+    Setting {
+        name: "wifiSsid",
+        key: "wifi_ssid",
+        kind: SettingKind::WifiSsid,
+        value: |settings| &mut settings.wifi_ssid,
+    },
+    Setting {
+        name: "wifiPassword",
+        key: "wifi_password",
+        kind: SettingKind::WifiPassword,
+        value: |settings| &mut settings.wifi_password,
+    },
+    Setting {
+        name: "registerDeviceUrl",
+        key: "register_url",
+        kind: SettingKind::Url,
+        value: |settings| &mut settings.register_device_url,
+    },
+    Setting {
+        name: "configurationUrl",
+        key: "config_url",
+        kind: SettingKind::Url,
+        value: |settings| &mut settings.configuration_url,
+    },
+    Setting {
+        name: "configurationErrorsUrl",
+        key: "config_err_url",
+        kind: SettingKind::Url,
+        value: |settings| &mut settings.configuration_errors_url,
+    },
+    Setting {
+        name: "alertUrl",
+        key: "alert_url",
+        kind: SettingKind::Url,
+        value: |settings| &mut settings.alert_url,
+    },
+    Setting {
+        name: "iAmAliveUrl",
+        key: "alive_url",
+        kind: SettingKind::Url,
+        value: |settings| &mut settings.i_am_alive_url,
+    },
+    Setting {
+        name: "deviceName",
+        key: "device_name",
+        kind: SettingKind::Text,
+        value: |settings| &mut settings.device_name,
+    },
+    Setting {
+        name: "deviceDescription",
+        key: "device_desc",
+        kind: SettingKind::Text,
+        value: |settings| &mut settings.device_description,
+    },
+];
+
+// the settings kept in NVS, one key each: they are seeded with the defaults at the
+// first boot and can be changed at runtime, the changes are used from the next boot
+pub struct SettingsService {
+    storage: Option<Box<dyn KeyValueStorage>>,
+}
+
+impl SettingsService {
+    pub fn new(storage: Option<Box<dyn KeyValueStorage>>) -> Self {
+        SettingsService { storage }
+    }
+
+    // without the storage the defaults are used
+    pub fn open(storage_provider: &mut dyn StorageProvider) -> Self {
+        SettingsService::new(match storage_provider.open(SETTINGS_NAMESPACE) {
+            Err(e) => {
+                error!("[settings]: unable to open the settings storage: {}", e);
+                None
+            }
+            Ok(storage) => Some(storage),
+        })
+    }
+
+    // the stored settings; the missing ones (all of them at the first boot) are
+    // stored with their default value, the ones that are not valid keep the default
+    pub fn load(&mut self) -> Settings {
+        let mut settings = Settings::default();
+        let storage = match self.storage.as_mut() {
+            None => return settings,
+            Some(storage) => storage,
+        };
+        let mut seeded = 0;
+        for setting in SETTINGS {
+            let value = (setting.value)(&mut settings);
+            match storage.read(setting.key) {
+                Ok(Some(stored)) => {
+                    let stored = String::from_utf8(stored)
+                        .map_err(|e| e.to_string())
+                        .and_then(|stored| check(setting.kind, &stored).map(|_| stored));
+                    match stored {
+                        Ok(stored) => *value = stored,
+                        Err(e) => warn!(
+                            "[settings]: stored {} not valid, using the default: {}",
+                            setting.name, e
+                        ),
+                    }
+                }
+                Ok(None) => match storage.write(setting.key, value.as_bytes()) {
+                    Ok(_) => seeded += 1,
+                    Err(e) => error!("[settings]: unable to store {}: {}", setting.name, e),
+                },
+                Err(e) => error!("[settings]: unable to read {}: {}", setting.name, e),
+            }
+        }
+        if seeded > 0 {
+            info!(
+                "[settings]: {} setting(s) stored with the default value",
+                seeded
+            );
+        }
+        settings
+    }
+
+    // stores the given settings, by name; null restores the default. Nothing is
+    // stored if one of them is not valid. Returns the names of the updated settings.
+    pub fn update(&mut self, values: &Map<String, Value>) -> Result<Vec<String>, String> {
+        let storage = self
+            .storage
+            .as_mut()
+            .ok_or_else(|| "the settings storage is not available".to_owned())?;
+        let mut updates = Vec::new();
+        for (name, value) in values {
+            let setting = SETTINGS
+                .iter()
+                .find(|setting| setting.name == name)
+                .ok_or_else(|| format!("unknown setting {:?}", name))?;
+            let value = match value {
+                Value::Null => None,
+                Value::String(value) => {
+                    check(setting.kind, value).map_err(|e| format!("{}: {}", name, e))?;
+                    Some(value)
+                }
+                _ => return Err(format!("{}: expected a string or null", name)),
+            };
+            updates.push((setting, value));
+        }
+        for (setting, value) in &updates {
+            // the default is stored again at the next boot
+            let result = match value {
+                None => storage.remove(setting.key),
+                Some(value) => storage.write(setting.key, value.as_bytes()),
+            };
+            result.map_err(|e| format!("unable to store {}: {}", setting.name, e))?;
+        }
+        Ok(updates
+            .iter()
+            .map(|(setting, _)| setting.name.to_owned())
+            .collect())
+    }
+}
+
+fn check(kind: SettingKind, value: &str) -> Result<(), String> {
+    match kind {
+        SettingKind::WifiSsid if value.is_empty() || value.len() > MAX_WIFI_SSID_LENGTH => Err(
+            format!("the SSID must have 1 to {} bytes", MAX_WIFI_SSID_LENGTH),
+        ),
+        SettingKind::WifiPassword if value.len() > MAX_WIFI_PASSWORD_LENGTH => Err(format!(
+            "the password must have at most {} bytes",
+            MAX_WIFI_PASSWORD_LENGTH
+        )),
+        SettingKind::Url => check_server_url(value),
+        SettingKind::Text if value.trim().is_empty() => Err("it must not be empty".to_owned()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(all(test, not(feature = "hal")))]
+mod tests {
+    use super::*;
+    use crate::hal::host::storage::InMemoryStorageProvider;
+    use serde_json::json;
+
+    fn values(values: Value) -> Map<String, Value> {
+        values.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn load_seeds_the_missing_settings_with_the_defaults() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut storage = provider.open(SETTINGS_NAMESPACE).unwrap();
+        storage.write("device_name", b"Roof").unwrap();
+        let mut service = SettingsService::open(&mut provider);
+
+        let settings = service.load();
+        assert_eq!(settings.device_name, "Roof");
+        assert_eq!(settings.wifi_ssid, WIFI_SSID);
+        // every setting is now stored, the stored one is not overwritten
+        for setting in SETTINGS {
+            assert!(
+                storage.read(setting.key).unwrap().is_some(),
+                "{}",
+                setting.key
+            );
+        }
+        assert_eq!(storage.read("device_name").unwrap(), Some(b"Roof".to_vec()));
+        assert_eq!(
+            storage.read("wifi_ssid").unwrap(),
+            Some(WIFI_SSID.as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn load_reads_back_the_stored_settings() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut service = SettingsService::open(&mut provider);
+        assert_eq!(service.load(), Settings::default());
+        service
+            .update(&values(json!({
+                "wifiSsid": "a",
+                "wifiPassword": "",
+                "deviceName": "Roof",
+                "configurationUrl": "https://server/configuration"
+            })))
+            .unwrap();
+
+        // at the next boot
+        let settings = SettingsService::open(&mut provider).load();
+        assert_eq!(
+            settings,
+            Settings {
+                wifi_ssid: "a".to_owned(),
+                wifi_password: "".to_owned(),
+                device_name: "Roof".to_owned(),
+                configuration_url: "https://server/configuration".to_owned(),
+                ..Settings::default()
+            }
+        );
+    }
+
+    #[test]
+    fn load_uses_the_default_of_a_stored_setting_that_is_not_valid() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut storage = provider.open(SETTINGS_NAMESPACE).unwrap();
+        storage.write("config_url", b"ftp://server").unwrap();
+        storage.write("device_name", &[0xFF, 0xFE]).unwrap();
+
+        let settings = SettingsService::open(&mut provider).load();
+        assert_eq!(settings.configuration_url, CONFIGURATION_URL);
+        assert_eq!(settings.device_name, DEVICE_NAME);
+    }
+
+    #[test]
+    fn update_returns_the_names_of_the_updated_settings() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut service = SettingsService::open(&mut provider);
+        service.load();
+        let mut updated = service
+            .update(&values(json!({ "deviceName": "Roof", "wifiSsid": "home" })))
+            .unwrap();
+        updated.sort();
+        assert_eq!(updated, ["deviceName", "wifiSsid"]);
+    }
+
+    #[test]
+    fn update_stores_nothing_when_one_value_is_not_valid() {
+        let documents = [
+            json!({ "deviceName": "Roof", "configurationUrl": "server/configuration" }),
+            json!({ "deviceName": "Roof", "deviceDescription": " " }),
+            json!({ "deviceName": "Roof", "wifiSsid": 42 }),
+            json!({ "deviceName": "Roof", "unknown": "value" }),
+        ];
+        for rejected in documents {
+            let mut provider = InMemoryStorageProvider::new();
+            let mut service = SettingsService::open(&mut provider);
+            service.load();
+            let result = service.update(&values(rejected.clone()));
+            assert!(result.is_err(), "{}", rejected);
+            assert_eq!(
+                SettingsService::open(&mut provider).load(),
+                Settings::default(),
+                "{}",
+                rejected
+            );
+        }
+    }
+
+    #[test]
+    fn null_restores_the_default_at_the_next_load() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut service = SettingsService::open(&mut provider);
+        service.load();
+        service
+            .update(&values(json!({ "deviceName": "Roof" })))
+            .unwrap();
+        assert_eq!(service.load().device_name, "Roof");
+
+        service
+            .update(&values(json!({ "deviceName": null })))
+            .unwrap();
+        let mut storage = provider.open(SETTINGS_NAMESPACE).unwrap();
+        assert_eq!(storage.read("device_name").unwrap(), None);
+        assert_eq!(service.load().device_name, DEVICE_NAME);
+        // and the default is stored again
+        assert_eq!(
+            storage.read("device_name").unwrap(),
+            Some(DEVICE_NAME.as_bytes().to_vec())
+        );
+    }
+
+    #[test]
+    fn wifi_credentials_are_limited_to_the_length_of_the_driver() {
+        let mut provider = InMemoryStorageProvider::new();
+        let mut service = SettingsService::open(&mut provider);
+        let accepted = json!({ "wifiSsid": "s".repeat(32), "wifiPassword": "p".repeat(64) });
+        service.update(&values(accepted)).unwrap();
+
+        let error = service
+            .update(&values(json!({ "wifiSsid": "s".repeat(33) })))
+            .unwrap_err();
+        assert!(error.starts_with("wifiSsid:"), "{}", error);
+        let error = service
+            .update(&values(json!({ "wifiPassword": "p".repeat(65) })))
+            .unwrap_err();
+        assert!(error.starts_with("wifiPassword:"), "{}", error);
+        assert!(service.update(&values(json!({ "wifiSsid": "" }))).is_err());
+
+        let settings = service.load();
+        assert_eq!(settings.wifi_ssid, "s".repeat(32));
+        assert_eq!(settings.wifi_password, "p".repeat(64));
+    }
+
+    #[test]
+    fn without_the_storage_the_defaults_are_used() {
+        let mut service = SettingsService::new(None);
+        assert_eq!(service.load(), Settings::default());
+        assert!(service
+            .update(&values(json!({ "deviceName": "Roof" })))
+            .is_err());
+    }
+
+    #[test]
+    fn with_server_keeps_the_paths() {
+        let settings = Settings {
+            register_device_url: "https://192.168.1.102:8443/api/v1/device/register".to_owned(),
+            alert_url: "https://server/api/v1/weather-sensor/submit?x=1".to_owned(),
+            i_am_alive_url: "https://server".to_owned(),
+            ..Settings::default()
+        }
+        .with_server("http://localhost:8080/")
+        .unwrap();
+        assert_eq!(
+            settings.register_device_url,
+            "http://localhost:8080/api/v1/device/register"
+        );
+        assert_eq!(
+            settings.alert_url,
+            "http://localhost:8080/api/v1/weather-sensor/submit?x=1"
+        );
+        assert_eq!(settings.i_am_alive_url, "http://localhost:8080");
+        assert_eq!(settings.device_name, Settings::default().device_name);
+    }
+
+    #[test]
+    fn with_server_rejects_an_invalid_server() {
+        assert!(Settings::default().with_server("localhost:8080").is_err());
+        assert!(Settings::default().with_server("http://").is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+pub const NONCE_HEADER: &str = "x-signature-nonce";
+
+// HMAC-SHA256 (lowercase hex) of "<timestamp>.<nonce>.<body>", where the timestamp is
+// in milliseconds since the epoch: the server recomputes it to reject tampered bodies,
+// and rejects old timestamps and nonces not greater than the last one to stop replays
+pub fn sign(key: &[u8], timestamp_millis: i64, nonce: u64, body: &[u8]) -> String {
+    let prefix = format!("{}.{}.", timestamp_millis, nonce);
+    to_hex(&hmac_sha256(key, &[prefix.as_bytes(), body]))
+}
+
+// HMAC-SHA256 of the concatenation of the parts
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+// short digest of a content (the first 8 bytes of its SHA-256), to tell its versions
+// apart when the server does not send an ETag
+pub fn content_version(content: &[u8]) -> String {
+    to_hex(&Sha256::digest(content)[..8])
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // test cases of RFC 4231, section 4 (the 5th one is truncated, it is left out)
+    #[test]
+    fn hmac_sha256_rfc_4231() {
+        let key_1_to_25: Vec<u8> = (1..=25).collect();
+        let cases: [(&[u8], &[u8], &str); 6] = [
+            (
+                &[0x0b; 20],
+                b"Hi There",
+                "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            ),
+            (
+                b"Jefe",
+                b"what do ya want for nothing?",
+                "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            ),
+            (
+                &[0xaa; 20],
+                &[0xdd; 50],
+                "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe",
+            ),
+            (
+                &key_1_to_25,
+                &[0xcd; 50],
+                "82558a389a443c0ea4cc819899f2083a85f0faa3e578f8077a2e3ff46729665b",
+            ),
+            (
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First",
+                "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54",
+            ),
+            (
+                &[0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size data. The key needs to be hashed before being used by the HMAC algorithm.",
+                "9b09ffa71b942fcb27635fbcd5b0e944bfdc63644f0713938a7f51535c3a35e2",
+            ),
+        ];
+        for (key, data, expected) in cases {
+            assert_eq!(to_hex(&hmac_sha256(key, &[data])), expected);
+            // the parts are concatenated
+            let (head, tail) = data.split_at(data.len() / 2);
+            assert_eq!(to_hex(&hmac_sha256(key, &[head, tail])), expected);
+        }
+    }
+
+    // the example of the README, the server must compute the same
+    #[test]
+    fn sign_the_canonical_request() {
+        let body = br#"{"macAddress":"AA:BB:CC:DD:EE:FF"}"#;
+        assert_eq!(
+            sign(b"key", 1700000000000, 42, body),
+            "313d3d5d6924cf447f58f221c1e4731bf1e23b62b0e60a8b1d9fb71f32205e18"
+        );
+        assert_eq!(
+            sign(b"key", 1700000000000, 42, body),
+            to_hex(&hmac_sha256(
+                b"key",
+                &[b"1700000000000.42.{\"macAddress\":\"AA:BB:CC:DD:EE:FF\"}"]
+            ))
+        );
+        // every part is signed
+        assert_ne!(
+            sign(b"key", 1700000000001, 42, body),
+            sign(b"key", 1700000000000, 42, body)
+        );
+        assert_ne!(
+            sign(b"key", 1700000000000, 43, body),
+            sign(b"key", 1700000000000, 42, body)
+        );
+        assert_ne!(
+            sign(b"other", 1700000000000, 42, body),
+            sign(b"key", 1700000000000, 42, body)
+        );
+    }
+
+    #[test]
+    fn content_version_is_the_start_of_the_sha256() {
+        assert_eq!(content_version(b"abc"), "ba7816bf8f01cfea");
+    }
+}
@@ -0,0 +1,69 @@
+use super::client_service::{post_registration, post_request, HttpSession};
+use crate::error::ClientError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageKind {
+    Registration,
+    Measurement,
+    Heartbeat,
+}
+
+// delivers the messages of the device to the server, the payloads are JSON; a single
+// attempt, the client service retries the transient errors. The HTTP session is the
+// one of the device, for the transports that talk with the server over HTTP.
+pub trait MessageTransport {
+    // returns the body of the response, empty if the transport has no responses
+    fn send(
+        &mut self,
+        session: &mut HttpSession,
+        kind: MessageKind,
+        payload: &[u8],
+    ) -> Result<String, ClientError>;
+}
+
+// posts each message to its endpoint, authenticated and signed
+pub struct HttpMessageTransport {
+    register_device_url: String,
+    alert_url: String,
+    i_am_alive_url: String,
+}
+
+impl HttpMessageTransport {
+    pub fn new(
+        register_device_url: &str,
+        alert_url: &str,
+        i_am_alive_url: &str,
+    ) -> HttpMessageTransport {
+        HttpMessageTransport {
+            register_device_url: register_device_url.to_owned(),
+            alert_url: alert_url.to_owned(),
+            i_am_alive_url: i_am_alive_url.to_owned(),
+        }
+    }
+}
+
+impl MessageTransport for HttpMessageTransport {
+    fn send(
+        &mut self,
+        session: &mut HttpSession,
+        kind: MessageKind,
+        payload: &[u8],
+    ) -> Result<String, ClientError> {
+        let transport = session.transport.as_mut();
+        let credentials = &mut session.credentials;
+        let url = match kind {
+            MessageKind::Registration => {
+                return post_registration(
+                    transport,
+                    payload,
+                    &self.register_device_url,
+                    credentials,
+                )
+                .map(|_| String::new())
+            }
+            MessageKind::Measurement => &self.alert_url,
+            MessageKind::Heartbeat => &self.i_am_alive_url,
+        };
+        post_request(transport, payload, url, credentials)
+    }
+}
@@ -0,0 +1,34 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+// prints the log records on stderr, used when running on a Linux host
+struct HostLogger;
+
+static LOGGER: HostLogger = HostLogger;
+
+impl Log for HostLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        // the simulator runs a station per thread
+        match std::thread::current().name() {
+            Some(name) if name != "main" => eprintln!(
+                "{} [{}] ({}) {}",
+                record.level(),
+                name,
+                record.target(),
+                record.args()
+            ),
+            _ => eprintln!("{} ({}) {}", record.level(), record.target(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub fn initialize_default() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
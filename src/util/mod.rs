@@ -1 +1,3 @@
+#[cfg(not(feature = "hal"))]
+pub mod host_logger;
 pub mod thread_util;
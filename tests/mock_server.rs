@@ -0,0 +1,285 @@
+// Runs a simulated station against the mock server, both started as separate processes:
+// each test has its own mock on a free port, so the tests can run in parallel.
+//
+// cargo +stable test --target x86_64-unknown-linux-gnu --no-default-features \
+//     --features std,mock-server,simulator --test mock_server
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+// the station of the simulator with --first-station 1
+const MAC_ADDRESS: &str = "02:53:49:4D:00:01";
+// a station that does not stop in time is killed
+const STATION_TIMEOUT: Duration = Duration::from_secs(90);
+
+struct MockServer {
+    process: Child,
+    port: u16,
+}
+
+impl MockServer {
+    fn start() -> MockServer {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_mock_server"))
+            .args(["--port", "0"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("unable to start the mock server");
+        let mut stdout = BufReader::new(process.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).unwrap();
+        let port = line
+            .trim()
+            .strip_prefix("mock server listening on port ")
+            .and_then(|port| port.parse().ok())
+            .unwrap_or_else(|| panic!("unexpected output of the mock server: {:?}", line));
+        // the mock logs every request, a full pipe would block it
+        thread::spawn(move || io_sink(stdout));
+        MockServer { process, port }
+    }
+
+    fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    fn post(&self, path: &str, body: Value) {
+        let (status, response) = self.request("POST", path, &body.to_string());
+        assert!(
+            (200..=204).contains(&status),
+            "POST {} failed with {}: {}",
+            path,
+            status,
+            response
+        );
+    }
+
+    // the payloads received by the endpoint, in order
+    fn received(&self, endpoint: &str) -> Vec<Value> {
+        let (status, response) = self.request("GET", "/mock/received", "");
+        assert_eq!(status, 200);
+        let received: Vec<Value> = serde_json::from_str(&response).unwrap();
+        received
+            .into_iter()
+            .filter(|payload| payload["endpoint"] == endpoint)
+            .collect()
+    }
+
+    fn request(&self, method: &str, path: &str, body: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nhost: 127.0.0.1:{}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+            method,
+            path,
+            self.port,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response
+            .split(' ')
+            .nth(1)
+            .and_then(|status| status.parse().ok())
+            .unwrap_or_else(|| panic!("unexpected response: {:?}", response));
+        let body = response
+            .split_once("\r\n\r\n")
+            .map_or("", |(_, body)| body)
+            .to_owned();
+        (status, body)
+    }
+
+    // runs one station for the given number of cycles, until it stops
+    fn run_station(&self, cycles: u32) {
+        let mut station = Command::new(env!("CARGO_BIN_EXE_simulator"))
+            .args(["--server", &self.url()])
+            .args(["--cycles", &cycles.to_string()])
+            .args(["--dropout", "0", "--log-level", "warn"])
+            .spawn()
+            .expect("unable to start the simulator");
+        let started = Instant::now();
+        loop {
+            if let Some(status) = station.try_wait().unwrap() {
+                assert!(status.success(), "the simulator failed: {}", status);
+                return;
+            }
+            if started.elapsed() > STATION_TIMEOUT {
+                station.kill().ok();
+                panic!("the station did not stop after {} cycle(s)", cycles);
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.process.kill().ok();
+        self.process.wait().ok();
+    }
+}
+
+fn io_sink(mut stdout: impl Read) {
+    std::io::copy(&mut stdout, &mut std::io::sink()).ok();
+}
+
+// fast schedules and retries, so that the tests take a few seconds
+fn fast_configuration() -> Value {
+    json!({
+        "weatherSensorSupplyIntervalSeconds": 1,
+        "iAmAliveIntervalSeconds": 1,
+        "retryPolicies": {
+            "submit": { "maxAttempts": 3, "baseDelayMillis": 10, "maxDelayMillis": 50 },
+            "heartbeat": { "maxAttempts": 2, "baseDelayMillis": 10, "maxDelayMillis": 50 }
+        }
+    })
+}
+
+fn bearer(payload: &Value) -> &str {
+    payload["authorization"].as_str().unwrap_or_default()
+}
+
+#[test]
+fn station_registers_downloads_the_configuration_and_sends_the_measurements() {
+    let mock = MockServer::start();
+    mock.post("/mock/configuration", fast_configuration());
+    mock.run_station(2);
+
+    let register = mock.received("register");
+    assert_eq!(register.len(), 1);
+    assert_eq!(register[0]["body"]["macAddress"], MAC_ADDRESS);
+    assert_eq!(bearer(&register[0]), "");
+
+    let configuration = mock.received("configuration");
+    assert_eq!(configuration.len(), 1);
+    assert_eq!(configuration[0]["body"]["macAddress"], MAC_ADDRESS);
+    let token = bearer(&configuration[0]).to_owned();
+    assert!(token.starts_with("Bearer mock-"), "{}", token);
+
+    // with the same interval both the tasks run in each cycle
+    let heartbeats = mock.received("i-am-alive");
+    let submissions = mock.received("submit");
+    assert_eq!(heartbeats.len(), 2);
+    assert_eq!(submissions.len(), 2);
+    for payload in heartbeats.iter().chain(&submissions) {
+        assert_eq!(payload["body"]["macAddress"], MAC_ADDRESS);
+        assert_eq!(bearer(payload), token);
+    }
+    for submission in &submissions {
+        assert!(
+            submission["body"]["temperature"].is_number(),
+            "{}",
+            submission
+        );
+        assert!(submission["body"]["humidity"].is_number(), "{}", submission);
+    }
+}
+
+#[test]
+fn failed_submission_is_retried_while_the_behaviour_lasts() {
+    let mock = MockServer::start();
+    mock.post("/mock/configuration", fast_configuration());
+    mock.post(
+        "/mock/behaviours",
+        json!({ "endpoint": "submit", "status": 503, "times": 2 }),
+    );
+    mock.run_station(1);
+
+    // the third attempt succeeds, with the same measurement
+    let submissions = mock.received("submit");
+    assert_eq!(submissions.len(), 3);
+    assert!(submissions
+        .iter()
+        .all(|submission| submission["body"] == submissions[0]["body"]));
+}
+
+#[test]
+fn submission_failing_forever_stops_at_the_attempts_of_the_policy() {
+    let mock = MockServer::start();
+    mock.post("/mock/configuration", fast_configuration());
+    mock.post(
+        "/mock/behaviours",
+        json!({ "endpoint": "submit", "status": 500 }),
+    );
+    mock.run_station(1);
+
+    assert_eq!(mock.received("submit").len(), 3);
+    // the heartbeat is not affected
+    assert_eq!(mock.received("i-am-alive").len(), 1);
+}
+
+#[test]
+fn failed_registration_is_retried() {
+    let mock = MockServer::start();
+    mock.post("/mock/configuration", fast_configuration());
+    mock.post(
+        "/mock/behaviours",
+        json!({ "endpoint": "register", "status": 502, "times": 1 }),
+    );
+    mock.run_station(1);
+
+    assert_eq!(mock.received("register").len(), 2);
+    // the mock issues a token also with the failure, the second one is used
+    let configuration = mock.received("configuration");
+    assert_eq!(configuration.len(), 1);
+    assert!(bearer(&configuration[0]).starts_with("Bearer mock-2-"));
+    assert_eq!(mock.received("submit").len(), 1);
+}
+
+#[test]
+fn delayed_heartbeat_is_not_retried() {
+    let mock = MockServer::start();
+    mock.post("/mock/configuration", fast_configuration());
+    mock.post(
+        "/mock/behaviours",
+        json!({ "endpoint": "i-am-alive", "delayMillis": 500, "times": 1 }),
+    );
+    let started = Instant::now();
+    mock.run_station(1);
+
+    assert!(started.elapsed() >= Duration::from_millis(500));
+    assert_eq!(mock.received("i-am-alive").len(), 1);
+    assert_eq!(mock.received("submit").len(), 1);
+}
+
+#[test]
+fn malformed_configuration_falls_back_to_the_defaults() {
+    let mock = MockServer::start();
+    mock.post(
+        "/mock/behaviours",
+        json!({ "endpoint": "configuration", "malformed": true }),
+    );
+    mock.run_station(1);
+
+    // a body that is not JSON is not retried, the compiled configuration moved to the
+    // mock by --server is used
+    assert_eq!(mock.received("configuration").len(), 1);
+    assert_eq!(mock.received("i-am-alive").len(), 1);
+    assert_eq!(mock.received("submit").len(), 1);
+}
+
+#[test]
+fn firmware_update_is_acknowledged_as_failed_on_the_host() {
+    let mock = MockServer::start();
+    mock.post("/mock/configuration", fast_configuration());
+    let firmware_url = format!("{}/firmware.bin", mock.url());
+    mock.post(
+        "/mock/commands",
+        json!({ "id": "ota-1", "type": "startOta", "url": firmware_url }),
+    );
+    mock.run_station(2);
+
+    // the command comes with the first heartbeat, its outcome goes with the second one
+    let heartbeats = mock.received("i-am-alive");
+    assert_eq!(heartbeats.len(), 2);
+    let acknowledgements = &heartbeats[1]["body"]["acknowledgedCommands"];
+    assert_eq!(acknowledgements[0]["id"], "ota-1");
+    assert_eq!(acknowledgements[0]["status"], "failed");
+    let message = acknowledgements[0]["message"].as_str().unwrap();
+    assert!(message.contains("not supported"), "{}", message);
+}